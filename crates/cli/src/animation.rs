@@ -0,0 +1,240 @@
+use serde::Deserialize;
+
+use crate::theme::Color;
+
+/// Longest step a frame may advance animations by, so a surface waking up after being idle
+/// carries on where it stopped instead of jumping ahead.
+const MAX_STEP_MS: u32 = 100;
+
+/// How an animation's progress is spread over its duration.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Easing {
+    Linear,
+    EaseIn,
+    #[default]
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// The eased progress for linear progress `t`, both from 0 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// Values a [`Tween`] can move between.
+pub(crate) trait Lerp: Copy {
+    /// The value `t` of the way from `self` to `to`.
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+/// A position.
+impl Lerp for (f32, f32) {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        (self.0.lerp(to.0, t), self.1.lerp(to.1, t))
+    }
+}
+
+/// Each straight channel on its own.
+impl Lerp for Color {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        let channels = self.0.to_be_bytes().map(f32::from);
+        let target = to.0.to_be_bytes().map(f32::from);
+        let mixed: [u8; 4] =
+            std::array::from_fn(|i| channels[i].lerp(target[i], t).round().clamp(0.0, 255.0) as u8);
+        Color(u32::from_be_bytes(mixed))
+    }
+}
+
+/// A value moving from one end to the other over `duration` milliseconds of frame time.
+///
+/// The clock starts at the first [`Tween::update`], the frame the tween is first drawn in,
+/// as frame callback timestamps have no known relation to any other clock.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Tween<T> {
+    from: T,
+    to: T,
+    duration: u32,
+    easing: Easing,
+    start: Option<u32>,
+    value: T,
+    done: bool,
+}
+
+impl<T: Lerp> Tween<T> {
+    pub fn new(from: T, to: T, duration: u32, easing: Easing) -> Self {
+        Tween {
+            from,
+            to,
+            duration,
+            easing,
+            start: None,
+            value: if duration == 0 { to } else { from },
+            done: duration == 0,
+        }
+    }
+
+    /// A tween resting at `value`.
+    pub fn fixed(value: T) -> Self {
+        Self::new(value, value, 0, Easing::Linear)
+    }
+
+    /// Continue from wherever this one is now towards `to`, as when it changes direction.
+    pub fn retarget(&self, to: T, duration: u32, easing: Easing) -> Self {
+        Self::new(self.value, to, duration, easing)
+    }
+
+    /// Advance to the frame callback timestamp `time`, in milliseconds.
+    pub fn update(&mut self, time: u32) {
+        let start = *self.start.get_or_insert(time);
+        let t = match self.duration {
+            0 => 1.0,
+            duration => time.wrapping_sub(start) as f32 / duration as f32,
+        };
+        self.value = self.from.lerp(self.to, self.easing.apply(t));
+        self.done = t >= 1.0;
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Whether the value reached its target, so frames no longer need drawing for it.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Turns frame callback timestamps into the time passed since the previous frame.
+#[derive(Debug, Default)]
+pub(crate) struct FrameClock {
+    last: Option<u32>,
+}
+
+impl FrameClock {
+    /// Seconds since the last frame, none for the first one.
+    pub fn tick(&mut self, time: u32) -> f32 {
+        let elapsed = self
+            .last
+            .map_or(0, |last| time.wrapping_sub(last).min(MAX_STEP_MS));
+        self.last = Some(time);
+        elapsed as f32 / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    #[test]
+    fn easings_run_from_0_to_1_without_turning_back() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+            // Progress outside the duration is clamped.
+            assert_eq!(easing.apply(-0.5), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(1.5), 1.0, "{easing:?}");
+
+            let mut previous = 0.0;
+            for step in 1..=100 {
+                let eased = easing.apply(step as f32 / 100.0);
+                assert!(eased >= previous, "{easing:?} turned back at {step}");
+                previous = eased;
+            }
+        }
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+    }
+
+    #[test]
+    fn tween_starts_at_its_first_frame_and_ends_after_its_duration() {
+        let mut tween = Tween::new(10.0, 20.0, 200, Easing::Linear);
+        assert_eq!(tween.value(), 10.0);
+        tween.update(5000);
+        assert_eq!(tween.value(), 10.0);
+        tween.update(5050);
+        assert_eq!(tween.value(), 12.5);
+        assert!(!tween.is_done());
+        tween.update(5200);
+        assert_eq!(tween.value(), 20.0);
+        assert!(tween.is_done());
+        // A late frame does not overshoot.
+        tween.update(6000);
+        assert_eq!(tween.value(), 20.0);
+
+        // Timestamps wrap around after about 49 days.
+        let mut tween = Tween::new(0.0, 1.0, 100, Easing::Linear);
+        tween.update(u32::MAX - 49);
+        tween.update(0);
+        assert_eq!(tween.value(), 0.5);
+    }
+
+    #[test]
+    fn retargeting_continues_from_the_current_value() {
+        let mut tween = Tween::new((0.0, 0.0), (100.0, 50.0), 100, Easing::Linear);
+        tween.update(0);
+        tween.update(40);
+        let mut back = tween.retarget((0.0, 0.0), 100, Easing::EaseOut);
+        assert_eq!(back.value(), (40.0, 20.0));
+        back.update(1000);
+        assert_eq!(back.value(), (40.0, 20.0));
+        back.update(1100);
+        assert_eq!(back.value(), (0.0, 0.0));
+        assert!(back.is_done());
+    }
+
+    #[test]
+    fn fixed_and_instant_tweens_are_done() {
+        let tween = Tween::fixed(3.0);
+        assert!(tween.is_done());
+        assert_eq!(tween.value(), 3.0);
+
+        let mut instant = Tween::new(0.0, 1.0, 0, Easing::EaseIn);
+        assert!(instant.is_done());
+        assert_eq!(instant.value(), 1.0);
+        instant.update(7);
+        assert_eq!(instant.value(), 1.0);
+    }
+
+    #[test]
+    fn colors_mix_each_channel() {
+        let from = Color(0xFF_00_80_FF);
+        let to = Color(0x00_FF_80_00);
+        assert_eq!(from.lerp(to, 0.0), from);
+        assert_eq!(from.lerp(to, 1.0), to);
+        assert_eq!(from.lerp(to, 0.5), Color(0x80_80_80_80));
+    }
+
+    #[test]
+    fn frame_clock_measures_and_caps_steps() {
+        let mut clock = FrameClock::default();
+        assert_eq!(clock.tick(1000), 0.0);
+        assert_eq!(clock.tick(1016), 0.016);
+        // Woken up after a while: no jump.
+        assert_eq!(clock.tick(60_000), MAX_STEP_MS as f32 / 1000.0);
+        assert_eq!(clock.tick(60_010), 0.01);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    ptr,
+};
+
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+#[derive(Error, Debug)]
+pub(crate) enum AuthError {
+    #[error("authentication failed: {0}")]
+    Failed(String),
+    #[error("user or password contains a NUL byte")]
+    InvalidInput,
+}
+
+/// Verifies a user's password.
+///
+/// The locker only talks to this trait, so it can run against a mock instead of the system's PAM stack.
+pub(crate) trait Authenticator: Send + Sync {
+    fn authenticate(&self, user: &str, password: &str) -> Result<(), AuthError>;
+}
+
+/// Accepts a single password, recording every one it was given.
+#[cfg(test)]
+pub(crate) struct MockAuthenticator {
+    password: String,
+    attempts: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MockAuthenticator {
+    pub fn new(password: &str) -> Self {
+        MockAuthenticator {
+            password: password.to_string(),
+            attempts: Default::default(),
+        }
+    }
+
+    pub fn attempts(&self) -> Vec<String> {
+        self.attempts.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Authenticator for MockAuthenticator {
+    fn authenticate(&self, _user: &str, password: &str) -> Result<(), AuthError> {
+        self.attempts.lock().unwrap().push(password.to_string());
+        if password == self.password {
+            Ok(())
+        } else {
+            Err(AuthError::Failed("wrong password".to_string()))
+        }
+    }
+}
+
+/// Authenticates through PAM with the given service name, e.g. `login`.
+pub(crate) struct PamAuthenticator {
+    pub(crate) service: String,
+}
+
+impl Authenticator for PamAuthenticator {
+    fn authenticate(&self, user: &str, password: &str) -> Result<(), AuthError> {
+        let service = CString::new(self.service.as_str()).map_err(|_| AuthError::InvalidInput)?;
+        let user = CString::new(user).map_err(|_| AuthError::InvalidInput)?;
+        // Wiped once the transaction is over, along with the copy the locker passed in.
+        let password = Zeroizing::new(CString::new(password).map_err(|_| AuthError::InvalidInput)?);
+
+        let conversation = PamConv {
+            conv: converse,
+            appdata_ptr: password.as_ptr() as *mut c_void,
+        };
+
+        let mut handle = ptr::null_mut();
+        // SAFETY: all pointers outlive the PAM transaction, which ends with `pam_end` below.
+        unsafe {
+            let status = pam_start(service.as_ptr(), user.as_ptr(), &conversation, &mut handle);
+            if status != PAM_SUCCESS {
+                return Err(AuthError::Failed(strerror(handle, status)));
+            }
+
+            let mut status = pam_authenticate(handle, 0);
+            if status == PAM_SUCCESS {
+                status = pam_acct_mgmt(handle, 0);
+            }
+            let result = match status {
+                PAM_SUCCESS => Ok(()),
+                _ => Err(AuthError::Failed(strerror(handle, status))),
+            };
+
+            pam_end(handle, status);
+            result
+        }
+    }
+}
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_CONV_ERR: c_int = 19;
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: extern "C" fn(c_int, *mut *const PamMessage, *mut *mut PamResponse, *mut c_void) -> c_int,
+    appdata_ptr: *mut c_void,
+}
+
+#[link(name = "pam")]
+extern "C" {
+    fn pam_start(
+        service_name: *const c_char,
+        user: *const c_char,
+        pam_conversation: *const PamConv,
+        pamh: *mut *mut c_void,
+    ) -> c_int;
+    fn pam_authenticate(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_acct_mgmt(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_end(pamh: *mut c_void, pam_status: c_int) -> c_int;
+    fn pam_strerror(pamh: *mut c_void, errnum: c_int) -> *const c_char;
+}
+
+/// Answers every password prompt with the password passed through `appdata`.
+extern "C" fn converse(
+    count: c_int,
+    messages: *mut *const PamMessage,
+    responses: *mut *mut PamResponse,
+    appdata: *mut c_void,
+) -> c_int {
+    let Ok(count) = usize::try_from(count) else {
+        return PAM_CONV_ERR;
+    };
+
+    // SAFETY: PAM hands us `count` messages and takes ownership of the `calloc`ed responses.
+    unsafe {
+        let replies = libc::calloc(count, std::mem::size_of::<PamResponse>()) as *mut PamResponse;
+        if replies.is_null() {
+            return PAM_BUF_ERR;
+        }
+
+        for index in 0..count {
+            let message = &**messages.add(index);
+            let reply = &mut *replies.add(index);
+            if matches!(message.msg_style, PAM_PROMPT_ECHO_OFF | PAM_PROMPT_ECHO_ON) {
+                reply.resp = libc::strdup(appdata as *const c_char);
+                if reply.resp.is_null() {
+                    free_responses(replies, index);
+                    return PAM_BUF_ERR;
+                }
+            }
+        }
+
+        *responses = replies;
+    }
+    PAM_SUCCESS
+}
+
+unsafe fn free_responses(replies: *mut PamResponse, count: usize) {
+    for index in 0..count {
+        libc::free((*replies.add(index)).resp as *mut c_void);
+    }
+    libc::free(replies as *mut c_void);
+}
+
+unsafe fn strerror(handle: *mut c_void, status: c_int) -> String {
+    let message = pam_strerror(handle, status);
+    if message.is_null() {
+        format!("PAM error {status}")
+    } else {
+        CStr::from_ptr(message).to_string_lossy().into_owned()
+    }
+}
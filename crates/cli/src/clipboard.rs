@@ -0,0 +1,223 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Write},
+    os::fd::OwnedFd,
+};
+
+use smithay_client_toolkit::{
+    data_device_manager::{
+        data_device::{DataDevice, DataDeviceData, DataDeviceDataExt},
+        data_source::{CopyPasteSource, DataSourceData},
+        DataDeviceManagerState, WritePipe,
+    },
+    globals::GlobalData,
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    reexports::client::{
+        globals::GlobalList,
+        protocol::{
+            wl_data_device::WlDataDevice, wl_data_device_manager::WlDataDeviceManager,
+            wl_data_source::WlDataSource, wl_seat,
+        },
+        Dispatch, QueueHandle,
+    },
+};
+use tracing::{info, warn};
+
+/// Mime types used when offering or receiving plain text, in order of preference.
+pub(crate) const TEXT_MIME_TYPES: [&str; 2] = ["text/plain;charset=utf-8", "UTF8_STRING"];
+
+/// Writes of at most `PIPE_BUF` bytes never block once the pipe is reported writable.
+const PIPE_CHUNK: usize = 4096;
+
+/// Copy and paste through `wl_data_device`.
+///
+/// Pipes are serviced from the calloop loop, so transfers of any size never stall drawing.
+pub(crate) struct Clipboard {
+    pub(crate) manager: Option<DataDeviceManagerState>,
+    pub(crate) device: Option<DataDevice>,
+    pub(crate) source: Option<CopyPasteSource>,
+    pub(crate) contents: String,
+}
+
+impl Clipboard {
+    pub fn new<D>(globals: &GlobalList, qh: &QueueHandle<D>) -> Self
+    where
+        D: Dispatch<WlDataDeviceManager, GlobalData> + 'static,
+    {
+        let manager = DataDeviceManagerState::bind(globals, qh)
+            .map_err(|error| warn!("wl_data_device_manager not available: {error}"))
+            .ok();
+
+        Clipboard {
+            manager,
+            device: None,
+            source: None,
+            contents: String::new(),
+        }
+    }
+
+    /// Create the data device for a seat, if one does not exist yet.
+    pub fn add_seat<D>(&mut self, qh: &QueueHandle<D>, seat: &wl_seat::WlSeat)
+    where
+        D: Dispatch<WlDataDevice, DataDeviceData> + 'static,
+    {
+        if let (Some(manager), None) = (&self.manager, &self.device) {
+            self.device = Some(manager.get_data_device(qh, seat));
+        }
+    }
+
+    pub fn remove_seat(&mut self) {
+        if let Some(device) = self.device.take() {
+            device.release();
+        }
+    }
+
+    /// Take ownership of the selection with `text`, in response to the input event with `serial`.
+    pub fn copy<D>(&mut self, qh: &QueueHandle<D>, text: &str, serial: u32)
+    where
+        D: Dispatch<WlDataSource, DataSourceData> + 'static,
+    {
+        let (Some(manager), Some(device)) = (&self.manager, &self.device) else {
+            return;
+        };
+
+        let source = manager.create_copy_paste_source(qh, TEXT_MIME_TYPES);
+        source.set_selection(device, serial);
+        info!("Copied {} bytes to the clipboard", text.len());
+
+        self.contents = text.to_string();
+        self.source = Some(source);
+    }
+
+    /// Request the current selection as text; `on_text` runs once the whole transfer arrived.
+    pub fn paste<D, F>(&self, loop_handle: &LoopHandle<'static, D>, on_text: F)
+    where
+        F: FnOnce(&mut D, String) + 'static,
+    {
+        let Some(device) = &self.device else {
+            return;
+        };
+        let Some(offer) = device.selection_offer() else {
+            info!("Nothing to paste");
+            return;
+        };
+        let mime_types = device.selection_mime_types();
+        let Some(mime_type) = preferred_text_mime(&mime_types) else {
+            info!("Selection has no text representation: {mime_types:?}");
+            return;
+        };
+
+        match offer.receive(mime_type.to_string()) {
+            Ok(pipe) => read_pipe(loop_handle, pipe, on_text),
+            Err(error) => warn!("Failed to receive selection: {error:?}"),
+        }
+    }
+
+    /// Answer a `send` request for our copy source.
+    pub fn send<D>(
+        &self,
+        loop_handle: &LoopHandle<'static, D>,
+        source: &WlDataSource,
+        mime: &str,
+        pipe: WritePipe,
+    ) {
+        let ours = self.source.as_ref().map(|s| s.inner()) == Some(source);
+        if ours && TEXT_MIME_TYPES.contains(&mime) {
+            write_pipe(loop_handle, pipe, self.contents.clone().into_bytes());
+        }
+    }
+
+    /// Forget our copy source once another client took over the selection.
+    pub fn cancelled(&mut self, source: &WlDataSource) {
+        if self.source.as_ref().map(|s| s.inner()) == Some(source) {
+            self.source = None;
+            self.contents.clear();
+        } else {
+            source.destroy();
+        }
+    }
+}
+
+pub(crate) fn preferred_text_mime(offered: &[String]) -> Option<&'static str> {
+    TEXT_MIME_TYPES
+        .into_iter()
+        .find(|mime| offered.iter().any(|offered| offered == mime))
+}
+
+/// Read a pipe to the end from the event loop, one chunk per wakeup.
+pub(crate) fn read_pipe<D, F>(
+    loop_handle: &LoopHandle<'static, D>,
+    pipe: impl Into<OwnedFd>,
+    on_text: F,
+) where
+    F: FnOnce(&mut D, String) + 'static,
+{
+    let file = File::from(pipe.into());
+    let mut contents = Vec::new();
+    let mut on_text = Some(on_text);
+
+    let source = Generic::new(file, Interest::READ, Mode::Level);
+    let result = loop_handle.insert_source(source, move |_, file, state| {
+        let mut chunk = [0; 64 * 1024];
+        match file.read(&mut chunk) {
+            Ok(0) => {
+                let text = String::from_utf8_lossy(&contents).into_owned();
+                if let Some(on_text) = on_text.take() {
+                    on_text(state, text);
+                }
+                Ok(PostAction::Remove)
+            }
+            Ok(read) => {
+                contents.extend_from_slice(&chunk[..read]);
+                Ok(PostAction::Continue)
+            }
+            Err(error) if is_transient(&error) => Ok(PostAction::Continue),
+            Err(error) => {
+                warn!("Failed to read from pipe: {error}");
+                Ok(PostAction::Remove)
+            }
+        }
+    });
+
+    if let Err(error) = result {
+        warn!("Failed to register pipe with the event loop: {error}");
+    }
+}
+
+/// Write `data` to a pipe from the event loop and close it when done.
+pub(crate) fn write_pipe<D>(
+    loop_handle: &LoopHandle<'static, D>,
+    pipe: impl Into<OwnedFd>,
+    data: Vec<u8>,
+) {
+    let file = File::from(pipe.into());
+    let mut written = 0;
+
+    let source = Generic::new(file, Interest::WRITE, Mode::Level);
+    let result = loop_handle.insert_source(source, move |_, file, _| {
+        let end = usize::min(written + PIPE_CHUNK, data.len());
+        match file.write(&data[written..end]) {
+            Ok(count) => {
+                written += count;
+                if written == data.len() {
+                    Ok(PostAction::Remove)
+                } else {
+                    Ok(PostAction::Continue)
+                }
+            }
+            Err(error) if is_transient(&error) => Ok(PostAction::Continue),
+            Err(error) => {
+                warn!("Failed to write to pipe: {error}");
+                Ok(PostAction::Remove)
+            }
+        }
+    });
+
+    if let Err(error) = result {
+        warn!("Failed to register pipe with the event loop: {error}");
+    }
+}
+
+fn is_transient(error: &io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock)
+}
@@ -0,0 +1,32 @@
+use std::ffi::{CStr, CString};
+
+/// The local time formatted by `strftime`, empty if the format is invalid or too long.
+pub(crate) fn now(format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+
+    let mut buffer = [0u8; 256];
+    // SAFETY: `localtime_r` only writes to `tm`, and `strftime` writes at most `buffer.len()`
+    // bytes including the terminating nul, returning 0 if that is not enough.
+    let written = unsafe {
+        let time = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    if written == 0 {
+        return String::new();
+    }
+
+    CStr::from_bytes_until_nul(&buffer)
+        .map(|text| text.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
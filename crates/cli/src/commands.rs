@@ -0,0 +1,208 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::control::ControlCommand;
+use crate::layer_properties::{parse_margin, Margin, Placement};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub(crate) struct Args {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Commands {
+    /// Show the layer shell bar (the default)
+    Layer(LayerArgs),
+    /// Open an xdg-shell window
+    Window(WindowArgs),
+    /// Search the installed applications and launch one
+    Launcher,
+    /// Choose one of the lines read from stdin, like dmenu
+    Dmenu(DmenuArgs),
+    /// Set the wallpaper of every output from one or more images
+    Wallpaper(WallpaperArgs),
+    /// Lock the session until the user's password is entered
+    Lock(LockArgs),
+    /// Briefly show a value such as the volume, updating the OSD if one is already shown
+    Osd(OsdArgs),
+    /// Run a notification daemon for org.freedesktop.Notifications
+    Notify(NotifyArgs),
+    /// Send a command to the running layer shell bar
+    Msg(MsgArgs),
+}
+
+/// Placement options, each overriding the config file's `[bar]` section.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub(crate) struct LayerArgs {
+    /// Where the bar is placed on its output
+    #[arg(short, long, value_enum)]
+    pub placement: Option<Placement>,
+
+    /// Width in pixels, ignored when the placement stretches the bar horizontally
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height in pixels, ignored when the placement stretches the bar vertically
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Distance from the anchored edges as ALL, VERTICAL,HORIZONTAL or TOP,RIGHT,BOTTOM,LEFT
+    #[arg(short, long, value_parser = parse_margin, allow_hyphen_values = true)]
+    pub margin: Option<Margin>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct WindowArgs {
+    /// Show the lines of this file in the list instead of numbered rows
+    #[arg(long)]
+    pub list: Option<PathBuf>,
+
+    /// Number of rows listed without a file
+    #[arg(long, default_value_t = 10_000)]
+    pub rows: usize,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct WallpaperArgs {
+    /// Images to show, directories are searched for PNG and JPEG files
+    pub paths: Vec<PathBuf>,
+
+    /// How images are scaled to each output
+    #[arg(short, long, value_enum, default_value_t = ScaleMode::Fill)]
+    pub mode: ScaleMode,
+
+    /// Color drawn wherever the image does not cover the output, as RRGGBB or RRGGBBAA
+    #[arg(short, long, default_value = "000000", value_parser = parse_color)]
+    pub color: u32,
+
+    /// Advance to the next image every this many seconds
+    #[arg(short, long)]
+    pub interval: Option<u64>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DmenuArgs {
+    /// Text shown left of the input
+    #[arg(short, long, default_value = "")]
+    pub prompt: String,
+
+    /// Number of items listed at once
+    #[arg(short, long, default_value_t = 10)]
+    pub lines: usize,
+
+    /// Match items case-insensitively
+    #[arg(short = 'i', long)]
+    pub case_insensitive: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct LockArgs {
+    /// PAM service used to verify the password
+    #[arg(long, default_value = "login")]
+    pub pam_service: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct OsdArgs {
+    /// Value from 0 to 100 shown by the progress bar
+    pub value: u32,
+
+    /// Text shown above the progress bar
+    #[arg(short, long, default_value = "")]
+    pub label: String,
+
+    /// Icon shown left of the label
+    #[arg(short, long, value_enum)]
+    pub icon: Option<OsdIcon>,
+
+    /// Milliseconds until the OSD hides again
+    #[arg(short, long, default_value_t = 1500)]
+    pub timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct MsgArgs {
+    #[command(subcommand)]
+    pub command: ControlCommand,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct NotifyArgs {
+    /// Screen corner the notifications are stacked in
+    #[arg(short, long, value_enum, default_value_t = Corner::TopRight)]
+    pub corner: Corner,
+
+    /// Milliseconds until a notification expires when the sender leaves it to the server
+    #[arg(short = 't', long, default_value_t = 5000)]
+    pub default_timeout: u64,
+
+    /// D-Bus address to serve on instead of the session bus
+    #[arg(long)]
+    pub address: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OsdIcon {
+    Volume,
+    Muted,
+    Brightness,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScaleMode {
+    /// Cover the output, cropping the image
+    Fill,
+    /// Fit the whole image, bordered by the background color
+    Fit,
+    /// Show the image unscaled in the middle of the output
+    Center,
+    /// Repeat the image unscaled from the top left corner
+    Tile,
+}
+
+/// Parse an `RRGGBB` or `RRGGBBAA` hex color, with an optional leading `#`, into ARGB.
+pub(crate) fn parse_color(value: &str) -> Result<u32, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    // `from_str_radix` would also take a sign.
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(format!("{value:?}: not a hex color"));
+    }
+    let parsed = u32::from_str_radix(hex, 16).map_err(|error| format!("{value:?}: {error}"));
+    match hex.len() {
+        6 => parsed.map(|rgb| 0xFF00_0000 | rgb),
+        8 => parsed.map(|rgba| rgba.rotate_right(8)),
+        _ => Err(format!("{value:?}: expected RRGGBB or RRGGBBAA")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_opaque_unless_given_alpha() {
+        assert_eq!(parse_color("336699"), Ok(0xFF_33_66_99));
+        assert_eq!(parse_color("#336699"), Ok(0xFF_33_66_99));
+        assert_eq!(parse_color("33669980"), Ok(0x80_33_66_99));
+    }
+
+    #[test]
+    fn colors_must_be_hex_digits_only() {
+        assert!(parse_color("+33669").is_err());
+        assert!(parse_color("-3366990").is_err());
+        assert!(parse_color("#33669g").is_err());
+        assert!(parse_color("fff").is_err());
+        assert!(parse_color("").is_err());
+    }
+}
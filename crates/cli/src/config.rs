@@ -0,0 +1,729 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use inotify::{EventMask, Inotify, WatchMask};
+use serde::Deserialize;
+use smithay_client_toolkit::{
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    seat::{
+        keyboard::{KeyEvent, Modifiers},
+        pointer::{BTN_LEFT, BTN_MIDDLE, BTN_RIGHT},
+    },
+};
+use thiserror::Error;
+use tracing::{info, warn};
+use xkbcommon::xkb;
+
+use crate::animation::Easing;
+use crate::commands::LayerArgs;
+use crate::font::Font;
+use crate::icons::Icons;
+use crate::layer_properties::{LayerProperties, Margin, Placement};
+use crate::pixel::PixelFormat;
+use crate::scroll::Scroll;
+use crate::theme::Theme;
+use crate::xdg;
+
+const FILE_NAME: &str = "config.toml";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("reading {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("{}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("{}: {message}", path.display())]
+    Invalid { path: PathBuf, message: String },
+}
+
+/// `$XDG_CONFIG_HOME/<name>/config.toml`.
+pub(crate) fn path() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(FILE_NAME))
+}
+
+/// Everything read from the config file. Anything left out keeps its default.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) bar: BarConfig,
+    pub(crate) theme: Theme,
+    pub(crate) font: FontConfig,
+    pub(crate) icons: IconConfig,
+    /// Replaces the default bindings as a whole when given.
+    pub(crate) keybindings: HashMap<KeyBinding, Action>,
+    /// Drawn left to right.
+    pub(crate) modules: Vec<Module>,
+    pub(crate) window: WindowConfig,
+    pub(crate) render: RenderConfig,
+    pub(crate) pointer: PointerConfig,
+    pub(crate) animation: AnimationConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bar: BarConfig::default(),
+            theme: Theme::default(),
+            font: FontConfig::default(),
+            icons: IconConfig::default(),
+            keybindings: HashMap::from([
+                (KeyBinding::key(xkb::keysyms::KEY_Escape), Action::Quit),
+                (KeyBinding::key(xkb::keysyms::KEY_F12), Action::ToggleStats),
+            ]),
+            modules: vec![Module::Text {
+                on_click: ClickActions::default(),
+                on_scroll: ScrollActions::default(),
+            }],
+            window: WindowConfig::default(),
+            render: RenderConfig::default(),
+            pointer: PointerConfig::default(),
+            animation: AnimationConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Read and validate the config at `path`, using the defaults if there is no file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                info!("No config at {}, using the defaults", path.display());
+                return Ok(Config::default());
+            }
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+
+        let config: Config = toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        config.validate().map_err(|message| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        })?;
+        info!("Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    /// Check what the types alone do not, naming the offending key.
+    fn validate(&self) -> Result<(), String> {
+        self.theme.validate()?;
+        if !(self.font.size.is_finite() && self.font.size > 0.0 && self.font.size <= 500.0) {
+            return Err(format!(
+                "font.size: expected a size between 0 and 500, got {}",
+                self.font.size
+            ));
+        }
+        for (key, value) in [
+            ("bar.width", self.bar.width),
+            ("bar.height", self.bar.height),
+        ] {
+            if value == Some(0) {
+                return Err(format!(
+                    "{key}: must not be 0, leave it out for the default"
+                ));
+            }
+        }
+        for (key, value) in [
+            ("window.width", self.window.width),
+            ("window.height", self.window.height),
+        ] {
+            if value == 0 {
+                return Err(format!("{key}: must not be 0"));
+            }
+        }
+        for (index, module) in self.modules.iter().enumerate() {
+            if let Module::Clock { format, .. } = module {
+                if format.is_empty() {
+                    return Err(format!("modules[{index}].format: must not be empty"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the layer shell bar goes, overridden by the `layer` subcommand's options.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct BarConfig {
+    pub(crate) placement: Placement,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) margin: Margin,
+}
+
+impl BarConfig {
+    pub fn properties(&self, overrides: &LayerArgs) -> LayerProperties {
+        let placement = overrides.placement.unwrap_or(self.placement);
+        let size = placement.size(
+            overrides.width.or(self.width),
+            overrides.height.or(self.height),
+        );
+        placement.properties(size, overrides.margin.unwrap_or(self.margin))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct FontConfig {
+    /// A font file name such as `DejaVuSansMono.ttf`, or an absolute path.
+    pub(crate) file: Option<String>,
+    pub(crate) size: f32,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        FontConfig {
+            file: None,
+            size: 16.0,
+        }
+    }
+}
+
+impl FontConfig {
+    pub fn load(&self) -> anyhow::Result<Font> {
+        match &self.file {
+            Some(file) => Font::find(file, self.size),
+            None => Font::load(self.size),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct IconConfig {
+    /// An installed icon theme such as `Adwaita`, only `hicolor` is searched without one.
+    pub(crate) theme: Option<String>,
+}
+
+impl IconConfig {
+    pub fn load(&self) -> Icons {
+        Icons::new(self.theme.as_deref())
+    }
+}
+
+/// A key with the modifiers that must be held, written like `ctrl+shift+h`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub(crate) struct KeyBinding {
+    keysym: u32,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+}
+
+impl KeyBinding {
+    fn key(keysym: u32) -> Self {
+        KeyBinding {
+            keysym,
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+        }
+    }
+
+    pub fn matches(&self, event: &KeyEvent, modifiers: &Modifiers) -> bool {
+        lowercase(event.keysym) == self.keysym
+            && modifiers.ctrl == self.ctrl
+            && modifiers.alt == self.alt
+            && modifiers.shift == self.shift
+            && modifiers.logo == self.logo
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut parts: Vec<&str> = value.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+        if keysym == xkb::keysyms::KEY_NoSymbol {
+            return Err(format!("{value:?}: unknown key {key:?}"));
+        }
+
+        let mut binding = KeyBinding::key(lowercase(keysym));
+        for modifier in parts {
+            let held = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut binding.ctrl,
+                "alt" => &mut binding.alt,
+                "shift" => &mut binding.shift,
+                "super" | "logo" => &mut binding.logo,
+                _ => {
+                    return Err(format!(
+                    "{value:?}: unknown modifier {modifier:?}, expected ctrl, alt, shift or super"
+                ))
+                }
+            };
+            *held = true;
+        }
+        Ok(binding)
+    }
+}
+
+/// Shifted letters arrive as their uppercase keysym, bindings are matched on the lowercase one.
+fn lowercase(keysym: u32) -> u32 {
+    match keysym {
+        xkb::keysyms::KEY_A..=xkb::keysyms::KEY_Z => {
+            keysym + (xkb::keysyms::KEY_a - xkb::keysyms::KEY_A)
+        }
+        _ => keysym,
+    }
+}
+
+/// Something a key binding or click does, written as its name or `{ exec = "command" }`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Action {
+    /// Exit
+    Quit,
+    /// Unmap the bar until it is shown over the control socket
+    Hide,
+    /// Empty the text
+    ClearText,
+    /// Start or stop scrolling the background
+    ToggleAnimation,
+    /// Show or hide frame statistics
+    ToggleStats,
+    /// Run a command with `sh -c`
+    Exec(String),
+}
+
+/// What clicking a module does, per button.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ClickActions {
+    pub(crate) left: Option<Action>,
+    pub(crate) middle: Option<Action>,
+    pub(crate) right: Option<Action>,
+    /// Run on a second left click in quick succession, instead of `left`.
+    pub(crate) double: Option<Action>,
+}
+
+impl ClickActions {
+    /// The action for a click of `button`, `count` clicks in a row.
+    pub fn get(&self, button: u32, count: u32) -> Option<&Action> {
+        match button {
+            BTN_LEFT if count == 2 && self.double.is_some() => self.double.as_ref(),
+            BTN_LEFT => self.left.as_ref(),
+            BTN_MIDDLE => self.middle.as_ref(),
+            BTN_RIGHT => self.right.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// What scrolling over a module does, run once per wheel step.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ScrollActions {
+    pub(crate) up: Option<Action>,
+    pub(crate) down: Option<Action>,
+    pub(crate) left: Option<Action>,
+    pub(crate) right: Option<Action>,
+}
+
+impl ScrollActions {
+    /// The actions for `scroll`, each as many times as it scrolled steps that way.
+    pub fn get(&self, scroll: &Scroll) -> Vec<&Action> {
+        let mut actions = Vec::new();
+        for (action, steps) in [
+            (&self.up, -scroll.steps_y),
+            (&self.down, scroll.steps_y),
+            (&self.left, -scroll.steps_x),
+            (&self.right, scroll.steps_x),
+        ] {
+            if let Some(action) = action {
+                actions.extend(std::iter::repeat_n(action, steps.max(0) as usize));
+            }
+        }
+        actions
+    }
+}
+
+/// Something drawn on the bar.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum Module {
+    /// The editable text
+    Text {
+        #[serde(default, rename = "on-click")]
+        on_click: ClickActions,
+        #[serde(default, rename = "on-scroll")]
+        on_scroll: ScrollActions,
+    },
+    /// The local time
+    Clock {
+        /// As understood by `strftime`
+        #[serde(default = "default_clock_format")]
+        format: String,
+        #[serde(default, rename = "on-click")]
+        on_click: ClickActions,
+        #[serde(default, rename = "on-scroll")]
+        on_scroll: ScrollActions,
+    },
+}
+
+impl Module {
+    pub fn on_click(&self) -> &ClickActions {
+        match self {
+            Module::Text { on_click, .. } | Module::Clock { on_click, .. } => on_click,
+        }
+    }
+
+    pub fn on_scroll(&self) -> &ScrollActions {
+        match self {
+            Module::Text { on_scroll, .. } | Module::Clock { on_scroll, .. } => on_scroll,
+        }
+    }
+}
+
+fn default_clock_format() -> String {
+    "%H:%M".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct WindowConfig {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) title: String,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: 640,
+            height: 480,
+            title: "a wayland window".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RenderConfig {
+    /// Buffer format to draw in if the compositor supports it, picked automatically if not given.
+    pub(crate) format: Option<PixelFormat>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct PointerConfig {
+    /// Scroll content along with the fingers, inverting the wheel as well.
+    pub(crate) natural_scroll: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct AnimationConfig {
+    /// Length of transitions such as the bar sliding in, in milliseconds. 0 turns them off.
+    pub(crate) duration: u32,
+    pub(crate) easing: Easing,
+    /// How fast the animated gradient moves, in pixels per second.
+    pub(crate) speed: f32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        AnimationConfig {
+            duration: 200,
+            easing: Easing::default(),
+            speed: 60.0,
+        }
+    }
+}
+
+/// Something that applies config changes while running.
+pub(crate) trait ConfigHandler: Sized + 'static {
+    fn config_changed(&mut self, config: Config);
+}
+
+/// Reload the config at `path` whenever it is written, moved into place or removed.
+///
+/// The directory is watched rather than the file, since editors often save by replacing it. A
+/// config that fails to load is reported and the current one kept.
+pub(crate) fn watch<D: ConfigHandler>(
+    loop_handle: &LoopHandle<'static, D>,
+    path: PathBuf,
+    mut current: Config,
+) -> io::Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        ));
+    };
+    let name = name.to_os_string();
+    fs::create_dir_all(dir)?;
+
+    let inotify = Inotify::init()?;
+    inotify.watches().add(
+        dir,
+        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE | WatchMask::MOVED_FROM,
+    )?;
+    info!("Watching {} for changes", path.display());
+
+    loop_handle
+        .insert_source(
+            Generic::new(inotify, Interest::READ, Mode::Level),
+            move |_, inotify, state| {
+                let mut buffer = [0; 4096];
+                let mut changed = false;
+                loop {
+                    match inotify.read_events(&mut buffer) {
+                        Ok(events) => {
+                            let mut any = false;
+                            for event in events {
+                                any = true;
+                                changed |= event.name == Some(name.as_os_str())
+                                    && !event.mask.contains(EventMask::ISDIR);
+                            }
+                            if !any {
+                                break;
+                            }
+                        }
+                        Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(error) => return Err(error),
+                    }
+                }
+                if !changed {
+                    return Ok(PostAction::Continue);
+                }
+
+                match Config::load(&path) {
+                    Ok(config) if config == current => {}
+                    Ok(config) => {
+                        info!("Config changed, applying it");
+                        current = config.clone();
+                        state.config_changed(config);
+                    }
+                    Err(error) => warn!("Keeping the current config: {error}"),
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|error| io::Error::other(error.error))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay_client_toolkit::shell::wlr_layer::Anchor;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|error| error.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn empty_file_is_the_defaults() {
+        assert_eq!(parse(""), Ok(Config::default()));
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let config = parse(
+            r#"
+            [bar]
+            placement = "left-dock"
+            width = 64
+            margin = { top = 4, left = 8 }
+
+            [font]
+            size = 12.5
+
+            [keybindings]
+            "ctrl+shift+H" = "hide"
+            "super+Return" = { exec = "foot" }
+
+            [[modules]]
+            type = "clock"
+            format = "%a %H:%M"
+            on-click = { left = "toggle-animation", double = "quit" }
+
+            [[modules]]
+            type = "text"
+            on-scroll = { up = "clear-text" }
+
+            [window]
+            width = 800
+            title = "test"
+
+            [render]
+            format = "rgb565"
+
+            [pointer]
+            natural-scroll = true
+
+            [animation]
+            duration = 0
+            easing = "ease-in-out"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.bar.placement, Placement::LeftDock);
+        assert_eq!(config.bar.width, Some(64));
+        assert_eq!(
+            config.bar.margin,
+            Margin {
+                top: 4,
+                left: 8,
+                ..Margin::default()
+            }
+        );
+        assert_eq!(config.font.size, 12.5);
+        assert_eq!(
+            config.keybindings,
+            HashMap::from([
+                (
+                    KeyBinding {
+                        ctrl: true,
+                        shift: true,
+                        ..KeyBinding::key(xkb::keysyms::KEY_h)
+                    },
+                    Action::Hide
+                ),
+                (
+                    KeyBinding {
+                        logo: true,
+                        ..KeyBinding::key(xkb::keysyms::KEY_Return)
+                    },
+                    Action::Exec("foot".to_string())
+                ),
+            ])
+        );
+        assert_eq!(config.modules.len(), 2);
+        let Module::Clock {
+            format, on_click, ..
+        } = &config.modules[0]
+        else {
+            panic!("expected a clock, got {:?}", config.modules[0]);
+        };
+        assert_eq!(format, "%a %H:%M");
+        assert_eq!(on_click.get(BTN_LEFT, 1), Some(&Action::ToggleAnimation));
+        assert_eq!(on_click.get(BTN_LEFT, 2), Some(&Action::Quit));
+        assert_eq!(on_click.get(BTN_RIGHT, 1), None);
+        assert_eq!(
+            config.window,
+            WindowConfig {
+                width: 800,
+                title: "test".to_string(),
+                ..WindowConfig::default()
+            }
+        );
+        assert_eq!(config.render.format, Some(PixelFormat::Rgb565));
+        assert!(config.pointer.natural_scroll);
+        assert_eq!(config.animation.duration, 0);
+        assert_eq!(config.animation.easing, Easing::EaseInOut);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bindings() {
+        assert!(parse("[bar]\nplacment = \"top-bar\"")
+            .unwrap_err()
+            .contains("placment"));
+        assert!(parse("[keybindings]\n\"ctrl+nokey\" = \"quit\"")
+            .unwrap_err()
+            .contains("unknown key"));
+        assert!(parse("[keybindings]\n\"hyper+a\" = \"quit\"")
+            .unwrap_err()
+            .contains("unknown modifier"));
+        assert!(parse("[keybindings]\n\"a\" = \"explode\"").is_err());
+    }
+
+    #[test]
+    fn validation_names_the_key() {
+        for (text, key) in [
+            ("[font]\nsize = 0", "font.size"),
+            ("[font]\nsize = 1000", "font.size"),
+            ("[bar]\nheight = 0", "bar.height"),
+            ("[window]\nwidth = 0", "window.width"),
+            (
+                "[[modules]]\ntype = \"clock\"\nformat = \"\"",
+                "modules[0].format",
+            ),
+        ] {
+            let error = parse(text).unwrap_err();
+            assert!(error.starts_with(key), "{text:?}: {error}");
+        }
+    }
+
+    #[test]
+    fn bar_properties_take_the_command_line_over_the_file() {
+        let bar = BarConfig {
+            placement: Placement::TopBar,
+            height: Some(40),
+            ..BarConfig::default()
+        };
+        assert_eq!(bar.properties(&LayerArgs::default()).size, (0, 40));
+
+        let overrides = LayerArgs {
+            placement: Some(Placement::BottomRightPopup),
+            width: Some(200),
+            ..LayerArgs::default()
+        };
+        let properties = bar.properties(&overrides);
+        assert_eq!(properties.size, (200, 40));
+        assert_eq!(properties.anchor, Anchor::BOTTOM | Anchor::RIGHT);
+    }
+
+    #[test]
+    fn scroll_actions_repeat_per_step() {
+        let actions = ScrollActions {
+            up: Some(Action::Quit),
+            right: Some(Action::ClearText),
+            ..ScrollActions::default()
+        };
+        let scroll = Scroll {
+            position: (0.0, 0.0),
+            x: 0.0,
+            y: 0.0,
+            steps_x: 1,
+            steps_y: -2,
+            source: None,
+            stop: false,
+        };
+        assert_eq!(
+            actions.get(&scroll),
+            [&Action::Quit, &Action::Quit, &Action::ClearText]
+        );
+    }
+
+    #[test]
+    fn loading_reports_the_path() {
+        let dir = std::env::temp_dir().join(format!("config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let missing = dir.join("missing.toml");
+        assert_eq!(Config::load(&missing).unwrap(), Config::default());
+
+        let invalid = dir.join("invalid.toml");
+        fs::write(&invalid, "[window]\nheight = 0\n").unwrap();
+        let error = Config::load(&invalid).unwrap_err().to_string();
+        assert!(error.contains("invalid.toml"), "{error}");
+        assert!(error.contains("window.height"), "{error}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::{
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer},
+};
+use tracing::{info, warn};
+
+use crate::layer_properties::{Margin, Placement};
+use crate::xdg;
+
+/// A command for a running instance, sent as one JSON object per line.
+///
+/// The same enum backs the `msg` subcommand, e.g. `msg set-text hello` sends
+/// `{"command":"set-text","text":"hello"}`.
+#[derive(Subcommand, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub(crate) enum ControlCommand {
+    /// Map the surface again after hiding it
+    Show,
+    /// Unmap the surface
+    Hide,
+    /// Show the surface if it is hidden, hide it otherwise
+    Toggle,
+    /// Replace the text shown on the surface
+    SetText { text: String },
+    /// Move the surface to another layer
+    SetLayer {
+        #[arg(value_enum)]
+        layer: LayerName,
+    },
+    /// Anchor the surface to these edges, none centers it
+    SetAnchor {
+        #[arg(value_enum)]
+        edges: Vec<Edge>,
+    },
+    /// Move the surface to a preset placement, keeping its margin
+    SetPlacement {
+        #[arg(value_enum)]
+        placement: Placement,
+        /// Width in pixels, the placement's default if not given
+        #[arg(long)]
+        #[serde(default)]
+        width: Option<u32>,
+        /// Height in pixels, the placement's default if not given
+        #[arg(long)]
+        #[serde(default)]
+        height: Option<u32>,
+    },
+    /// Request a size, 0 along an axis anchored on both sides stretches the surface
+    SetSize { width: u32, height: u32 },
+    /// Reserve this much space along the anchored edge, -1 to ignore other surfaces' zones
+    SetExclusiveZone {
+        #[arg(allow_hyphen_values = true)]
+        zone: i32,
+    },
+    /// Keep this much distance from the anchored edges
+    SetMargin {
+        #[arg(allow_hyphen_values = true)]
+        top: i32,
+        #[arg(allow_hyphen_values = true)]
+        right: i32,
+        #[arg(allow_hyphen_values = true)]
+        bottom: i32,
+        #[arg(allow_hyphen_values = true)]
+        left: i32,
+    },
+    /// Choose whether the surface takes keyboard focus
+    SetKeyboardInteractivity {
+        #[arg(value_enum)]
+        mode: KeyboardMode,
+    },
+    /// Exit the running instance
+    Quit,
+    /// Print the current state as JSON
+    GetState,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LayerName {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+impl From<LayerName> for Layer {
+    fn from(layer: LayerName) -> Self {
+        match layer {
+            LayerName::Background => Layer::Background,
+            LayerName::Bottom => Layer::Bottom,
+            LayerName::Top => Layer::Top,
+            LayerName::Overlay => Layer::Overlay,
+        }
+    }
+}
+
+impl From<Layer> for LayerName {
+    fn from(layer: Layer) -> Self {
+        match layer {
+            Layer::Background => LayerName::Background,
+            Layer::Bottom => LayerName::Bottom,
+            Layer::Top => LayerName::Top,
+            // `Layer` is non-exhaustive, anything new is at least above the top layer.
+            _ => LayerName::Overlay,
+        }
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum KeyboardMode {
+    None,
+    Exclusive,
+    OnDemand,
+}
+
+impl From<KeyboardMode> for KeyboardInteractivity {
+    fn from(mode: KeyboardMode) -> Self {
+        match mode {
+            KeyboardMode::None => KeyboardInteractivity::None,
+            KeyboardMode::Exclusive => KeyboardInteractivity::Exclusive,
+            KeyboardMode::OnDemand => KeyboardInteractivity::OnDemand,
+        }
+    }
+}
+
+impl From<KeyboardInteractivity> for KeyboardMode {
+    fn from(interactivity: KeyboardInteractivity) -> Self {
+        match interactivity {
+            KeyboardInteractivity::Exclusive => KeyboardMode::Exclusive,
+            KeyboardInteractivity::OnDemand => KeyboardMode::OnDemand,
+            // Also non-exhaustive, treat anything new as not taking focus.
+            _ => KeyboardMode::None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Edge {
+    const ALL: [Edge; 4] = [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right];
+
+    fn anchor(self) -> Anchor {
+        match self {
+            Edge::Top => Anchor::TOP,
+            Edge::Bottom => Anchor::BOTTOM,
+            Edge::Left => Anchor::LEFT,
+            Edge::Right => Anchor::RIGHT,
+        }
+    }
+
+    pub fn to_anchor(edges: &[Edge]) -> Anchor {
+        edges
+            .iter()
+            .fold(Anchor::empty(), |anchor, edge| anchor | edge.anchor())
+    }
+
+    pub fn from_anchor(anchor: Anchor) -> Vec<Edge> {
+        Edge::ALL
+            .into_iter()
+            .filter(|edge| anchor.contains(edge.anchor()))
+            .collect()
+    }
+}
+
+/// What `get-state` reports.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LayerState {
+    pub(crate) visible: bool,
+    pub(crate) text: String,
+    pub(crate) layer: LayerName,
+    pub(crate) anchor: Vec<Edge>,
+    pub(crate) exclusive_zone: i32,
+    pub(crate) margin: Margin,
+    pub(crate) keyboard_interactivity: KeyboardMode,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// The answer to one command, sent back as one JSON line.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reply {
+    pub(crate) ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) state: Option<LayerState>,
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply {
+            ok: true,
+            error: None,
+            state: None,
+        }
+    }
+
+    pub fn error(error: impl ToString) -> Self {
+        Reply {
+            ok: false,
+            error: Some(error.to_string()),
+            state: None,
+        }
+    }
+
+    pub fn state(state: LayerState) -> Self {
+        Reply {
+            state: Some(state),
+            ..Reply::ok()
+        }
+    }
+}
+
+/// Something that can be controlled over a [`ControlSocket`].
+pub(crate) trait ControlHandler: Sized + 'static {
+    fn control(&mut self, command: ControlCommand) -> Reply;
+}
+
+/// The listening control socket, removed again when dropped.
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+}
+
+pub(crate) fn socket_path() -> io::Result<PathBuf> {
+    xdg::runtime_socket("control")
+}
+
+impl ControlSocket {
+    /// Listen for commands from the event loop, refusing to replace another running instance.
+    pub fn bind<D: ControlHandler>(loop_handle: &LoopHandle<'static, D>) -> io::Result<Self> {
+        let path = socket_path()?;
+        match UnixStream::connect(&path) {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("another instance is listening on {}", path.display()),
+                ))
+            }
+            // Left behind by an instance that did not shut down cleanly.
+            Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                fs::remove_file(&path)?;
+            }
+            Err(_) => {}
+        }
+
+        // Only this user may quit or rearrange the bar.
+        let listener = xdg::bind_private(&path)?;
+        listener.set_nonblocking(true)?;
+        info!("Listening for commands on {}", path.display());
+
+        let handle = loop_handle.clone();
+        loop_handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener, _| {
+                    loop {
+                        match listener.accept() {
+                            Ok((stream, _)) => serve(&handle, stream),
+                            Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(error) => {
+                                warn!("Failed to accept control connection: {error}");
+                                break;
+                            }
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|error| io::Error::other(error.error))?;
+
+        Ok(ControlSocket { path })
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {error}", self.path.display());
+        }
+    }
+}
+
+/// Longest command line accepted, the connection is dropped rather than buffering more.
+const MAX_LINE: usize = 64 * 1024;
+
+/// Answer each command line of one connection as soon as it is complete, for as long as the
+/// client keeps the connection open.
+fn serve<D: ControlHandler>(loop_handle: &LoopHandle<'static, D>, stream: UnixStream) {
+    if let Err(error) = stream.set_nonblocking(true) {
+        warn!("Failed to set up control connection: {error}");
+        return;
+    }
+    let mut input = Vec::new();
+    let mut output = Vec::new();
+    let mut closed = false;
+
+    // Edge triggered, so each wakeup reads and writes until the socket would block.
+    let source = Generic::new(stream, Interest::BOTH, Mode::Edge);
+    let result = loop_handle.insert_source(source, move |_, stream, state| {
+        let mut chunk = [0; 4096];
+        while !closed {
+            match stream.read(&mut chunk) {
+                Ok(0) => closed = true,
+                Ok(read) => input.extend_from_slice(&chunk[..read]),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Failed to read control command: {error}");
+                    return Ok(PostAction::Remove);
+                }
+            }
+        }
+
+        while let Some(end) = input.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = input.drain(..=end).collect();
+            answer(state, &line, &mut output);
+        }
+        if closed && !input.is_empty() {
+            // The last line may end the connection instead of a newline.
+            answer(state, &std::mem::take(&mut input), &mut output);
+        }
+        if input.len() > MAX_LINE {
+            warn!("Control command longer than {MAX_LINE} bytes, closing the connection");
+            return Ok(PostAction::Remove);
+        }
+
+        while !output.is_empty() {
+            match stream.write(&output) {
+                Ok(written) => {
+                    output.drain(..written);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                // Continued once the client reads and the socket is writable again.
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Failed to write control reply: {error}");
+                    return Ok(PostAction::Remove);
+                }
+            }
+        }
+
+        if closed && output.is_empty() {
+            Ok(PostAction::Remove)
+        } else {
+            Ok(PostAction::Continue)
+        }
+    });
+
+    if let Err(error) = result {
+        warn!("Failed to register control connection with the event loop: {error}");
+    }
+}
+
+/// Run the command on one line, unless it is blank, and queue its reply line.
+fn answer<D: ControlHandler>(state: &mut D, line: &[u8], output: &mut Vec<u8>) {
+    let line = String::from_utf8_lossy(line);
+    if line.trim().is_empty() {
+        return;
+    }
+    let reply = match serde_json::from_str::<ControlCommand>(&line) {
+        Ok(command) => {
+            info!("Control command {command:?}");
+            state.control(command)
+        }
+        Err(error) => Reply::error(format!("invalid command: {error}")),
+    };
+    serde_json::to_writer(&mut *output, &reply).expect("serialize reply");
+    output.push(b'\n');
+}
+
+/// Send one command to the running instance and wait for its reply.
+pub(crate) fn send(command: &ControlCommand) -> io::Result<Reply> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("no running instance at {}: {error}", path.display()),
+        )
+    })?;
+
+    let mut line = serde_json::to_string(command)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use smithay_client_toolkit::reexports::calloop::EventLoop;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        commands: Vec<ControlCommand>,
+    }
+
+    impl ControlHandler for Recorder {
+        fn control(&mut self, command: ControlCommand) -> Reply {
+            self.commands.push(command);
+            Reply::ok()
+        }
+    }
+
+    fn read_reply(reader: &mut BufReader<UnixStream>) -> Reply {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn replies_to_each_line_while_the_connection_stays_open() {
+        let mut event_loop = EventLoop::<Recorder>::try_new().unwrap();
+        let mut recorder = Recorder::default();
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        serve(&event_loop.handle(), server);
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+
+        client.write_all(b"{\"command\":\"show\"}\n").unwrap();
+        event_loop
+            .dispatch(Duration::from_millis(100), &mut recorder)
+            .unwrap();
+        assert_eq!(read_reply(&mut reader), Reply::ok());
+        assert_eq!(recorder.commands, [ControlCommand::Show]);
+
+        // A line split over several writes is answered once it is complete.
+        client.write_all(b"{\"command\":\"set-text\",").unwrap();
+        event_loop
+            .dispatch(Duration::from_millis(100), &mut recorder)
+            .unwrap();
+        assert_eq!(recorder.commands.len(), 1);
+        client.write_all(b"\"text\":\"hi\"}\n\nnot json\n").unwrap();
+        event_loop
+            .dispatch(Duration::from_millis(100), &mut recorder)
+            .unwrap();
+        assert_eq!(read_reply(&mut reader), Reply::ok());
+        assert!(!read_reply(&mut reader).ok);
+        assert_eq!(
+            recorder.commands[1],
+            ControlCommand::SetText {
+                text: "hi".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn answers_a_last_line_without_newline() {
+        let mut event_loop = EventLoop::<Recorder>::try_new().unwrap();
+        let mut recorder = Recorder::default();
+        let (mut client, server) = UnixStream::pair().unwrap();
+        serve(&event_loop.handle(), server);
+
+        client.write_all(b"{\"command\":\"quit\"}").unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        event_loop
+            .dispatch(Duration::from_millis(100), &mut recorder)
+            .unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "{\"ok\":true}\n");
+        assert_eq!(recorder.commands, [ControlCommand::Quit]);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use tracing::{info, warn};
+
+use crate::process::spawn_command;
+use crate::xdg;
+
+/// Terminal used for `Terminal=true` entries when `$TERMINAL` is not set.
+const DEFAULT_TERMINAL: &str = "xterm";
+
+/// An application from a freedesktop `.desktop` file.
+#[derive(Debug, Clone)]
+pub(crate) struct DesktopEntry {
+    /// The desktop file ID, e.g. `org.gnome.Nautilus.desktop`.
+    pub(crate) id: String,
+    pub(crate) path: PathBuf,
+    pub(crate) name: String,
+    pub(crate) icon: Option<String>,
+    pub(crate) exec: String,
+    pub(crate) terminal: bool,
+    pub(crate) working_dir: Option<PathBuf>,
+}
+
+impl DesktopEntry {
+    /// The argument vector from `Exec`, with field codes expanded and wrapped in a terminal if needed.
+    pub fn command(&self) -> Option<Vec<String>> {
+        let mut command = Vec::new();
+        for argument in split_exec(&self.exec)? {
+            match argument.as_str() {
+                "%f" | "%F" | "%u" | "%U" => {}
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        command.push("--icon".to_string());
+                        command.push(icon.clone());
+                    }
+                }
+                _ => command.push(self.expand(&argument)),
+            }
+        }
+        if command.is_empty() {
+            return None;
+        }
+
+        if self.terminal {
+            let terminal = env::var("TERMINAL")
+                .ok()
+                .filter(|terminal| !terminal.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_TERMINAL.to_string());
+            let mut wrapped: Vec<String> =
+                terminal.split_whitespace().map(str::to_string).collect();
+            wrapped.push("-e".to_string());
+            wrapped.append(&mut command);
+            command = wrapped;
+        }
+        Some(command)
+    }
+
+    /// Start the application in its own process group, so it outlives the launcher.
+    pub fn launch(&self) -> io::Result<()> {
+        let Some(command) = self.command() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: invalid Exec {:?}", self.id, self.exec),
+            ));
+        };
+        info!("Launching {}: {command:?}", self.id);
+
+        let mut process = Command::new(&command[0]);
+        process.args(&command[1..]);
+        if let Some(dir) = &self.working_dir {
+            process.current_dir(dir);
+        }
+        spawn_command(&mut process)
+    }
+
+    /// Expand the field codes embedded in a single argument.
+    fn expand(&self, argument: &str) -> String {
+        let mut expanded = String::with_capacity(argument.len());
+        let mut chars = argument.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('c') => expanded.push_str(&self.name),
+                Some('k') => expanded.push_str(&self.path.to_string_lossy()),
+                // File and URL codes get nothing to expand to, the rest are deprecated.
+                _ => {}
+            }
+        }
+        expanded
+    }
+}
+
+/// All applications visible in the menu, sorted by name.
+///
+/// Earlier data directories shadow later ones with the same desktop file ID.
+pub(crate) fn scan() -> Vec<DesktopEntry> {
+    let desktops: Vec<String> = env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for dir in xdg::data_dirs() {
+        let applications = dir.join("applications");
+        let mut files = Vec::new();
+        collect_desktop_files(&applications, &mut files);
+
+        for path in files {
+            let id = desktop_file_id(&applications, &path);
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(contents) => entries.extend(parse(id, path, &contents, &desktops)),
+                Err(error) => warn!("Failed to read {}: {error}", path.display()),
+            }
+        }
+    }
+
+    entries.sort_by_cached_key(|entry| entry.name.to_lowercase());
+    info!("Found {} applications", entries.len());
+    entries
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_desktop_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "desktop")
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// The path below `applications/` with `/` replaced by `-`.
+fn desktop_file_id(applications: &Path, path: &Path) -> String {
+    path.strip_prefix(applications)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('/', "-")
+}
+
+/// Parse the `[Desktop Entry]` group, skipping entries that should not be shown.
+fn parse(id: String, path: PathBuf, contents: &str, desktops: &[String]) -> Option<DesktopEntry> {
+    let mut keys = HashMap::new();
+    let mut in_entry = false;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if let (true, Some((key, value))) = (in_entry, line.split_once('=')) {
+            keys.insert(key.trim(), unescape(value.trim()));
+        }
+    }
+
+    let get = |key: &str| keys.get(key).map(String::as_str);
+    let flag = |key: &str| get(key) == Some("true");
+
+    if get("Type") != Some("Application") || flag("NoDisplay") || flag("Hidden") {
+        return None;
+    }
+    if let Some(only) = get("OnlyShowIn") {
+        if !list(only).any(|desktop| desktops.iter().any(|d| d == desktop)) {
+            return None;
+        }
+    }
+    if let Some(not) = get("NotShowIn") {
+        if list(not).any(|desktop| desktops.iter().any(|d| d == desktop)) {
+            return None;
+        }
+    }
+    if let Some(try_exec) = get("TryExec") {
+        if !executable_exists(try_exec) {
+            return None;
+        }
+    }
+
+    Some(DesktopEntry {
+        name: localized(&keys, "Name")?.to_string(),
+        icon: get("Icon").map(str::to_string),
+        exec: get("Exec")?.to_string(),
+        terminal: flag("Terminal"),
+        working_dir: get("Path").filter(|dir| !dir.is_empty()).map(PathBuf::from),
+        id,
+        path,
+    })
+}
+
+/// The value of `key` for the current locale, following the `lang_COUNTRY@MODIFIER` fallbacks.
+fn localized<'a>(keys: &'a HashMap<&str, String>, key: &str) -> Option<&'a str> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default();
+    // Drop the encoding, e.g. `de_DE.UTF-8@euro` -> `de_DE@euro`.
+    let (base, modifier) = match locale.split_once('@') {
+        Some((base, modifier)) => (base, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let base = base.split('.').next().unwrap_or(base);
+    let lang = base.split('_').next().unwrap_or(base);
+
+    let mut candidates = Vec::new();
+    if let Some(modifier) = modifier {
+        candidates.push(format!("{base}@{modifier}"));
+    }
+    candidates.push(base.to_string());
+    if let Some(modifier) = modifier {
+        candidates.push(format!("{lang}@{modifier}"));
+    }
+    candidates.push(lang.to_string());
+
+    candidates
+        .iter()
+        .filter(|candidate| !candidate.is_empty())
+        .find_map(|candidate| keys.get(format!("{key}[{candidate}]").as_str()))
+        .or_else(|| keys.get(key))
+        .map(String::as_str)
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(';').filter(|item| !item.is_empty())
+}
+
+/// Undo the string escapes `\s`, `\n`, `\t`, `\r` and `\\`.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split an `Exec` value into arguments, honouring double quotes and their backslash escapes.
+///
+/// Returns `None` for an unterminated quote.
+fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_argument = true;
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => current.push(chars.next()?),
+                        other => current.push(other),
+                    }
+                }
+            }
+            ' ' | '\t' => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            other => {
+                in_argument = true;
+                current.push(other);
+            }
+        }
+    }
+    if in_argument {
+        arguments.push(current);
+    }
+    Some(arguments)
+}
+
+fn executable_exists(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.is_absolute() {
+        return path.is_file();
+    }
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(exec: &str) -> DesktopEntry {
+        let contents = format!(
+            "[Desktop Entry]\nType=Application\nName=My App\nExec={exec}\n\n[Desktop Action new]\nExec=ignored\n"
+        );
+        parse(
+            "my-app.desktop".to_string(),
+            PathBuf::from("/usr/share/applications/my-app.desktop"),
+            &contents,
+            &[],
+        )
+        .expect("a visible application")
+    }
+
+    #[test]
+    fn unescapes_string_escapes() {
+        assert_eq!(unescape(r"a\sb\tc\nd\re"), "a b\tc\nd\re");
+        assert_eq!(unescape(r"C:\\dir"), r"C:\dir");
+        assert_eq!(unescape(r"\\\\"), r"\\");
+        assert_eq!(unescape(r"keep\q and\"), r"keep\q and\");
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            split_exec(r#"run  "two words" "a \"quote\"" "back\\slash""#),
+            Some(vec![
+                "run".to_string(),
+                "two words".to_string(),
+                r#"a "quote""#.to_string(),
+                r"back\slash".to_string(),
+            ])
+        );
+        assert_eq!(split_exec(r#"run "unterminated"#), None);
+        assert_eq!(split_exec(r#"run "ends in \"#), None);
+    }
+
+    #[test]
+    fn exec_escapes_apply_in_both_passes() {
+        // As written in the file, where backslashes inside quotes are doubled once for the
+        // string escapes and once more for the quoting.
+        let entry = entry(r#""/opt/My App/run" --title "say \\"hi\\"" --path "C:\\\\dir""#);
+        assert_eq!(
+            entry.command(),
+            Some(vec![
+                "/opt/My App/run".to_string(),
+                "--title".to_string(),
+                r#"say "hi""#.to_string(),
+                "--path".to_string(),
+                r"C:\dir".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let entry = entry("app %U --name=%c --rate 100%% %k");
+        assert_eq!(
+            entry.command(),
+            Some(vec![
+                "app".to_string(),
+                "--name=My App".to_string(),
+                "--rate".to_string(),
+                "100%".to_string(),
+                "/usr/share/applications/my-app.desktop".to_string(),
+            ])
+        );
+    }
+}
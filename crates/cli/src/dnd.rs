@@ -0,0 +1,334 @@
+use std::path::PathBuf;
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, SurfaceData},
+    data_device_manager::{
+        data_device::{DataDevice, DataDeviceDataExt},
+        data_offer::DragOffer,
+        data_source::{DataSourceData, DragSource},
+        DataDeviceManagerState, WritePipe,
+    },
+    reexports::calloop::LoopHandle,
+    reexports::client::{
+        protocol::{
+            wl_data_device_manager::DndAction, wl_data_source::WlDataSource, wl_shm, wl_surface,
+        },
+        Dispatch, QueueHandle,
+    },
+    shm::slot::SlotPool,
+};
+use tracing::{info, warn};
+
+use crate::clipboard::{read_pipe, write_pipe, TEXT_MIME_TYPES};
+
+/// Mime type used for lists of dropped files.
+pub(crate) const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+/// Distance in surface pixels the pointer has to travel with the button held before a drag starts.
+const DRAG_THRESHOLD: f64 = 8.0;
+
+const ICON_SIZE: i32 = 32;
+
+/// What was dropped onto a surface.
+#[derive(Debug)]
+pub(crate) enum Dropped {
+    Files(Vec<PathBuf>),
+    Text(String),
+}
+
+/// Drag-and-drop through `wl_data_device`, both as a drop target and as a drag source.
+#[derive(Default)]
+pub(crate) struct DragAndDrop {
+    /// A drag offer we can accept is hovering over the surface.
+    pub(crate) hovering: bool,
+    /// The offer of the drag over the surface, which is ours to destroy once it leaves.
+    offer: Option<DragOffer>,
+    /// Button press that may turn into a drag once the pointer moves far enough.
+    pub(crate) pending: Option<(u32, (f64, f64))>,
+    pub(crate) source: Option<DragSource>,
+    pub(crate) icon: Option<wl_surface::WlSurface>,
+    pub(crate) contents: String,
+}
+
+impl DragAndDrop {
+    /// A drag offer entered the surface: accept it if it carries files or text.
+    pub fn enter(&mut self, data_device: &DataDevice) {
+        let Some(mut offer) = data_device.drag_offer() else {
+            return;
+        };
+
+        let mime_type = preferred_drop_mime(&data_device.drag_mime_types());
+        info!(
+            "Drag entered @({:.1}, {:.1}) accepting {mime_type:?}",
+            offer.x, offer.y
+        );
+
+        self.hovering = mime_type.is_some();
+        self.offer = Some(offer.clone());
+        offer.accept_mime_type(offer.serial, mime_type.map(str::to_string));
+        if self.hovering {
+            offer.set_actions(DndAction::Copy, DndAction::Copy);
+        } else {
+            offer.set_actions(DndAction::empty(), DndAction::empty());
+        }
+    }
+
+    /// The drag left without a drop. The data device already forgot the offer, so it is
+    /// destroyed through the one kept from `enter`.
+    pub fn leave(&mut self) {
+        self.hovering = false;
+        if let Some(offer) = self.offer.take() {
+            offer.destroy();
+        }
+    }
+
+    /// Read the dropped data; `on_drop` runs once the whole transfer arrived.
+    pub fn drop_performed<D, F>(
+        &mut self,
+        loop_handle: &LoopHandle<'static, D>,
+        data_device: &DataDevice,
+        on_drop: F,
+    ) where
+        F: FnOnce(&mut D, Dropped) + 'static,
+    {
+        self.hovering = false;
+        // Destroyed below once the transfer is done.
+        self.offer = None;
+
+        let Some(offer) = data_device.drag_offer() else {
+            return;
+        };
+        let Some(mime_type) = preferred_drop_mime(&data_device.drag_mime_types()) else {
+            offer.destroy();
+            return;
+        };
+
+        match offer.receive(mime_type.to_string()) {
+            Ok(pipe) => read_pipe(loop_handle, pipe, move |state, contents| {
+                finish(&offer);
+                let dropped = if mime_type == URI_LIST_MIME_TYPE {
+                    Dropped::Files(parse_uri_list(&contents))
+                } else {
+                    Dropped::Text(contents)
+                };
+                on_drop(state, dropped);
+            }),
+            Err(error) => {
+                warn!("Failed to receive drop: {error}");
+                offer.destroy();
+            }
+        }
+    }
+
+    /// Remember a button press as the possible start of a drag.
+    pub fn press(&mut self, serial: u32, position: (f64, f64)) {
+        self.pending = Some((serial, position));
+    }
+
+    pub fn release(&mut self) {
+        self.pending = None;
+    }
+
+    /// Start dragging `text` once the held pointer moved past the threshold.
+    #[allow(clippy::too_many_arguments)]
+    pub fn motion<D>(
+        &mut self,
+        qh: &QueueHandle<D>,
+        manager: Option<&DataDeviceManagerState>,
+        device: Option<&DataDevice>,
+        compositor: &CompositorState,
+        pool: &mut SlotPool,
+        origin: &wl_surface::WlSurface,
+        position: (f64, f64),
+        text: &str,
+    ) where
+        D: Dispatch<WlDataSource, DataSourceData>
+            + Dispatch<wl_surface::WlSurface, SurfaceData>
+            + 'static,
+    {
+        let Some((serial, start)) = self.pending else {
+            return;
+        };
+        if (position.0 - start.0).hypot(position.1 - start.1) < DRAG_THRESHOLD {
+            return;
+        }
+        self.pending = None;
+
+        let (Some(manager), Some(device)) = (manager, device) else {
+            return;
+        };
+        if text.is_empty() || self.source.is_some() {
+            return;
+        }
+
+        let source = manager.create_drag_and_drop_source(qh, TEXT_MIME_TYPES, DndAction::Copy);
+        let icon = compositor.create_surface(qh);
+        source.start_drag(device, origin, Some(&icon), serial);
+        draw_icon(pool, &icon);
+        info!("Started dragging {} bytes", text.len());
+
+        self.contents = text.to_string();
+        self.source = Some(source);
+        self.icon = Some(icon);
+    }
+
+    /// Whether `source` is the source of our current drag.
+    pub fn owns(&self, source: &WlDataSource) -> bool {
+        self.source.as_ref().map(|s| s.inner()) == Some(source)
+    }
+
+    /// Answer a `send` request for our drag source.
+    pub fn send<D>(&self, loop_handle: &LoopHandle<'static, D>, mime: &str, pipe: WritePipe) {
+        if TEXT_MIME_TYPES.contains(&mime) {
+            write_pipe(loop_handle, pipe, self.contents.clone().into_bytes());
+        }
+    }
+
+    /// Tear down the drag once it was cancelled or finished.
+    pub fn end(&mut self) {
+        self.source = None;
+        self.contents.clear();
+        if let Some(icon) = self.icon.take() {
+            icon.destroy();
+        }
+    }
+}
+
+fn finish(offer: &DragOffer) {
+    offer.finish();
+    offer.destroy();
+}
+
+fn preferred_drop_mime(offered: &[String]) -> Option<&'static str> {
+    std::iter::once(URI_LIST_MIME_TYPE)
+        .chain(TEXT_MIME_TYPES)
+        .find(|mime| offered.iter().any(|offered| offered == mime))
+}
+
+/// Local paths from a `text/uri-list`, skipping comments and non-`file` URIs.
+fn parse_uri_list(contents: &str) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|uri| uri.strip_prefix("file://"))
+        .map(|rest| {
+            // Skip the optional host part, `file://host/path`.
+            let path = &rest[rest.find('/').unwrap_or(rest.len())..];
+            PathBuf::from(percent_decode(path))
+        })
+        .collect()
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|hex| bytes[index] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn draw_icon(pool: &mut SlotPool, icon: &wl_surface::WlSurface) {
+    let (buffer, canvas) = match pool.create_buffer(
+        ICON_SIZE,
+        ICON_SIZE,
+        ICON_SIZE * 4,
+        wl_shm::Format::Argb8888,
+    ) {
+        Ok(buffer) => buffer,
+        Err(error) => {
+            warn!("Failed to create drag icon buffer: {error}");
+            return;
+        }
+    };
+
+    // A translucent square with an opaque outline, premultiplied.
+    canvas
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(index, chunk)| {
+            let x = index as i32 % ICON_SIZE;
+            let y = index as i32 / ICON_SIZE;
+            let edge = x < 2 || y < 2 || x >= ICON_SIZE - 2 || y >= ICON_SIZE - 2;
+            let color: u32 = if edge { 0xFF_30_60_C0 } else { 0x80_18_30_60 };
+
+            let array: &mut [u8; 4] = chunk.try_into().unwrap();
+            *array = color.to_le_bytes();
+        });
+
+    icon.damage_buffer(0, 0, ICON_SIZE, ICON_SIZE);
+    if let Err(error) = buffer.attach_to(icon) {
+        warn!("Failed to attach drag icon buffer: {error}");
+        return;
+    }
+    icon.commit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_unescapes_bytes() {
+        assert_eq!(percent_decode("/my%20file.txt"), "/my file.txt");
+        assert_eq!(percent_decode("%C3%A9t%c3%a9"), "été");
+        assert_eq!(percent_decode("100%25"), "100%");
+    }
+
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(percent_decode("50%"), "50%");
+        assert_eq!(percent_decode("%2"), "%2");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+        // A sign is not a hex digit.
+        assert_eq!(percent_decode("%+1"), "%+1");
+        // Not UTF-8 once decoded.
+        assert_eq!(percent_decode("%FF"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn uri_list_keeps_local_paths() {
+        let list = "# dragged from a file manager\r\n\
+                    file:///home/user/My%20Photo.png\r\n\
+                    \r\n\
+                    https://example.com/remote.png\r\n\
+                    file://host/etc/hosts\r\n\
+                    file:///tmp/last";
+        assert_eq!(
+            parse_uri_list(list),
+            [
+                PathBuf::from("/home/user/My Photo.png"),
+                PathBuf::from("/etc/hosts"),
+                PathBuf::from("/tmp/last"),
+            ]
+        );
+        assert!(parse_uri_list("").is_empty());
+        assert!(parse_uri_list("#file:///commented\n").is_empty());
+    }
+
+    #[test]
+    fn files_are_preferred_over_text() {
+        let offered = |types: &[&str]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            preferred_drop_mime(&offered(&[TEXT_MIME_TYPES[0], URI_LIST_MIME_TYPE])),
+            Some(URI_LIST_MIME_TYPE)
+        );
+        assert_eq!(preferred_drop_mime(&offered(&["image/png"])), None);
+    }
+}
@@ -0,0 +1,672 @@
+use std::ops::Range;
+
+use raster::Gradient;
+
+use crate::font::Font;
+
+/// Fill a rectangle with an opaque color, clipped to the canvas.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fill_rect(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    color: u32,
+) {
+    let left = x.clamp(0, width as i32) as usize;
+    let right = (x + w).clamp(0, width as i32) as usize;
+    let top = y.clamp(0, height as i32) as usize;
+    let bottom = (y + h).clamp(0, height as i32) as usize;
+
+    raster::fill(canvas, width as usize, top..bottom, left..right, color);
+}
+
+/// Premultiply a straight ARGB color, scaling its alpha by `opacity` first.
+pub(crate) fn premultiply(color: u32, opacity: f32) -> u32 {
+    let [blue, green, red, alpha] = color.to_le_bytes();
+    let alpha = (alpha as f32 * opacity.clamp(0.0, 1.0)).round() as u32;
+    let scale = |channel: u8| (channel as u32 * alpha + 127) / 255;
+    (alpha << 24) | (scale(red) << 16) | (scale(green) << 8) | scale(blue)
+}
+
+/// How much of the pixel at `px`, `py` lies inside a rectangle with rounded corners, from 0 to 1.
+///
+/// The edges are antialiased over one pixel, so a radius of 0 on whole pixels gives hard edges.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn rounded_rect_coverage(
+    px: u32,
+    py: u32,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    radius: f32,
+) -> f32 {
+    let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+    let radius = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+
+    // Distance past the straight part of the edges, both positive only in a corner.
+    let dx = (x + radius - cx).max(cx - (x + w - radius)).max(0.0);
+    let dy = (y + radius - cy).max(cy - (y + h - radius)).max(0.0);
+    let inside = if dx > 0.0 && dy > 0.0 {
+        radius - (dx * dx + dy * dy).sqrt()
+    } else {
+        (cx - x).min(x + w - cx).min(cy - y).min(y + h - cy)
+    };
+    (inside + 0.5).clamp(0.0, 1.0)
+}
+
+/// Sub-scanlines sampled per pixel row when filling polygons.
+const SUBSAMPLES: usize = 4;
+
+/// An axis-aligned rectangle in whole pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rect {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) w: i32,
+    pub(crate) h: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.h
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.w <= 0 || self.h <= 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The part of both rectangles, empty if they do not overlap.
+    pub fn intersect(&self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let w = (self.right().min(other.right()) - x).max(0);
+        let h = (self.bottom().min(other.bottom()) - y).max(0);
+        Rect { x, y, w, h }
+    }
+
+    /// Shrunk by `amount` on every side.
+    pub fn inset(&self, amount: i32) -> Rect {
+        Rect {
+            x: self.x + amount,
+            y: self.y + amount,
+            w: (self.w - 2 * amount).max(0),
+            h: (self.h - 2 * amount).max(0),
+        }
+    }
+
+    /// The smallest rectangle holding every pixel a shape between these bounds touches.
+    fn enclosing(left: f32, top: f32, right: f32, bottom: f32) -> Rect {
+        let (x, y) = (left.floor() as i32, top.floor() as i32);
+        Rect {
+            x,
+            y,
+            w: right.ceil() as i32 - x,
+            h: bottom.ceil() as i32 - y,
+        }
+    }
+}
+
+/// Premultiplied ARGB pixels, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<u32>,
+}
+
+impl Image {
+    /// An image with the straight color `paint` gives each pixel.
+    pub fn from_fn(width: u32, height: u32, paint: impl Fn(u32, u32) -> u32) -> Self {
+        let pixels = (0..width * height)
+            .map(|index| premultiply(paint(index % width, index / width), 1.0))
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Antialiased drawing into a premultiplied ARGB8888 buffer, limited to a clip rectangle.
+///
+/// Colors are straight (not premultiplied) ARGB, and everything is blended over what is
+/// already there.
+pub(crate) struct Canvas<'a> {
+    pixels: &'a mut [u8],
+    width: u32,
+    height: u32,
+    clip: Rect,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(pixels: &'a mut [u8], width: u32, height: u32) -> Self {
+        Canvas {
+            pixels,
+            width,
+            height,
+            clip: Rect::new(0, 0, width as i32, height as i32),
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// Only draw inside `clip`, or anywhere on the canvas without one.
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip.map_or(self.bounds(), |clip| clip.intersect(self.bounds()));
+    }
+
+    /// Replace every pixel inside the clip with `color`, without blending.
+    pub fn clear(&mut self, color: u32) {
+        self.write(self.clip, premultiply(color, 1.0));
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: u32) {
+        // Opaque colors replace what is below, so they skip blending.
+        if color >> 24 == 0xFF {
+            self.write(rect.intersect(self.clip), premultiply(color, 1.0));
+        } else {
+            self.fill_rect_with(rect, |_, _| color);
+        }
+    }
+
+    /// Fill `rect` with `gradient`, which covers the whole canvas.
+    pub fn fill_gradient(&mut self, rect: Rect, gradient: &Gradient) {
+        let area = rect.intersect(self.clip);
+        if !area.is_empty() {
+            gradient.fill(self.pixels, self.width as usize, rows(area), columns(area));
+        }
+    }
+
+    /// Fill `rect` with the color `paint` gives each pixel.
+    pub fn fill_rect_with(&mut self, rect: Rect, paint: impl Fn(i32, i32) -> u32) {
+        let area = rect.intersect(self.clip);
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                self.blend(x, y, premultiply(paint(x, y), 1.0), 1.0);
+            }
+        }
+    }
+
+    /// Outline `rect` with lines `width` pixels wide, inside its bounds.
+    pub fn stroke_rect(&mut self, rect: Rect, width: i32, color: u32) {
+        let width = width.min(rect.w / 2).min(rect.h / 2).max(0);
+        let inner = rect.inset(width);
+        let edges = [
+            Rect::new(rect.x, rect.y, rect.w, width),
+            Rect::new(rect.x, inner.bottom(), rect.w, width),
+            Rect::new(rect.x, inner.y, width, inner.h),
+            Rect::new(inner.right(), inner.y, width, inner.h),
+        ];
+        for edge in edges {
+            self.fill_rect(edge, color);
+        }
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f32, color: u32) {
+        self.fill_rounded_rect_with(rect, radius, |_, _| color);
+    }
+
+    /// Fill `rect` with rounded corners, with the color `paint` gives each pixel.
+    pub fn fill_rounded_rect_with(
+        &mut self,
+        rect: Rect,
+        radius: f32,
+        paint: impl Fn(i32, i32) -> u32,
+    ) {
+        let (x, y, w, h) = (rect.x as f32, rect.y as f32, rect.w as f32, rect.h as f32);
+        self.fill_coverage(
+            rect,
+            |px, py| rounded_rect_coverage(px as u32, py as u32, x, y, w, h, radius),
+            paint,
+        );
+    }
+
+    /// Outline `rect` with rounded corners with a line `width` wide, inside its bounds.
+    pub fn stroke_rounded_rect(&mut self, rect: Rect, radius: f32, width: f32, color: u32) {
+        let (x, y, w, h) = (rect.x as f32, rect.y as f32, rect.w as f32, rect.h as f32);
+        let (px, py) = (x + width, y + width);
+        let (iw, ih) = ((w - 2.0 * width).max(0.0), (h - 2.0 * width).max(0.0));
+        self.fill_coverage(
+            rect,
+            |cx, cy| {
+                let (cx, cy) = (cx as u32, cy as u32);
+                let outer = rounded_rect_coverage(cx, cy, x, y, w, h, radius);
+                let inner = rounded_rect_coverage(cx, cy, px, py, iw, ih, radius - width);
+                outer - inner
+            },
+            |_, _| color,
+        );
+    }
+
+    pub fn fill_circle(&mut self, cx: f32, cy: f32, radius: f32, color: u32) {
+        let bounds = Rect::enclosing(cx - radius, cy - radius, cx + radius, cy + radius);
+        self.fill_coverage(
+            bounds,
+            |x, y| (radius - distance(x, y, cx, cy) + 0.5).clamp(0.0, 1.0),
+            |_, _| color,
+        );
+    }
+
+    /// Outline a circle with a line `width` wide, centered on its edge.
+    pub fn stroke_circle(&mut self, cx: f32, cy: f32, radius: f32, width: f32, color: u32) {
+        let outer = radius + width / 2.0;
+        let inner = (radius - width / 2.0).max(0.0);
+        let bounds = Rect::enclosing(cx - outer, cy - outer, cx + outer, cy + outer);
+        self.fill_coverage(
+            bounds,
+            |x, y| {
+                let distance = distance(x, y, cx, cy);
+                let coverage = |radius: f32| (radius - distance + 0.5).clamp(0.0, 1.0);
+                coverage(outer) - coverage(inner)
+            },
+            |_, _| color,
+        );
+    }
+
+    /// Stroke part of a circle from angle `start` to `end`, in radians clockwise from the
+    /// positive x axis, with a line `width` wide and flat ends.
+    #[allow(clippy::too_many_arguments)]
+    pub fn arc(
+        &mut self,
+        cx: f32,
+        cy: f32,
+        radius: f32,
+        start: f32,
+        end: f32,
+        width: f32,
+        color: u32,
+    ) {
+        let outer = radius + width / 2.0;
+        let inner = (radius - width / 2.0).max(0.0);
+        // Segments about two pixels long along the outer edge are indistinguishable from a curve.
+        let segments = ((end - start).abs() * outer / 2.0).ceil().clamp(1.0, 512.0) as usize;
+        let point = |radius: f32, index: usize| {
+            let angle = start + (end - start) * index as f32 / segments as f32;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        };
+
+        let points: Vec<_> = (0..=segments)
+            .map(|index| point(outer, index))
+            .chain((0..=segments).rev().map(|index| point(inner, index)))
+            .collect();
+        self.fill_polygon(&points, color);
+    }
+
+    /// A straight line `width` wide with flat ends.
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: u32) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        let points = [
+            (from.0 + nx, from.1 + ny),
+            (to.0 + nx, to.1 + ny),
+            (to.0 - nx, to.1 - ny),
+            (from.0 - nx, from.1 - ny),
+        ];
+        self.fill_polygon(&points, color);
+    }
+
+    /// Fill the inside of a closed polygon by the nonzero winding rule.
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)], color: u32) {
+        if points.len() < 3 {
+            return;
+        }
+        let (left, top, right, bottom) = points.iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(left, top, right, bottom), &(x, y)| {
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            },
+        );
+        let area = Rect::enclosing(left, top, right, bottom).intersect(self.clip);
+        if area.is_empty() {
+            return;
+        }
+
+        let color = premultiply(color, 1.0);
+        let edges: Vec<_> = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .filter(|(from, to)| from.1 != to.1)
+            .collect();
+        let mut coverage = vec![0.0f32; area.w as usize];
+        let mut crossings = Vec::new();
+
+        for y in area.y..area.bottom() {
+            coverage.fill(0.0);
+            for sample in 0..SUBSAMPLES {
+                let sy = y as f32 + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+                crossings.clear();
+                for (from, to) in &edges {
+                    let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+                    if sy < top || sy >= bottom {
+                        continue;
+                    }
+                    let x = from.0 + (sy - from.1) * (to.0 - from.0) / (to.1 - from.1);
+                    crossings.push((x, if to.1 > from.1 { 1 } else { -1 }));
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                let mut span_start = 0.0;
+                for &(x, direction) in &crossings {
+                    if winding == 0 {
+                        span_start = x;
+                    }
+                    winding += direction;
+                    if winding == 0 {
+                        add_span(&mut coverage, area, span_start, x);
+                    }
+                }
+            }
+
+            for (column, &covered) in coverage.iter().enumerate() {
+                if covered > 0.0 {
+                    self.blend(area.x + column as i32, y, color, covered.min(1.0));
+                }
+            }
+        }
+    }
+
+    /// Draw `image` with its top left corner at `x`, `y`, its alpha scaled by `opacity`.
+    pub fn blit(&mut self, image: &Image, x: i32, y: i32, opacity: f32) {
+        let area = Rect::new(x, y, image.width as i32, image.height as i32).intersect(self.clip);
+        for py in area.y..area.bottom() {
+            let row = (py - y) as usize * image.width as usize;
+            for px in area.x..area.right() {
+                let color = image.pixels[row + (px - x) as usize];
+                self.blend(px, py, color, opacity);
+            }
+        }
+    }
+
+    /// Draw `text` with its baseline at `y`, returning the end x.
+    pub fn text(&mut self, font: &mut Font, x: i32, y: i32, text: &str, color: u32) -> i32 {
+        let color = premultiply(color, 1.0);
+        font.rasterize(x, y, text, |px, py, coverage| {
+            self.blend(px, py, color, coverage as f32 / 255.0);
+        })
+    }
+
+    /// Move everything drawn by `dx`, `dy`, leaving transparency where nothing moved in.
+    /// Ignores the clip.
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        if (dx, dy) == (0, 0) {
+            return;
+        }
+        let (width, height) = (self.width as i32, self.height as i32);
+        let source = self.pixels.to_vec();
+        self.pixels.fill(0);
+        let (left, right) = (dx.max(0), (width + dx).min(width));
+        if left >= right {
+            return;
+        }
+        let length = (right - left) as usize * 4;
+        for y in dy.max(0)..(height + dy).min(height) {
+            let from = self.index(left - dx, y - dy);
+            let to = self.index(left, y);
+            self.pixels[to..to + length].copy_from_slice(&source[from..from + length]);
+        }
+    }
+
+    /// Scale the alpha of everything drawn by `opacity`. Ignores the clip.
+    pub fn fade(&mut self, opacity: f32) {
+        let opacity = opacity.clamp(0.0, 1.0);
+        if opacity < 1.0 {
+            // Premultiplied, so the color channels scale along with the alpha.
+            for channel in self.pixels.iter_mut() {
+                *channel = (*channel as f32 * opacity).round() as u8;
+            }
+        }
+    }
+
+    /// Blend `coverage` of every pixel in `bounds`, in the color `paint` gives it.
+    fn fill_coverage(
+        &mut self,
+        bounds: Rect,
+        coverage: impl Fn(i32, i32) -> f32,
+        paint: impl Fn(i32, i32) -> u32,
+    ) {
+        let area = bounds.intersect(self.clip);
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                let covered = coverage(x, y);
+                if covered > 0.0 {
+                    self.blend(x, y, premultiply(paint(x, y), 1.0), covered);
+                }
+            }
+        }
+    }
+
+    /// Composite a premultiplied color over one pixel, unless it is outside the clip.
+    fn blend(&mut self, x: i32, y: i32, color: u32, coverage: f32) {
+        if !self.clip.contains(x, y) {
+            return;
+        }
+        let index = self.index(x, y);
+        let pixel = &mut self.pixels[index..index + 4];
+        let source = color.to_le_bytes();
+        let inverse = 1.0 - source[3] as f32 / 255.0 * coverage;
+        for (channel, source) in pixel.iter_mut().zip(source) {
+            *channel = (source as f32 * coverage + *channel as f32 * inverse)
+                .round()
+                .min(255.0) as u8;
+        }
+    }
+
+    /// Replace every pixel in `area`, which is inside the canvas, with a premultiplied color.
+    fn write(&mut self, area: Rect, color: u32) {
+        if !area.is_empty() {
+            raster::fill(
+                self.pixels,
+                self.width as usize,
+                rows(area),
+                columns(area),
+                color,
+            );
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+}
+
+/// The rows of a non-empty `area` inside a canvas.
+fn rows(area: Rect) -> Range<usize> {
+    area.y as usize..area.bottom() as usize
+}
+
+/// The columns of a non-empty `area` inside a canvas.
+fn columns(area: Rect) -> Range<usize> {
+    area.x as usize..area.right() as usize
+}
+
+/// Distance from the center of pixel `x`, `y` to a point.
+fn distance(x: i32, y: i32, cx: f32, cy: f32) -> f32 {
+    let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Add the part of each pixel in `area`'s columns that lies between `start` and `end`, for one
+/// of the sub-scanlines.
+fn add_span(coverage: &mut [f32], area: Rect, start: f32, end: f32) {
+    let start = start.max(area.x as f32);
+    let end = end.min(area.right() as f32);
+    if start >= end {
+        return;
+    }
+    for x in start.floor() as i32..end.ceil() as i32 {
+        let overlap = end.min(x as f32 + 1.0) - start.max(x as f32);
+        coverage[(x - area.x) as usize] += overlap / SUBSAMPLES as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 16;
+    /// Left in pixels nothing is supposed to touch.
+    const UNTOUCHED: u8 = 0x5A;
+
+    type Draw<'a> = &'a dyn Fn(&mut Canvas);
+
+    fn pixels() -> Vec<u8> {
+        vec![UNTOUCHED; (SIZE * SIZE * 4) as usize]
+    }
+
+    fn pixel(pixels: &[u8], x: i32, y: i32) -> u32 {
+        let index = (y as usize * SIZE as usize + x as usize) * 4;
+        u32::from_le_bytes(pixels[index..index + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn rects_intersect_and_inset() {
+        let rect = Rect::new(2, 3, 10, 6);
+        assert_eq!(
+            rect.intersect(Rect::new(8, 0, 10, 5)),
+            Rect::new(8, 3, 4, 2)
+        );
+        assert!(rect.intersect(Rect::new(20, 20, 5, 5)).is_empty());
+        assert_eq!(rect.inset(2), Rect::new(4, 5, 6, 2));
+        assert!(rect.inset(4).is_empty());
+        assert!(rect.contains(2, 3) && rect.contains(11, 8));
+        assert!(!rect.contains(12, 8) && !rect.contains(2, 9));
+    }
+
+    #[test]
+    fn clip_stays_on_the_canvas() {
+        let mut pixels = pixels();
+        let mut canvas = Canvas::new(&mut pixels, SIZE, SIZE);
+        canvas.set_clip(Some(Rect::new(-4, 10, 8, 20)));
+        assert_eq!(canvas.clip(), Rect::new(0, 10, 4, 6));
+        canvas.set_clip(None);
+        assert_eq!(canvas.clip(), canvas.bounds());
+    }
+
+    #[test]
+    fn every_primitive_stays_inside_the_clip() {
+        let clip = Rect::new(4, 5, 6, 7);
+        let image = Image::from_fn(SIZE, SIZE, |_, _| 0xFF_00_FF_00);
+        let gradient = Gradient::new(SIZE, SIZE, 0);
+        let primitives: [(&str, Draw); 13] = [
+            ("clear", &|canvas| canvas.clear(0xFF_10_20_30)),
+            ("opaque rect", &|canvas| {
+                canvas.fill_rect(Rect::new(-5, -5, 40, 40), 0xFF_FF_00_00)
+            }),
+            ("translucent rect", &|canvas| {
+                canvas.fill_rect(Rect::new(-5, -5, 40, 40), 0x80_FF_00_00)
+            }),
+            ("gradient", &|canvas| {
+                canvas.fill_gradient(canvas.bounds(), &gradient)
+            }),
+            ("stroked rect", &|canvas| {
+                canvas.stroke_rect(Rect::new(3, 4, 8, 9), 3, 0xFF_FF_00_00)
+            }),
+            ("rounded rect", &|canvas| {
+                canvas.fill_rounded_rect(canvas.bounds(), 4.0, 0xFF_FF_00_00)
+            }),
+            ("stroked rounded rect", &|canvas| {
+                canvas.stroke_rounded_rect(Rect::new(2, 2, 12, 12), 4.0, 4.0, 0xFF_FF_00_00)
+            }),
+            ("circle", &|canvas| {
+                canvas.fill_circle(8.0, 8.0, 12.0, 0xFF_FF_00_00)
+            }),
+            ("stroked circle", &|canvas| {
+                canvas.stroke_circle(8.0, 8.0, 5.0, 4.0, 0xFF_FF_00_00)
+            }),
+            ("arc", &|canvas| {
+                canvas.arc(8.0, 8.0, 5.0, 0.0, 6.0, 4.0, 0xFF_FF_00_00)
+            }),
+            ("line", &|canvas| {
+                canvas.line((-10.0, -10.0), (30.0, 30.0), 6.0, 0xFF_FF_00_00)
+            }),
+            ("polygon", &|canvas| {
+                canvas.fill_polygon(&[(-20.0, 8.0), (8.0, -20.0), (36.0, 30.0)], 0xFF_FF_00_00)
+            }),
+            ("image", &|canvas| canvas.blit(&image, -3, 2, 1.0)),
+        ];
+
+        for (name, draw) in primitives {
+            let mut pixels = pixels();
+            let mut canvas = Canvas::new(&mut pixels, SIZE, SIZE);
+            canvas.set_clip(Some(clip));
+            draw(&mut canvas);
+
+            let untouched = u32::from_le_bytes([UNTOUCHED; 4]);
+            let mut drawn = 0;
+            for y in 0..SIZE as i32 {
+                for x in 0..SIZE as i32 {
+                    let pixel = pixel(&pixels, x, y);
+                    if !clip.contains(x, y) {
+                        assert_eq!(pixel, untouched, "{name} drew outside the clip at {x}, {y}");
+                    } else if pixel != untouched {
+                        drawn += 1;
+                    }
+                }
+            }
+            assert!(drawn > 0, "{name} drew nothing inside the clip");
+        }
+    }
+
+    #[test]
+    fn shapes_off_the_canvas_draw_nothing() {
+        let mut pixels = pixels();
+        let mut canvas = Canvas::new(&mut pixels, SIZE, SIZE);
+        canvas.fill_rect(Rect::new(-10, -10, 5, 5), 0xFF_FF_00_00);
+        canvas.fill_rect(Rect::new(20, 0, 5, 5), 0x80_FF_00_00);
+        canvas.fill_circle(-20.0, 8.0, 4.0, 0xFF_FF_00_00);
+        canvas.fill_polygon(&[(30.0, 30.0), (40.0, 30.0), (35.0, 40.0)], 0xFF_FF_00_00);
+        canvas.line((-5.0, -5.0), (-1.0, -5.0), 2.0, 0xFF_FF_00_00);
+        canvas.blit(
+            &Image::from_fn(4, 4, |_, _| 0xFF_FF_00_00),
+            SIZE as i32,
+            0,
+            1.0,
+        );
+        assert!(pixels.iter().all(|&byte| byte == UNTOUCHED));
+    }
+
+    #[test]
+    fn blends_premultiplied() {
+        assert_eq!(premultiply(0x80_FF_80_00, 1.0), 0x80_80_40_00);
+        assert_eq!(premultiply(0xFF_FF_FF_FF, 0.5), 0x80_80_80_80);
+
+        let mut pixels = vec![0; 4];
+        let mut canvas = Canvas::new(&mut pixels, 1, 1);
+        canvas.clear(0xFF_00_00_FF);
+        canvas.fill_rect(Rect::new(0, 0, 1, 1), 0x80_FF_00_00);
+        assert_eq!(
+            u32::from_le_bytes(pixels.try_into().unwrap()),
+            0xFF_80_00_7F
+        );
+    }
+}
@@ -16,6 +16,12 @@ pub enum AppError {
     Calloop(#[from] CalloopError),
     #[error(transparent)]
     Dispatch(#[from] wayland_client::DispatchError),
+    #[error(transparent)]
+    Wayland(#[from] wayland_client::backend::WaylandError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    DBus(#[from] zbus::Error),
 }
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use fontdue::{FontSettings, Metrics};
+
+use crate::xdg;
+
+/// Font files tried first, in order, before settling for any font that can be found.
+const PREFERRED_FONTS: [&str; 4] = [
+    "DejaVuSans.ttf",
+    "NotoSans-Regular.ttf",
+    "LiberationSans-Regular.ttf",
+    "Cantarell-Regular.otf",
+];
+
+/// A font rasterized at a single pixel size, with a glyph cache.
+pub(crate) struct Font {
+    font: fontdue::Font,
+    size: f32,
+    glyphs: HashMap<char, (Metrics, Vec<u8>)>,
+}
+
+impl Font {
+    /// Load the first preferred font found in the XDG font directories.
+    pub fn load(size: f32) -> anyhow::Result<Self> {
+        let files = installed_fonts();
+        let path = PREFERRED_FONTS
+            .iter()
+            .find_map(|name| files.iter().find(|file| file.ends_with(name)))
+            .or_else(|| files.first())
+            .ok_or_else(|| anyhow!("no TrueType or OpenType font found"))?;
+        Self::from_file(path, size)
+    }
+
+    /// Load a font by file name from the XDG font directories, or from an absolute path.
+    pub fn find(name: &str, size: f32) -> anyhow::Result<Self> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return Self::from_file(path, size);
+        }
+
+        let path = installed_fonts()
+            .into_iter()
+            .find(|file| file.file_name() == Some(name.as_ref()))
+            .ok_or_else(|| anyhow!("font {name:?} not found"))?;
+        Self::from_file(&path, size)
+    }
+
+    pub fn from_file(path: &Path, size: f32) -> anyhow::Result<Self> {
+        let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let font = fontdue::Font::from_bytes(data, FontSettings::default())
+            .map_err(|error| anyhow!("parsing {}: {error}", path.display()))?;
+        Ok(Font {
+            font,
+            size,
+            glyphs: HashMap::new(),
+        })
+    }
+
+    /// Distance from the top of a line to its baseline.
+    pub fn ascent(&self) -> i32 {
+        self.font
+            .horizontal_line_metrics(self.size)
+            .map_or(self.size, |metrics| metrics.ascent)
+            .ceil() as i32
+    }
+
+    pub fn line_height(&self) -> i32 {
+        self.font
+            .horizontal_line_metrics(self.size)
+            .map_or(self.size * 1.2, |metrics| metrics.new_line_size)
+            .ceil() as i32
+    }
+
+    /// Width of `text` in pixels.
+    pub fn measure(&mut self, text: &str) -> i32 {
+        text.chars()
+            .map(|c| self.glyph(c).0.advance_width)
+            .sum::<f32>()
+            .round() as i32
+    }
+
+    /// Draw `text` with its baseline at `y` into a premultiplied ARGB canvas, returning the end x.
+    ///
+    /// `color` is straight (not premultiplied) ARGB.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        canvas: &mut [u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: u32,
+    ) -> i32 {
+        let [blue, green, red, alpha] = color.to_le_bytes();
+        self.rasterize(x, y, text, |px, py, coverage| {
+            if py < 0 || py >= height as i32 || px < 0 || px >= width as i32 {
+                return;
+            }
+
+            let index = (py as usize * width as usize + px as usize) * 4;
+            let pixel = &mut canvas[index..index + 4];
+            let source = alpha as u32 * coverage as u32 / 255;
+            let inverse = 255 - source;
+            for (channel, value) in pixel.iter_mut().zip([blue, green, red, 255]) {
+                *channel = ((value as u32 * source + *channel as u32 * inverse) / 255) as u8;
+            }
+        })
+    }
+
+    /// Lay out `text` with its baseline at `y`, calling `plot` with every pixel a glyph covers
+    /// and how much, and return the end x.
+    pub fn rasterize(
+        &mut self,
+        x: i32,
+        y: i32,
+        text: &str,
+        mut plot: impl FnMut(i32, i32, u8),
+    ) -> i32 {
+        let mut pen = x as f32;
+
+        for c in text.chars() {
+            let (metrics, bitmap) = self.glyph(c);
+            let left = pen.round() as i32 + metrics.xmin;
+            let top = y - metrics.height as i32 - metrics.ymin;
+
+            for (row, coverage) in bitmap.chunks_exact(metrics.width.max(1)).enumerate() {
+                for (column, &coverage) in coverage.iter().enumerate() {
+                    if coverage != 0 {
+                        plot(left + column as i32, top + row as i32, coverage);
+                    }
+                }
+            }
+
+            pen += metrics.advance_width;
+        }
+
+        pen.round() as i32
+    }
+
+    fn glyph(&mut self, c: char) -> &(Metrics, Vec<u8>) {
+        let (font, size) = (&self.font, self.size);
+        self.glyphs
+            .entry(c)
+            .or_insert_with(|| font.rasterize(c, size))
+    }
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let home_fonts = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".fonts"));
+    xdg::data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("fonts"))
+        .chain(home_fonts)
+        .collect()
+}
+
+fn installed_fonts() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for dir in font_dirs() {
+        collect_fonts(&dir, &mut files);
+    }
+    files
+}
+
+fn collect_fonts(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect_fonts(&path, files);
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| matches!(extension, "ttf" | "otf"))
+        {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/// Bonus for a match directly following the previous match.
+const CONSECUTIVE_BONUS: i64 = 6;
+/// Bonus for a match at the start of a word.
+const WORD_START_BONUS: i64 = 8;
+
+/// Score `candidate` against `query`, where every query character has to appear in order.
+///
+/// Higher scores are better matches; `None` means the candidate does not match at all.
+pub(crate) fn score(query: &str, candidate: &str, ignore_case: bool) -> Option<i64> {
+    let fold = |c: char| {
+        if ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    };
+
+    let mut wanted = query.chars().map(fold).peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+
+    for (index, c) in candidate.chars().enumerate() {
+        let Some(&next) = wanted.peek() else {
+            break;
+        };
+
+        if fold(c) == next {
+            wanted.next();
+            score += 1;
+
+            let word_start = match previous {
+                None => true,
+                Some(previous) => {
+                    !previous.is_alphanumeric() || (previous.is_lowercase() && c.is_uppercase())
+                }
+            };
+            if word_start {
+                score += WORD_START_BONUS;
+            }
+
+            match last_match {
+                Some(last) if last + 1 == index => score += CONSECUTIVE_BONUS,
+                Some(last) => score -= (index - last - 1).min(8) as i64,
+                None => score -= index.min(8) as i64,
+            }
+            last_match = Some(index);
+        }
+
+        previous = Some(c);
+    }
+
+    wanted.peek().is_none().then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert!(score("ffx", "Firefox", true).is_some());
+        assert_eq!(score("xf", "Firefox", true), None);
+        assert_eq!(score("firefoxx", "Firefox", true), None);
+        assert_eq!(score("a", "", true), None);
+    }
+
+    #[test]
+    fn empty_query_matches_everything_equally() {
+        assert_eq!(score("", "Firefox", false), Some(0));
+        assert_eq!(score("", "", false), Some(0));
+    }
+
+    #[test]
+    fn case_is_only_folded_when_asked() {
+        assert_eq!(score("fire", "Firefox", false), None);
+        assert_eq!(
+            score("Fire", "Firefox", false),
+            score("fire", "Firefox", true)
+        );
+        assert_eq!(score("ÉT", "été", true), score("ét", "été", false));
+    }
+
+    #[test]
+    fn word_starts_and_runs_rank_higher() {
+        let score = |query, candidate| score(query, candidate, true).unwrap();
+        // A prefix beats the same letters later on.
+        assert!(score("fi", "Files") > score("fi", "Profile"));
+        // Consecutive letters beat scattered ones.
+        assert!(score("term", "Terminal") > score("term", "Text Editor Remote"));
+        // Word starts, including camel case, beat letters inside words.
+        assert!(score("gc", "GnomeCalculator") > score("gc", "Bugcheck"));
+        assert!(score("fr", "File Roller") > score("fr", "Firefox"));
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Context;
+use image::imageops::FilterType;
+use resvg::{tiny_skia, usvg};
+use tracing::{info, warn};
+
+use crate::draw::Image;
+use crate::xdg;
+
+/// The theme every other one falls back to, which applications install their icons into.
+const FALLBACK_THEME: &str = "hicolor";
+/// Formats in order of preference. XPM is not supported.
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+/// How a theme directory's icons may be scaled, from `index.theme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SizeKind {
+    Fixed,
+    Scalable { min: u32, max: u32 },
+    Threshold(u32),
+}
+
+/// One of the directories listed by a theme's `index.theme`, such as `48x48/apps`.
+#[derive(Debug, Clone)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: SizeKind,
+}
+
+impl ThemeDir {
+    fn matches(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            SizeKind::Fixed => self.size == size,
+            SizeKind::Scalable { min, max } => (min..=max).contains(&size),
+            SizeKind::Threshold(threshold) => {
+                (self.size.saturating_sub(threshold)..=self.size + threshold).contains(&size)
+            }
+        }
+    }
+
+    /// How far the directory's icons are from `size` at `scale`, in device pixels.
+    fn distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (min, max) = match self.kind {
+            SizeKind::Fixed => (self.size, self.size),
+            SizeKind::Scalable { min, max } => (min, max),
+            SizeKind::Threshold(threshold) => {
+                (self.size.saturating_sub(threshold), self.size + threshold)
+            }
+        };
+        let (min, max) = (min * self.scale, max * self.scale);
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+/// An icon theme as described by the freedesktop icon theme specification.
+#[derive(Debug, Clone)]
+struct IconTheme {
+    name: String,
+    inherits: Vec<String>,
+    dirs: Vec<ThemeDir>,
+}
+
+impl IconTheme {
+    /// Read the theme's `index.theme` from the first base directory that has one.
+    fn load(name: &str, bases: &[PathBuf]) -> Option<Self> {
+        let index = bases
+            .iter()
+            .map(|base| base.join(name).join("index.theme"))
+            .find(|path| path.is_file())?;
+        match fs::read_to_string(&index) {
+            Ok(contents) => Some(Self::parse(name, &contents)),
+            Err(error) => {
+                warn!("Failed to read {}: {error}", index.display());
+                None
+            }
+        }
+    }
+
+    fn parse(name: &str, contents: &str) -> Self {
+        let groups = parse_groups(contents);
+        let header = groups.get("Icon Theme");
+        let list = |key: &str| -> Vec<String> {
+            header
+                .and_then(|header| header.get(key))
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut seen = HashSet::new();
+        let dirs = list("Directories")
+            .into_iter()
+            .chain(list("ScaledDirectories"))
+            .filter(|dir| seen.insert(dir.clone()))
+            .filter_map(|path| {
+                let keys = groups.get(path.as_str())?;
+                let number = |key: &str| keys.get(key).and_then(|value| value.parse().ok());
+                let size = number("Size")?;
+                let kind = match keys.get("Type").map(String::as_str) {
+                    Some("Fixed") => SizeKind::Fixed,
+                    Some("Scalable") => SizeKind::Scalable {
+                        min: number("MinSize").unwrap_or(size),
+                        max: number("MaxSize").unwrap_or(size),
+                    },
+                    _ => SizeKind::Threshold(number("Threshold").unwrap_or(2)),
+                };
+                Some(ThemeDir {
+                    path,
+                    size,
+                    scale: number("Scale").unwrap_or(1).max(1),
+                    kind,
+                })
+            })
+            .collect();
+
+        IconTheme {
+            name: name.to_string(),
+            inherits: list("Inherits"),
+            dirs,
+        }
+    }
+
+    /// The file for `icon` in this theme: one made for the size if there is one, otherwise
+    /// the closest in size.
+    fn lookup(&self, icon: &str, size: u32, scale: u32, bases: &[PathBuf]) -> Option<PathBuf> {
+        let find = |dir: &ThemeDir| {
+            bases
+                .iter()
+                .flat_map(|base| {
+                    EXTENSIONS.iter().map(move |extension| {
+                        base.join(&self.name)
+                            .join(&dir.path)
+                            .join(format!("{icon}.{extension}"))
+                    })
+                })
+                .find(|path| path.is_file())
+        };
+
+        if let Some(path) = self
+            .dirs
+            .iter()
+            .filter(|dir| dir.matches(size, scale))
+            .find_map(find)
+        {
+            return Some(path);
+        }
+
+        let mut closest = None;
+        let mut best = u32::MAX;
+        for dir in &self.dirs {
+            let distance = dir.distance(size, scale);
+            if distance >= best {
+                continue;
+            }
+            if let Some(path) = find(dir) {
+                closest = Some(path);
+                best = distance;
+            }
+        }
+        closest
+    }
+}
+
+/// Looks icons up by name in the user's theme and renders them, keeping every result.
+pub(crate) struct Icons {
+    /// Directories themes are found in, in order of preference.
+    bases: Vec<PathBuf>,
+    /// The user's theme, then what it inherits from depth first, with hicolor last.
+    themes: Vec<IconTheme>,
+    cache: HashMap<(String, u32, u32), Option<Rc<Image>>>,
+}
+
+impl Icons {
+    /// Icons from `theme`, or only the fallback theme without one.
+    pub fn new(theme: Option<&str>) -> Self {
+        let bases: Vec<PathBuf> = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".icons"))
+            .into_iter()
+            .chain(xdg::data_dirs().into_iter().map(|dir| dir.join("icons")))
+            .chain([PathBuf::from("/usr/share/pixmaps")])
+            .collect();
+        Self::load(theme, bases)
+    }
+
+    /// Icons from `theme` and the themes it inherits, searched for in `bases`.
+    fn load(theme: Option<&str>, bases: Vec<PathBuf>) -> Self {
+        let mut themes = Vec::new();
+        // The fallback is searched last, even when a theme inherits from it.
+        let mut visited = HashSet::from([FALLBACK_THEME.to_string()]);
+        let mut pending: Vec<String> = theme.into_iter().map(str::to_string).collect();
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            match IconTheme::load(&name, &bases) {
+                Some(theme) => {
+                    // Parents are searched in the order listed, each with its own parents first.
+                    pending.extend(theme.inherits.iter().rev().cloned());
+                    themes.push(theme);
+                }
+                None => warn!("Icon theme {name:?} not found"),
+            }
+        }
+        themes.extend(IconTheme::load(FALLBACK_THEME, &bases));
+        info!(
+            "Icon themes: {:?}",
+            themes.iter().map(|theme| &theme.name).collect::<Vec<_>>()
+        );
+
+        Icons {
+            bases,
+            themes,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The icon called `name`, or at an absolute path or `file://` URI, rendered to fit a
+    /// square `size` pixels wide at the buffer `scale`.
+    pub fn get(&mut self, name: &str, size: u32, scale: u32) -> Option<Rc<Image>> {
+        let key = (name.to_string(), size, scale);
+        if let Some(icon) = self.cache.get(&key) {
+            return icon.clone();
+        }
+
+        let icon = self.find(name, size, scale).and_then(|path| {
+            render(&path, size * scale)
+                .map_err(|error| warn!("Failed to load icon {}: {error:#}", path.display()))
+                .ok()
+                .map(Rc::new)
+        });
+        self.cache.insert(key, icon.clone());
+        icon
+    }
+
+    fn find(&self, name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let path = Path::new(name.strip_prefix("file://").unwrap_or(name));
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        if name.is_empty() || name.contains('/') {
+            return None;
+        }
+
+        self.themes
+            .iter()
+            .find_map(|theme| theme.lookup(name, size, scale, &self.bases))
+            .or_else(|| {
+                // Icons installed outside of any theme.
+                self.bases
+                    .iter()
+                    .flat_map(|base| {
+                        EXTENSIONS
+                            .iter()
+                            .map(move |extension| base.join(format!("{name}.{extension}")))
+                    })
+                    .find(|path| path.is_file())
+            })
+    }
+}
+
+/// Decode or rasterize the image at `path` to fit a square `pixels` wide.
+fn render(path: &Path, pixels: u32) -> anyhow::Result<Image> {
+    if path.extension().is_some_and(|extension| extension == "svg") {
+        return render_svg(path, pixels);
+    }
+
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    let factor = pixels as f32 / width.max(height).max(1) as f32;
+    let (width, height) = (
+        ((width as f32 * factor).round() as u32).max(1),
+        ((height as f32 * factor).round() as u32).max(1),
+    );
+    let image = if image.dimensions() == (width, height) {
+        image
+    } else {
+        image::imageops::resize(&image, width, height, FilterType::Triangle)
+    };
+    Ok(Image::from_fn(width, height, |x, y| {
+        let [red, green, blue, alpha] = image.get_pixel(x, y).0;
+        u32::from_be_bytes([alpha, red, green, blue])
+    }))
+}
+
+fn render_svg(path: &Path, pixels: u32) -> anyhow::Result<Image> {
+    let data = fs::read(path)?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default())?;
+    let size = tree.size();
+    let factor = pixels as f32 / size.width().max(size.height());
+    let width = ((size.width() * factor).round() as u32).max(1);
+    let height = ((size.height() * factor).round() as u32).max(1);
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).context("empty icon")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(factor, factor),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny-skia's pixels are premultiplied RGBA.
+    let pixels = pixmap
+        .data()
+        .chunks_exact(4)
+        .map(|pixel| u32::from_be_bytes([pixel[3], pixel[0], pixel[1], pixel[2]]))
+        .collect();
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// The keys of each `[group]` in an ini style file.
+fn parse_groups(contents: &str) -> HashMap<&str, HashMap<&str, String>> {
+    let mut groups: HashMap<&str, HashMap<&str, String>> = HashMap::new();
+    let mut group = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            group = Some(name);
+            groups.entry(name).or_default();
+            continue;
+        }
+        if let (Some(group), Some((key, value))) = (group, line.split_once('=')) {
+            groups
+                .entry(group)
+                .or_default()
+                .insert(key.trim(), value.trim().to_string());
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of icon themes, removed when dropped.
+    struct Base(PathBuf);
+
+    impl Base {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("icons-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Base(path)
+        }
+
+        fn theme(&self, name: &str, index: &str) {
+            fs::create_dir_all(self.0.join(name)).unwrap();
+            fs::write(self.0.join(name).join("index.theme"), index).unwrap();
+        }
+
+        /// A solid square PNG, `size` wide, at `path` under the base.
+        fn png(&self, path: &str, size: u32, color: [u8; 4]) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image::RgbaImage::from_pixel(size, size, image::Rgba(color))
+                .save(&path)
+                .unwrap();
+            path
+        }
+
+        fn svg(&self, path: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(
+                &path,
+                r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16"><rect width="16" height="16" fill="#0000ff"/></svg>"##,
+            )
+            .unwrap();
+            path
+        }
+    }
+
+    impl Drop for Base {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const SIZES: &str = "
+[Icon Theme]
+Name=Test
+Inherits=Parent, Other
+Directories=16x16/apps,48x48/apps,scalable/apps
+ScaledDirectories=16x16@2/apps,48x48/apps
+
+[16x16/apps]
+Size=16
+Type=Fixed
+
+[16x16@2/apps]
+Size=16
+Scale=2
+Type=Fixed
+
+[48x48/apps]
+Size=48
+
+[scalable/apps]
+Size=64
+Type=Scalable
+MinSize=8
+MaxSize=512
+";
+
+    #[test]
+    fn parses_index_theme() {
+        let theme = IconTheme::parse("Test", SIZES);
+        assert_eq!(theme.inherits, ["Parent", "Other"]);
+        let dirs: Vec<_> = theme
+            .dirs
+            .iter()
+            .map(|dir| (dir.path.as_str(), dir.size, dir.scale, dir.kind))
+            .collect();
+        assert_eq!(
+            dirs,
+            [
+                ("16x16/apps", 16, 1, SizeKind::Fixed),
+                ("48x48/apps", 48, 1, SizeKind::Threshold(2)),
+                (
+                    "scalable/apps",
+                    64,
+                    1,
+                    SizeKind::Scalable { min: 8, max: 512 }
+                ),
+                ("16x16@2/apps", 16, 2, SizeKind::Fixed),
+            ]
+        );
+    }
+
+    #[test]
+    fn directories_match_by_size_and_scale() {
+        let theme = IconTheme::parse("Test", SIZES);
+        let [fixed, threshold, scalable, scaled] = &theme.dirs[..] else {
+            panic!("expected four directories");
+        };
+        assert!(fixed.matches(16, 1) && !fixed.matches(17, 1) && !fixed.matches(16, 2));
+        assert!(scaled.matches(16, 2));
+        assert!(threshold.matches(46, 1) && threshold.matches(50, 1) && !threshold.matches(51, 1));
+        assert!(scalable.matches(8, 1) && scalable.matches(512, 1) && !scalable.matches(600, 1));
+
+        assert_eq!(fixed.distance(16, 1), 0);
+        assert_eq!(fixed.distance(24, 1), 8);
+        assert_eq!(fixed.distance(8, 2), 0);
+        assert_eq!(scaled.distance(16, 1), 16);
+        assert_eq!(threshold.distance(32, 1), 14);
+    }
+
+    #[test]
+    fn looks_up_the_best_size_then_the_closest() {
+        let base = Base::new("lookup");
+        base.theme("Test", SIZES);
+        let bases = [base.0.clone()];
+        let small = base.png("Test/16x16/apps/app.png", 16, [0; 4]);
+        base.svg("Test/16x16/apps/app.svg");
+        let large = base.png("Test/48x48/apps/app.png", 48, [0; 4]);
+        let theme = IconTheme::parse("Test", SIZES);
+
+        // PNG is preferred over SVG in the same directory.
+        assert_eq!(theme.lookup("app", 16, 1, &bases), Some(small.clone()));
+        assert_eq!(theme.lookup("app", 48, 1, &bases), Some(large.clone()));
+        // Nothing made for these sizes, so the closest.
+        assert_eq!(theme.lookup("app", 20, 1, &bases), Some(small));
+        assert_eq!(theme.lookup("app", 40, 1, &bases), Some(large));
+        assert_eq!(theme.lookup("missing", 16, 1, &bases), None);
+    }
+
+    #[test]
+    fn falls_back_through_inherited_themes() {
+        let base = Base::new("inherit");
+        let dirs = "Directories=apps\n\n[apps]\nSize=16\nType=Fixed\n";
+        base.theme("Test", &format!("[Icon Theme]\nInherits=Parent\n{dirs}"));
+        // Inheriting back must not loop.
+        base.theme(
+            "Parent",
+            &format!("[Icon Theme]\nInherits=Test,hicolor\n{dirs}"),
+        );
+        base.theme("hicolor", &format!("[Icon Theme]\n{dirs}"));
+        let own = base.png("Test/apps/both.png", 16, [0; 4]);
+        base.png("Parent/apps/both.png", 16, [0; 4]);
+        let inherited = base.png("Parent/apps/parent.png", 16, [0; 4]);
+        let fallback = base.png("hicolor/apps/fallback.png", 16, [0; 4]);
+        let loose = base.png("loose.png", 16, [0; 4]);
+
+        let icons = Icons::load(Some("Test"), vec![base.0.clone()]);
+        let names: Vec<_> = icons
+            .themes
+            .iter()
+            .map(|theme| theme.name.as_str())
+            .collect();
+        assert_eq!(names, ["Test", "Parent", "hicolor"]);
+        assert_eq!(icons.find("both", 16, 1), Some(own));
+        assert_eq!(icons.find("parent", 16, 1), Some(inherited));
+        assert_eq!(icons.find("fallback", 16, 1), Some(fallback.clone()));
+        assert_eq!(icons.find("loose", 16, 1), Some(loose));
+        assert_eq!(icons.find("apps/fallback", 16, 1), None);
+        assert_eq!(icons.find("", 16, 1), None);
+
+        let uri = format!("file://{}", fallback.display());
+        assert_eq!(icons.find(&uri, 16, 1), Some(fallback));
+    }
+
+    #[test]
+    fn renders_to_the_requested_size() {
+        let base = Base::new("render");
+        let png = base.png("red.png", 16, [0xFF, 0, 0, 0x80]);
+        base.svg("blue.svg");
+        let mut icons = Icons::load(None, vec![base.0.clone()]);
+
+        let red = icons.get(png.to_str().unwrap(), 16, 2).unwrap();
+        assert_eq!((red.width, red.height), (32, 32));
+        assert_eq!(red.pixels[0], premultiplied(0x80_FF_00_00));
+        let blue = icons.get("blue", 12, 1).unwrap();
+        assert_eq!((blue.width, blue.height), (12, 12));
+        assert_eq!(blue.pixels[6 * 12 + 6], 0xFF_00_00_FF);
+
+        // Results are kept, including missing icons.
+        assert!(Rc::ptr_eq(&icons.get("blue", 12, 1).unwrap(), &blue));
+        assert!(icons.get("missing", 12, 1).is_none());
+        assert!(icons.cache.contains_key(&("missing".to_string(), 12, 1)));
+    }
+
+    fn premultiplied(color: u32) -> u32 {
+        crate::draw::premultiply(color, 1.0)
+    }
+}
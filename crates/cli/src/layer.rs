@@ -1,30 +1,69 @@
+use std::io;
+use std::time::Instant;
+
+use raster::Gradient;
 use smithay_client_toolkit::{
     compositor::CompositorHandler,
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    data_device_manager::{
+        data_device::{DataDevice, DataDeviceHandler},
+        data_offer::{DataDeviceOffer, DataOfferHandler, DragOffer},
+        data_source::DataSourceHandler,
+        WritePipe,
+    },
+    delegate_compositor, delegate_data_device, delegate_data_device_manager, delegate_data_offer,
+    delegate_data_source, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
     delegate_registry, delegate_seat, delegate_shm,
     output::{OutputHandler, OutputState},
+    reexports::calloop::LoopHandle,
     reexports::client::{
         globals::GlobalList,
-        protocol::{wl_output, wl_seat, wl_shm, wl_surface},
+        protocol::{
+            wl_data_device_manager::DndAction, wl_data_source::WlDataSource, wl_output, wl_seat,
+            wl_surface,
+        },
         Connection, QueueHandle,
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         keyboard::{KeyEvent, KeyboardHandler, Modifiers},
-        pointer::{PointerEvent, PointerEventKind, PointerHandler},
+        pointer::{PointerEvent, PointerEventKind, PointerHandler, BTN_MIDDLE},
         Capability, SeatHandler, SeatState,
     },
     shell::{
-        wlr_layer::{LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
+        wlr_layer::{Anchor, LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
         WaylandSurface,
     },
     shm::slot::SlotPool,
     shm::{Shm, ShmHandler},
 };
-use tracing::info;
+use tracing::{debug, info, warn};
 use wayland_client::protocol::{wl_keyboard, wl_pointer};
-use xkbcommon::xkb::keysyms;
+
+use crate::animation::{FrameClock, Tween};
+use crate::clipboard::Clipboard;
+use crate::clock;
+use crate::commands::LayerArgs;
+use crate::config::{Action, Config, ConfigHandler, Module};
+use crate::control::{ControlCommand, ControlHandler, ControlSocket, Edge, LayerState, Reply};
+use crate::draw::{Canvas, Rect};
+use crate::font::Font;
+use crate::layer_properties::{LayerProperties, Margin};
+use crate::menu::{Menu, MenuAction, MenuEvent};
+use crate::pixel::{PixelFormat, PixelWriter};
+use crate::presentation::{delegate_presentation, Presentation, PresentationHandler};
+use crate::primary_selection::{
+    delegate_primary_selection, PrimarySelection, PrimarySelectionHandler,
+};
+use crate::process::spawn;
+use crate::redraw::RedrawScheduler;
+use crate::regions::{PointerRegions, RegionEvent};
+use crate::scroll::{Scroll, ScrollState};
+use crate::text_buffer::{EditAction, TextBuffer};
+use crate::widget::{Kind, Widget};
+
+/// Horizontal gap between the bar's modules.
+const MODULE_SPACING: i32 = 16;
 
 pub(crate) struct SimpleLayer {
     pub(crate) registry_state: RegistryState,
@@ -37,20 +76,70 @@ pub(crate) struct SimpleLayer {
     pub(crate) pool: SlotPool,
     pub(crate) width: u32,
     pub(crate) height: u32,
-    pub(crate) shift: Option<u32>,
+    /// How far the gradient has moved while animated, in pixels.
+    pub(crate) shift: Option<f32>,
     pub(crate) layer: LayerSurface,
     pub(crate) keyboard: Option<wl_keyboard::WlKeyboard>,
     pub(crate) keyboard_focus: bool,
     pub(crate) pointer: Option<wl_pointer::WlPointer>,
+    pub(crate) modifiers: Modifiers,
+    pub(crate) text: TextBuffer,
+    pub(crate) clipboard: Clipboard,
+    pub(crate) primary_selection: PrimarySelection,
+    pub(crate) loop_handle: LoopHandle<'static, SimpleLayer>,
+    /// Shown instead of the gradient when running as a launcher or dmenu.
+    pub(crate) menu: Option<Menu>,
+    /// The menu was closed without choosing anything.
+    pub(crate) cancelled: bool,
+    /// Whether the surface is mapped, it is unmapped by the `hide` command.
+    pub(crate) visible: bool,
+    /// The properties last sent to the compositor.
+    pub(crate) properties: LayerProperties,
+    /// Draws the text over the gradient.
+    pub(crate) font: Option<Font>,
+    /// The modules as laid out for the last frame, for routing pointer events.
+    pub(crate) root: Option<Widget>,
+    /// Which module the pointer is over or pressing.
+    pub(crate) regions: PointerRegions,
+    pub(crate) scroll: ScrollState,
+    pub(crate) control: Option<ControlSocket>,
+    /// Colors, key bindings and modules of the bar, replaced when the config file changes.
+    pub(crate) config: Config,
+    /// Placement given on the command line, which wins over the config file's.
+    pub(crate) overrides: LayerArgs,
+    /// Converts drawing to the buffer format, chosen again for every frame.
+    pub(crate) pixels: PixelWriter,
+    pub(crate) redraw: RedrawScheduler,
+    /// Kept for redrawing after changes made outside of Wayland events.
+    pub(crate) qh: QueueHandle<SimpleLayer>,
+    /// What the clock modules showed last, to redraw when it changes.
+    pub(crate) clocks: Vec<String>,
+    pub(crate) clock: FrameClock,
+    pub(crate) transition: Option<Transition>,
+    /// Frame statistics, shown over the bar while enabled.
+    pub(crate) presentation: Presentation,
+}
+
+/// The surface sliding in as it is shown, or fading out before it is hidden.
+pub(crate) struct Transition {
+    /// Where the contents are drawn relative to their place, in pixels.
+    offset: Tween<(f32, f32)>,
+    opacity: Tween<f32>,
+    /// Unmap the surface once done.
+    hide: bool,
 }
 
 impl SimpleLayer {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         globals: &GlobalList,
         qh: &QueueHandle<Self>,
+        loop_handle: LoopHandle<'static, SimpleLayer>,
         shm: Shm,
         pool: SlotPool,
         layer: LayerSurface,
+        properties: LayerProperties,
+        menu: Option<Menu>,
     ) -> Self {
         SimpleLayer {
             registry_state: RegistryState::new(globals),
@@ -68,48 +157,101 @@ impl SimpleLayer {
             keyboard: None,
             keyboard_focus: false,
             pointer: None,
+            modifiers: Modifiers::default(),
+            text: TextBuffer::default(),
+            clipboard: Clipboard::new(globals, qh),
+            primary_selection: PrimarySelection::new(globals, qh),
+            loop_handle,
+            menu,
+            cancelled: false,
+            visible: true,
+            properties,
+            font: None,
+            root: None,
+            regions: PointerRegions::default(),
+            scroll: ScrollState::default(),
+            control: None,
+            config: Config::default(),
+            overrides: LayerArgs::default(),
+            pixels: PixelWriter::new(PixelFormat::Argb8888),
+            redraw: RedrawScheduler::default(),
+            qh: qh.clone(),
+            clocks: Vec::new(),
+            clock: FrameClock::default(),
+            transition: None,
+            presentation: Presentation::new(globals, qh),
         }
     }
 
     pub fn draw(&mut self, qh: &QueueHandle<Self>) {
+        let started = Instant::now();
         let width = self.width;
         let height = self.height;
-        let stride = self.width as i32 * 4;
 
-        let (buffer, canvas) = self
+        // Transitions make the surface see-through on the way.
+        let opaque =
+            (self.menu.is_some() || self.config.theme.is_opaque()) && self.transition.is_none();
+        let format = PixelFormat::choose(self.config.render.format, self.shm.formats(), opaque);
+        if format != self.pixels.format() {
+            info!("Drawing in {format:?}");
+            if let Some(preferred) = self
+                .config
+                .render
+                .format
+                .filter(|&preferred| preferred != format)
+            {
+                warn!("{preferred:?} is not supported for this surface, using {format:?}");
+            }
+            self.pixels = PixelWriter::new(format);
+        }
+
+        let (buffer, buffer_canvas) = self
             .pool
             .create_buffer(
                 width as i32,
                 height as i32,
-                stride,
-                wl_shm::Format::Argb8888,
+                self.pixels.stride(width),
+                format.wl_format(),
             )
             .expect("create buffer");
 
-        // Draw to the window:
-        {
-            let shift = self.shift.unwrap_or(0);
-            canvas
-                .chunks_exact_mut(4)
-                .enumerate()
-                .for_each(|(index, chunk)| {
-                    let x = ((index + shift as usize) % width as usize) as u32;
-                    let y = (index / width as usize) as u32;
+        self.root = match (&self.menu, &mut self.font) {
+            (None, Some(font)) => {
+                let mut root = bar(&self.config, &self.text, self.keyboard_focus);
+                root.layout(Rect::new(0, 0, width as i32, height as i32), font);
+                Some(root)
+            }
+            _ => None,
+        };
 
-                    let a = 0xFF;
-                    let r = u32::min(((width - x) * 0xFF) / width, ((height - y) * 0xFF) / height);
-                    let g = u32::min((x * 0xFF) / width, ((height - y) * 0xFF) / height);
-                    let b = u32::min(((width - x) * 0xFF) / width, (y * 0xFF) / height);
-                    let color = (a << 24) + (r << 16) + (g << 8) + b;
+        // Draw to the window:
+        self.pixels.render(buffer_canvas, width, height, |pixels| {
+            match &mut self.menu {
+                Some(menu) => menu.draw(pixels, width, height),
+                None => {
+                    let mut canvas = Canvas::new(pixels, width, height);
+                    let theme = &self.config.theme;
+                    let shift = self.shift.unwrap_or(0.0) as u32;
+                    theme.draw_frame(&mut canvas, &Gradient::new(width, height, shift));
 
-                    let array: &mut [u8; 4] = chunk.try_into().unwrap();
-                    *array = color.to_le_bytes();
-                });
+                    if let (Some(root), Some(font)) = (&self.root, &mut self.font) {
+                        root.paint(&mut canvas, font, &self.regions);
+                        if self.presentation.enabled {
+                            self.presentation
+                                .stats
+                                .paint(&mut canvas, font, |summary| vec![summary.to_string()]);
+                        }
+                    }
+                }
+            }
 
-            if let Some(shift) = &mut self.shift {
-                *shift = (*shift + 1) % width;
+            if let Some(transition) = &self.transition {
+                let mut canvas = Canvas::new(pixels, width, height);
+                let (dx, dy) = transition.offset.value();
+                canvas.translate(dx.round() as i32, dy.round() as i32);
+                canvas.fade(transition.opacity.value());
             }
-        }
+        });
 
         // Damage the entire window
         self.layer
@@ -117,9 +259,9 @@ impl SimpleLayer {
             .damage_buffer(0, 0, width as i32, height as i32);
 
         // Request our next frame
-        self.layer
-            .wl_surface()
-            .frame(qh, self.layer.wl_surface().clone());
+        self.redraw.request(self.layer.wl_surface(), qh);
+        self.presentation
+            .feedback(self.layer.wl_surface(), qh, started.elapsed());
 
         // Attach and commit to present.
         buffer
@@ -131,6 +273,258 @@ impl SimpleLayer {
         // useful if you do damage tracking, since you don't need to redraw the undamaged parts
         // of the canvas.
     }
+
+    /// Advance the animations to the frame callback timestamp `time`, returning whether any
+    /// of them still needs frames drawn.
+    fn animate(&mut self, time: u32) -> bool {
+        let elapsed = self.clock.tick(time);
+        if let Some(shift) = &mut self.shift {
+            *shift = (*shift + self.config.animation.speed * elapsed) % self.width as f32;
+        }
+
+        let Some(transition) = &mut self.transition else {
+            return self.shift.is_some();
+        };
+        transition.offset.update(time);
+        transition.opacity.update(time);
+        if transition.offset.is_done() && transition.opacity.is_done() {
+            let hide = transition.hide;
+            self.transition = None;
+            if hide {
+                self.unmap();
+                return false;
+            }
+        }
+        // The last frame of a transition still has to be drawn.
+        true
+    }
+
+    /// Slide in from the anchored edge, or fade in when anchored to none or opposite ones.
+    fn show_transition(&self) -> Transition {
+        let animation = self.config.animation;
+        let anchor = self.properties.anchor;
+        let side = |start: Anchor, end: Anchor, size: u32| match (
+            anchor.contains(start),
+            anchor.contains(end),
+        ) {
+            (true, false) => -(size as f32),
+            (false, true) => size as f32,
+            _ => 0.0,
+        };
+        // In a corner, slide vertically.
+        let vertical = side(Anchor::TOP, Anchor::BOTTOM, self.height);
+        let offset = if vertical != 0.0 {
+            (0.0, vertical)
+        } else {
+            (side(Anchor::LEFT, Anchor::RIGHT, self.width), 0.0)
+        };
+
+        if offset == (0.0, 0.0) {
+            Transition {
+                offset: Tween::fixed(offset),
+                opacity: Tween::new(0.0, 1.0, animation.duration, animation.easing),
+                hide: false,
+            }
+        } else {
+            Transition {
+                offset: Tween::new(offset, (0.0, 0.0), animation.duration, animation.easing),
+                opacity: Tween::fixed(1.0),
+                hide: false,
+            }
+        }
+    }
+
+    /// Move from wherever the current transition is to fully in place and at `opacity`, then
+    /// unmap the surface if `hide` is set.
+    fn retarget_transition(&mut self, opacity: f32, hide: bool) {
+        let animation = self.config.animation;
+        let (offset, current) = match &self.transition {
+            Some(transition) => (transition.offset, transition.opacity),
+            None => (Tween::fixed((0.0, 0.0)), Tween::fixed(1.0)),
+        };
+        self.transition = Some(Transition {
+            offset: offset.retarget((0.0, 0.0), animation.duration, animation.easing),
+            opacity: current.retarget(opacity, animation.duration, animation.easing),
+            hide,
+        });
+        self.invalidate();
+    }
+
+    /// Fading out before being unmapped.
+    fn hiding(&self) -> bool {
+        self.transition
+            .as_ref()
+            .is_some_and(|transition| transition.hide)
+    }
+
+    /// Take the surface off the screen until it is shown again.
+    fn unmap(&mut self) {
+        self.visible = false;
+        self.transition = None;
+        self.layer.wl_surface().attach(None, 0, 0);
+        self.layer.commit();
+    }
+
+    /// Draw again at the next frame, unless the surface is waiting to be configured or hidden,
+    /// which draws everything anyway once it is shown.
+    pub fn invalidate(&mut self) {
+        if self.visible && !self.first_configure {
+            self.redraw.invalidate(self.layer.wl_surface(), &self.qh);
+        }
+    }
+
+    /// Redraw if the text of a clock module changed, called every second.
+    pub fn update_clocks(&mut self) {
+        let clocks: Vec<String> = self
+            .config
+            .modules
+            .iter()
+            .filter_map(|module| match module {
+                Module::Clock { format, .. } => Some(clock::now(format)),
+                Module::Text { .. } => None,
+            })
+            .collect();
+        if clocks != self.clocks {
+            self.clocks = clocks;
+            self.invalidate();
+        }
+    }
+
+    /// Send whatever differs from the current properties and commit it, unless the compositor
+    /// would reject them.
+    ///
+    /// A hidden surface only records them, they are sent in full when it is shown again.
+    pub fn reconfigure(&mut self, properties: LayerProperties) -> Result<(), String> {
+        properties.validate()?;
+        if self.visible && properties.apply(Some(&self.properties), &self.layer) {
+            self.layer.commit();
+        }
+        self.properties = properties;
+        Ok(())
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        self.invalidate();
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Hide => {
+                self.control(ControlCommand::Hide);
+            }
+            Action::ClearText => self.text.set_text(""),
+            Action::ToggleAnimation => self.shift = self.shift.xor(Some(0.0)),
+            Action::ToggleStats => self.presentation.toggle(&self.loop_handle),
+            Action::Exec(command) => {
+                if let Err(error) = spawn(&command) {
+                    warn!("Failed to run {command:?}: {error}");
+                }
+            }
+        }
+    }
+
+    /// Run what the config says a click on the module keyed `key` does.
+    fn module_clicked(&mut self, key: &str, button: u32, count: u32) {
+        let action = key
+            .strip_prefix("module.")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.config.modules.get(index))
+            .and_then(|module| module.on_click().get(button, count))
+            .cloned();
+        info!("Click {button:x} x{count} on {key}: {action:?}");
+        if let Some(action) = action {
+            self.run_action(action);
+        }
+    }
+
+    /// Run what the config says scrolling over the module under the pointer does.
+    fn scrolled(&mut self, scroll: Scroll) {
+        let (x, y) = (scroll.position.0 as i32, scroll.position.1 as i32);
+        let module = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit(x, y))
+            .and_then(|key| key.strip_prefix("module."))
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.config.modules.get(index));
+        let Some(module) = module else {
+            return;
+        };
+        let actions: Vec<Action> = module
+            .on_scroll()
+            .get(&scroll)
+            .into_iter()
+            .cloned()
+            .collect();
+        if !actions.is_empty() {
+            info!("Scroll {scroll:?}: {actions:?}");
+        }
+        for action in actions {
+            self.run_action(action);
+        }
+    }
+
+    /// Insert pasted text into the menu's query, or the text buffer without a menu.
+    fn insert_text(&mut self, text: &str) {
+        self.invalidate();
+        match &mut self.menu {
+            Some(menu) => menu.insert(text),
+            None => {
+                self.text.insert(text);
+                info!("Text: {:?}", self.text.text());
+            }
+        }
+    }
+
+    /// Act on the highlighted menu item, or the typed text if `input` is set, and exit.
+    fn accept_menu(&mut self, input: bool) {
+        let Some(menu) = &self.menu else {
+            return;
+        };
+        let selected = menu.selected().filter(|_| !input);
+
+        match &menu.action {
+            MenuAction::Launch(entries) => {
+                // Launching needs an application, not just text.
+                let Some(selected) = selected else {
+                    return;
+                };
+                let entry = &entries[selected];
+                if let Err(error) = entry.launch() {
+                    warn!("Failed to launch {}: {error}", entry.id);
+                }
+            }
+            // Stdout is the menu's result, so it carries nothing else.
+            MenuAction::Print => {
+                if let Err(error) = menu.print_choice(&mut io::stdout().lock(), input) {
+                    warn!("Failed to print the choice: {error}");
+                }
+            }
+        }
+        self.exit = true;
+    }
+}
+
+/// The modules left to right inside the theme's border and padding, each keyed by its index.
+fn bar(config: &Config, text: &TextBuffer, focused: bool) -> Widget {
+    let theme = &config.theme;
+    let modules = config
+        .modules
+        .iter()
+        .enumerate()
+        .map(|(index, module)| {
+            let widget = match module {
+                Module::Text { .. } => Widget::new(Kind::Label {
+                    text: text.text().to_string(),
+                    color: theme.foreground.with_opacity(theme.opacity),
+                    caret: focused.then(|| text.cursor()),
+                }),
+                Module::Clock { format, .. } => {
+                    Widget::label(clock::now(format), theme.accent.with_opacity(theme.opacity))
+                }
+            };
+            widget.with_key(format!("module.{index}"))
+        })
+        .collect();
+    Widget::row(MODULE_SPACING, modules).with_padding(theme.content_offset())
 }
 
 impl CompositorHandler for SimpleLayer {
@@ -149,9 +543,13 @@ impl CompositorHandler for SimpleLayer {
         _conn: &Connection,
         qh: &QueueHandle<Self>,
         _surface: &wl_surface::WlSurface,
-        _time: u32,
+        time: u32,
     ) {
-        self.draw(qh);
+        let animating = self.animate(time);
+        // A callback still pending when the surface was hidden must not map it again.
+        if self.redraw.frame(animating) && self.visible {
+            self.draw(qh);
+        }
     }
 }
 
@@ -206,10 +604,13 @@ impl LayerShellHandler for SimpleLayer {
             self.height = configure.new_size.1;
         }
 
-        // Initiate the first draw.
-        if self.first_configure {
+        // Initiate the first draw, also after the surface was shown again.
+        if self.first_configure && self.visible {
             self.first_configure = false;
+            self.transition = Some(self.show_transition());
             self.draw(qh);
+        } else {
+            self.invalidate();
         }
     }
 }
@@ -235,6 +636,8 @@ impl SeatHandler for SimpleLayer {
                 .get_keyboard(qh, &seat, None)
                 .expect("Failed to create keyboard");
             self.keyboard = Some(keyboard);
+            self.clipboard.add_seat(qh, &seat);
+            self.primary_selection.add_seat(qh, &seat);
         }
 
         if capability == Capability::Pointer && self.pointer.is_none() {
@@ -257,6 +660,8 @@ impl SeatHandler for SimpleLayer {
         if capability == Capability::Keyboard && self.keyboard.is_some() {
             info!("Unset keyboard capability");
             self.keyboard.take().unwrap().release();
+            self.clipboard.remove_seat();
+            self.primary_selection.remove_seat();
         }
 
         if capability == Capability::Pointer && self.pointer.is_some() {
@@ -282,6 +687,7 @@ impl KeyboardHandler for SimpleLayer {
         if self.layer.wl_surface() == surface {
             info!("Keyboard focus on window with pressed syms: {keysyms:?}");
             self.keyboard_focus = true;
+            self.invalidate();
         }
     }
 
@@ -296,22 +702,70 @@ impl KeyboardHandler for SimpleLayer {
         if self.layer.wl_surface() == surface {
             info!("Release keyboard focus on window");
             self.keyboard_focus = false;
+            self.invalidate();
         }
     }
 
     fn press_key(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
-        _: u32,
+        serial: u32,
         event: KeyEvent,
     ) {
         info!("Key press: {event:?}");
+        self.invalidate();
 
-        // press 'esc' to exit
-        if event.keysym == keysyms::KEY_Escape {
-            self.exit = true;
+        if let Some(menu) = &mut self.menu {
+            match menu.handle_key(&event, &self.modifiers) {
+                MenuEvent::Accept => self.accept_menu(false),
+                MenuEvent::AcceptInput => self.accept_menu(true),
+                MenuEvent::Cancel => {
+                    self.cancelled = true;
+                    self.exit = true;
+                }
+                MenuEvent::Paste => {
+                    self.clipboard
+                        .paste(&self.loop_handle, |layer: &mut SimpleLayer, text| {
+                            layer.insert_text(&text);
+                        });
+                }
+                MenuEvent::Changed | MenuEvent::Ignored => {}
+            }
+            return;
+        }
+
+        let action = self
+            .config
+            .keybindings
+            .iter()
+            .find(|(binding, _)| binding.matches(&event, &self.modifiers))
+            .map(|(_, action)| action.clone());
+        if let Some(action) = action {
+            info!("Key binding {action:?}");
+            self.run_action(action);
+            return;
+        }
+
+        match self.text.handle_key(&event, &self.modifiers) {
+            EditAction::Copy => {
+                let text = self.text.selected_text().unwrap_or(self.text.text());
+                self.clipboard.copy(qh, text, serial);
+            }
+            EditAction::Paste => {
+                self.clipboard
+                    .paste(&self.loop_handle, |layer: &mut SimpleLayer, text| {
+                        layer.insert_text(&text);
+                    });
+            }
+            EditAction::Edited => {
+                info!("Text: {:?}", self.text.text());
+                if let Some(selected) = self.text.selected_text() {
+                    self.primary_selection.set(qh, selected, serial);
+                }
+            }
+            EditAction::Ignored => {}
         }
     }
 
@@ -335,6 +789,7 @@ impl KeyboardHandler for SimpleLayer {
         modifiers: Modifiers,
     ) {
         info!("Update modifiers: {modifiers:?}");
+        self.modifiers = modifiers;
     }
 }
 
@@ -347,35 +802,319 @@ impl PointerHandler for SimpleLayer {
         events: &[PointerEvent],
     ) {
         use PointerEventKind::*;
+        let regions = self.regions.clone();
         for event in events {
             // Ignore events for other surfaces
             if &event.surface != self.layer.wl_surface() {
                 continue;
             }
+            let (x, y) = (event.position.0 as i32, event.position.1 as i32);
+            let hit = self
+                .root
+                .as_ref()
+                .and_then(|root| root.hit(x, y))
+                .map(str::to_string);
+            for region in self.regions.handle(&event.kind, hit.as_deref()) {
+                match region {
+                    RegionEvent::Enter(key) => info!("Pointer entered {key}"),
+                    RegionEvent::Leave(key) => info!("Pointer left {key}"),
+                    RegionEvent::Click { key, button, count } => {
+                        self.module_clicked(&key, button, count)
+                    }
+                }
+            }
+
             match event.kind {
                 Enter { .. } => {
-                    println!("Pointer entered @{:?}", event.position);
+                    debug!("Pointer entered @{:?}", event.position);
                 }
                 Leave { .. } => {
-                    println!("Pointer left");
+                    debug!("Pointer left");
                 }
                 Motion { .. } => {}
+                // Pastes the primary selection anywhere, as into the text module.
+                Press { button, .. } if button == BTN_MIDDLE => {
+                    debug!("Middle press @ {:?}", event.position);
+                    self.primary_selection
+                        .paste(&self.loop_handle, |layer: &mut SimpleLayer, text| {
+                            layer.insert_text(&text)
+                        });
+                }
+                // Modules only react to clicks, the background to any other press.
+                Press { .. } if hit.is_some() => {}
                 Press { button, .. } => {
-                    println!("Press {:x} @ {:?}", button, event.position);
-                    self.shift = self.shift.xor(Some(0));
+                    debug!("Press {:x} @ {:?}", button, event.position);
+                    self.shift = self.shift.xor(Some(0.0));
+                    self.invalidate();
                 }
                 Release { button, .. } => {
-                    println!("Release {:x} @ {:?}", button, event.position);
-                }
-                Axis {
-                    horizontal,
-                    vertical,
-                    ..
-                } => {
-                    println!("Scroll H:{horizontal:?}, V:{vertical:?}");
+                    debug!("Release {:x} @ {:?}", button, event.position);
                 }
+                Axis { .. } => {}
+            }
+        }
+
+        let surface = self.layer.wl_surface().clone();
+        let events = events.iter().filter(|event| event.surface == surface);
+        if let Some(scroll) = self
+            .scroll
+            .frame(events, self.config.pointer.natural_scroll)
+        {
+            self.scrolled(scroll);
+        }
+        // Hovered and pressed modules are highlighted.
+        if self.regions != regions {
+            self.invalidate();
+        }
+    }
+}
+
+impl DataDeviceHandler for SimpleLayer {
+    fn enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {}
+
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {}
+
+    fn motion(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {}
+
+    fn selection(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {
+        // The offer is kept by the data device and only read when pasting.
+    }
+
+    fn drop_performed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: DataDevice,
+    ) {
+    }
+}
+
+impl DataOfferHandler for SimpleLayer {
+    fn offer(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DataDeviceOffer,
+        _mime_type: String,
+    ) {
+    }
+
+    fn source_actions(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+
+    fn selected_action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+}
+
+impl DataSourceHandler for SimpleLayer {
+    fn accept_mime(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _mime: Option<String>,
+    ) {
+    }
+
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &WlDataSource,
+        mime: String,
+        fd: WritePipe,
+    ) {
+        self.clipboard.send(&self.loop_handle, source, &mime, fd);
+    }
+
+    fn cancelled(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        self.clipboard.cancelled(source);
+    }
+
+    fn dnd_dropped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _source: &WlDataSource) {
+    }
+
+    fn dnd_finished(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+    ) {
+    }
+
+    fn action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _action: DndAction,
+    ) {
+    }
+}
+
+impl PrimarySelectionHandler for SimpleLayer {
+    fn primary_selection(&mut self) -> &mut PrimarySelection {
+        &mut self.primary_selection
+    }
+
+    fn loop_handle(&self) -> &LoopHandle<'static, Self> {
+        &self.loop_handle
+    }
+}
+
+impl PresentationHandler for SimpleLayer {
+    fn presentation(&mut self) -> &mut Presentation {
+        &mut self.presentation
+    }
+
+    fn stats_updated(&mut self) {
+        if let Some(summary) = &self.presentation.stats.summary {
+            info!("Frame statistics: {summary}");
+        }
+        self.invalidate();
+    }
+}
+
+impl ConfigHandler for SimpleLayer {
+    fn config_changed(&mut self, config: Config) {
+        // Only touch what changed, so placement set over the control socket survives edits
+        // elsewhere in the file.
+        if config.bar != self.config.bar {
+            if let Err(error) = self.reconfigure(config.bar.properties(&self.overrides)) {
+                warn!("Keeping the current placement: {error}");
+            }
+        }
+        if config.font != self.config.font {
+            match config.font.load() {
+                Ok(font) => self.font = Some(font),
+                Err(error) => warn!("Keeping the current font: {error}"),
             }
         }
+        self.config = config;
+        self.invalidate();
+    }
+}
+
+impl ControlHandler for SimpleLayer {
+    fn control(&mut self, command: ControlCommand) -> Reply {
+        match command {
+            ControlCommand::Show if !self.visible => {
+                // Unmapping reset the surface's state, so send all of it again. The commit asks
+                // for a new configure, which draws the first frame.
+                self.visible = true;
+                self.first_configure = true;
+                self.properties.apply(None, &self.layer);
+                self.layer.commit();
+            }
+            // Shown again before it finished fading out.
+            ControlCommand::Show if self.hiding() => self.retarget_transition(1.0, false),
+            ControlCommand::Hide if self.visible && !self.hiding() => {
+                self.retarget_transition(0.0, true)
+            }
+            ControlCommand::Show | ControlCommand::Hide => {}
+            ControlCommand::Toggle => {
+                let command = match self.visible && !self.hiding() {
+                    true => ControlCommand::Hide,
+                    false => ControlCommand::Show,
+                };
+                return self.control(command);
+            }
+            ControlCommand::SetText { text } => {
+                self.text.set_text(&text);
+                self.invalidate();
+            }
+            ControlCommand::SetLayer { layer } => {
+                return self.reconfigure_reply(LayerProperties {
+                    layer: layer.into(),
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::SetAnchor { edges } => {
+                return self.reconfigure_reply(LayerProperties {
+                    anchor: Edge::to_anchor(&edges),
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::SetPlacement {
+                placement,
+                width,
+                height,
+            } => {
+                let size = placement.size(width, height);
+                return self.reconfigure_reply(placement.properties(size, self.properties.margin));
+            }
+            ControlCommand::SetSize { width, height } => {
+                return self.reconfigure_reply(LayerProperties {
+                    size: (width, height),
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::SetExclusiveZone { zone } => {
+                return self.reconfigure_reply(LayerProperties {
+                    exclusive_zone: zone,
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::SetMargin {
+                top,
+                right,
+                bottom,
+                left,
+            } => {
+                return self.reconfigure_reply(LayerProperties {
+                    margin: Margin {
+                        top,
+                        right,
+                        bottom,
+                        left,
+                    },
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::SetKeyboardInteractivity { mode } => {
+                return self.reconfigure_reply(LayerProperties {
+                    keyboard_interactivity: mode.into(),
+                    ..self.properties.clone()
+                })
+            }
+            ControlCommand::Quit => self.exit = true,
+            ControlCommand::GetState => {
+                return Reply::state(LayerState {
+                    visible: self.visible && !self.hiding(),
+                    text: self.text.text().to_string(),
+                    layer: self.properties.layer.into(),
+                    anchor: Edge::from_anchor(self.properties.anchor),
+                    exclusive_zone: self.properties.exclusive_zone,
+                    margin: self.properties.margin,
+                    keyboard_interactivity: self.properties.keyboard_interactivity.into(),
+                    width: self.width,
+                    height: self.height,
+                })
+            }
+        }
+        Reply::ok()
+    }
+}
+
+impl SimpleLayer {
+    /// Reconfigure for a control command, reporting properties the compositor would reject.
+    fn reconfigure_reply(&mut self, properties: LayerProperties) -> Reply {
+        match self.reconfigure(properties) {
+            Ok(()) => Reply::ok(),
+            Err(error) => Reply::error(error),
+        }
     }
 }
 
@@ -395,6 +1134,13 @@ delegate_pointer!(SimpleLayer);
 
 delegate_layer!(SimpleLayer);
 
+delegate_data_device_manager!(SimpleLayer);
+delegate_data_device!(SimpleLayer);
+delegate_data_offer!(SimpleLayer);
+delegate_data_source!(SimpleLayer);
+delegate_primary_selection!(SimpleLayer);
+delegate_presentation!(SimpleLayer);
+
 delegate_registry!(SimpleLayer);
 
 impl ProvidesRegistryState for SimpleLayer {
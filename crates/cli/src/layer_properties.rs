@@ -0,0 +1,271 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerSurface,
+};
+use tracing::info;
+
+/// Distance from the anchored edges, in surface-local coordinates.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Margin {
+    #[serde(default)]
+    pub(crate) top: i32,
+    #[serde(default)]
+    pub(crate) right: i32,
+    #[serde(default)]
+    pub(crate) bottom: i32,
+    #[serde(default)]
+    pub(crate) left: i32,
+}
+
+impl Margin {
+    /// The same distance from every edge.
+    pub fn uniform(margin: i32) -> Self {
+        Margin {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+        }
+    }
+}
+
+/// Everything about a layer surface that is set with a request and applied on commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LayerProperties {
+    pub(crate) layer: Layer,
+    pub(crate) anchor: Anchor,
+    /// Zero along an axis lets the compositor pick the size, which needs both edges anchored.
+    pub(crate) size: (u32, u32),
+    pub(crate) exclusive_zone: i32,
+    pub(crate) margin: Margin,
+    pub(crate) keyboard_interactivity: KeyboardInteractivity,
+}
+
+impl LayerProperties {
+    /// Check that the compositor will accept these: a size of 0 is only allowed along an axis
+    /// anchored on both sides, anything else is a protocol error that disconnects the client.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size.0 == 0 && !self.anchor.contains(Anchor::LEFT | Anchor::RIGHT) {
+            return Err(
+                "a width of 0 needs the surface anchored to both the left and right edges"
+                    .to_string(),
+            );
+        }
+        if self.size.1 == 0 && !self.anchor.contains(Anchor::TOP | Anchor::BOTTOM) {
+            return Err(
+                "a height of 0 needs the surface anchored to both the top and bottom edges"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Send the requests for every property that differs from `current`, or all of them without it.
+    ///
+    /// Returns whether anything was sent; the caller commits to make it take effect.
+    pub fn apply(&self, current: Option<&LayerProperties>, surface: &LayerSurface) -> bool {
+        let differs = |changed: fn(&Self, &Self) -> bool| {
+            current.is_none_or(|current| changed(self, current))
+        };
+        let mut sent = false;
+
+        if differs(|new, old| new.layer != old.layer) {
+            surface.set_layer(self.layer);
+            sent = true;
+        }
+        if differs(|new, old| new.anchor != old.anchor) {
+            surface.set_anchor(self.anchor);
+            sent = true;
+        }
+        if differs(|new, old| new.size != old.size) {
+            surface.set_size(self.size.0, self.size.1);
+            sent = true;
+        }
+        if differs(|new, old| new.exclusive_zone != old.exclusive_zone) {
+            surface.set_exclusive_zone(self.exclusive_zone);
+            sent = true;
+        }
+        if differs(|new, old| new.margin != old.margin) {
+            let Margin {
+                top,
+                right,
+                bottom,
+                left,
+            } = self.margin;
+            surface.set_margin(top, right, bottom, left);
+            sent = true;
+        }
+        if differs(|new, old| new.keyboard_interactivity != old.keyboard_interactivity) {
+            surface.set_keyboard_interactivity(self.keyboard_interactivity);
+            sent = true;
+        }
+
+        if sent {
+            info!("Applied layer properties {self:?}");
+        }
+        sent
+    }
+}
+
+/// Where a layer surface goes on its output.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Placement {
+    /// Stretched along the top edge, reserving its height
+    #[default]
+    TopBar,
+    /// Stretched along the bottom edge, reserving its height
+    BottomBar,
+    /// Stretched along the left edge, reserving its width
+    LeftDock,
+    /// Stretched along the right edge, reserving its width
+    RightDock,
+    /// Floating in the top left corner
+    TopLeftPopup,
+    /// Floating in the top right corner
+    TopRightPopup,
+    /// Floating in the bottom left corner
+    BottomLeftPopup,
+    /// Floating in the bottom right corner
+    BottomRightPopup,
+    /// Centered above everything else, taking all keyboard input
+    CenteredOverlay,
+    /// Covering the whole output below every window
+    FullscreenBackground,
+}
+
+impl Placement {
+    /// The requested size, with a default for each length not given. Only the axes that are not
+    /// stretched matter.
+    pub fn size(self, width: Option<u32>, height: Option<u32>) -> (u32, u32) {
+        let (default_width, default_height) = match self {
+            Placement::TopBar | Placement::BottomBar => (0, 32),
+            Placement::LeftDock | Placement::RightDock => (48, 0),
+            Placement::TopLeftPopup
+            | Placement::TopRightPopup
+            | Placement::BottomLeftPopup
+            | Placement::BottomRightPopup => (320, 120),
+            Placement::CenteredOverlay => (640, 360),
+            Placement::FullscreenBackground => (0, 0),
+        };
+        (
+            width.unwrap_or(default_width),
+            height.unwrap_or(default_height),
+        )
+    }
+
+    /// The properties placing a surface of `size` here, `margin` away from the anchored edges.
+    ///
+    /// A size of 0 lets the compositor stretch the surface between the edges it is anchored to,
+    /// so it is used exactly along the axes anchored on both sides and `size` is ignored there.
+    pub fn properties(self, size: (u32, u32), margin: Margin) -> LayerProperties {
+        let anchor = match self {
+            Placement::TopBar => Anchor::LEFT | Anchor::TOP | Anchor::RIGHT,
+            Placement::BottomBar => Anchor::LEFT | Anchor::BOTTOM | Anchor::RIGHT,
+            Placement::LeftDock => Anchor::TOP | Anchor::LEFT | Anchor::BOTTOM,
+            Placement::RightDock => Anchor::TOP | Anchor::RIGHT | Anchor::BOTTOM,
+            Placement::TopLeftPopup => Anchor::TOP | Anchor::LEFT,
+            Placement::TopRightPopup => Anchor::TOP | Anchor::RIGHT,
+            Placement::BottomLeftPopup => Anchor::BOTTOM | Anchor::LEFT,
+            Placement::BottomRightPopup => Anchor::BOTTOM | Anchor::RIGHT,
+            Placement::CenteredOverlay => Anchor::empty(),
+            Placement::FullscreenBackground => Anchor::all(),
+        };
+        let stretched = |edges: Anchor, length: u32| match anchor.contains(edges) {
+            true => 0,
+            false => length,
+        };
+        let size = (
+            stretched(Anchor::LEFT | Anchor::RIGHT, size.0),
+            stretched(Anchor::TOP | Anchor::BOTTOM, size.1),
+        );
+
+        // Bars and docks reserve their thickness, the compositor adds the margin to it.
+        let exclusive_zone = match self {
+            Placement::TopBar | Placement::BottomBar => size.1 as i32,
+            Placement::LeftDock | Placement::RightDock => size.0 as i32,
+            // Not moved out of the way of other surfaces' zones, so it covers the whole output.
+            Placement::FullscreenBackground => -1,
+            _ => 0,
+        };
+        let (layer, keyboard_interactivity) = match self {
+            Placement::TopBar
+            | Placement::BottomBar
+            | Placement::LeftDock
+            | Placement::RightDock => (Layer::Top, KeyboardInteractivity::OnDemand),
+            Placement::TopLeftPopup
+            | Placement::TopRightPopup
+            | Placement::BottomLeftPopup
+            | Placement::BottomRightPopup => (Layer::Overlay, KeyboardInteractivity::None),
+            Placement::CenteredOverlay => (Layer::Overlay, KeyboardInteractivity::Exclusive),
+            Placement::FullscreenBackground => (Layer::Background, KeyboardInteractivity::None),
+        };
+
+        LayerProperties {
+            layer,
+            anchor,
+            size,
+            exclusive_zone,
+            margin,
+            keyboard_interactivity,
+        }
+    }
+}
+
+/// Parse a margin like CSS: one value for every edge, two for vertical and horizontal, or four
+/// for top, right, bottom and left, separated by commas.
+pub(crate) fn parse_margin(value: &str) -> Result<Margin, String> {
+    let values = value
+        .split(',')
+        .map(|part| part.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("{value:?}: {error}"))?;
+    match values[..] {
+        [margin] => Ok(Margin::uniform(margin)),
+        [vertical, horizontal] => Ok(Margin {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }),
+        [top, right, bottom, left] => Ok(Margin {
+            top,
+            right,
+            bottom,
+            left,
+        }),
+        _ => Err(format!("{value:?}: expected 1, 2 or 4 values")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_size_needs_both_edges_anchored() {
+        let bar = Placement::TopBar.properties((0, 32), Margin::default());
+        assert_eq!(bar.validate(), Ok(()));
+
+        let unstretched = LayerProperties {
+            anchor: Anchor::TOP | Anchor::LEFT,
+            ..bar.clone()
+        };
+        assert!(unstretched.validate().unwrap_err().contains("width"));
+
+        let flat = LayerProperties {
+            size: (100, 0),
+            ..bar
+        };
+        assert!(flat.validate().unwrap_err().contains("height"));
+    }
+
+    #[test]
+    fn every_placement_is_valid() {
+        for placement in Placement::value_variants() {
+            let properties = placement.properties(placement.size(None, None), Margin::default());
+            assert_eq!(properties.validate(), Ok(()), "{placement:?}");
+        }
+    }
+}
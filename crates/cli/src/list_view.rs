@@ -0,0 +1,320 @@
+use std::ops::Range;
+
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Modifiers};
+use xkbcommon::xkb::keysyms;
+
+use crate::draw::{Canvas, Rect};
+use crate::font::Font;
+
+const ROW_PADDING: (i32, i32) = (8, 4);
+const SCROLLBAR_WIDTH: i32 = 4;
+const SCROLLBAR_MIN_LENGTH: i32 = 16;
+
+const BACKGROUND: u32 = 0xFF_20_20_28;
+const STRIPE: u32 = 0xFF_26_26_30;
+const FOREGROUND: u32 = 0xFF_E0_E0_E0;
+const ACCENT: u32 = 0xFF_30_60_C0;
+const SCROLLBAR: u32 = 0x80_E0_E0_E8;
+
+/// What a key press did to a [`ListView`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ListEvent {
+    Ignored,
+    Changed,
+    /// Enter was pressed on the selected item.
+    Activated(usize),
+}
+
+/// A list of text rows scrolled by the pixel, which only ever draws the rows in view.
+pub(crate) struct ListView {
+    items: Vec<String>,
+    /// How far the list is scrolled, in pixels from the top of the first row.
+    offset: f64,
+    selected: Option<usize>,
+    /// Where the last layout put the list.
+    bounds: Rect,
+    row_height: i32,
+    /// Changed since the last frame, so its bounds have to be repainted.
+    damaged: bool,
+}
+
+impl ListView {
+    pub fn new(items: Vec<String>) -> Self {
+        ListView {
+            items,
+            offset: 0.0,
+            selected: None,
+            bounds: Rect::new(0, 0, 0, 0),
+            row_height: 1,
+            damaged: true,
+        }
+    }
+
+    pub fn item(&self, index: usize) -> &str {
+        &self.items[index]
+    }
+
+    /// Place the list within `bounds`, keeping the scroll position within its new limits.
+    pub fn layout(&mut self, bounds: Rect, font: &Font) {
+        let row_height = font.line_height() + 2 * ROW_PADDING.1;
+        if (bounds, row_height) != (self.bounds, self.row_height) {
+            self.bounds = bounds;
+            self.row_height = row_height;
+            self.damaged = true;
+            self.scroll_to(self.offset);
+        }
+    }
+
+    /// Move the view by `pixels`, positive down.
+    pub fn scroll_by(&mut self, pixels: f64) {
+        self.scroll_to(self.offset + pixels);
+    }
+
+    /// Move the view by whole rows, positive down.
+    pub fn scroll_rows(&mut self, rows: i32) {
+        self.scroll_by(rows as f64 * self.row_height as f64);
+    }
+
+    /// Select the row at `y` in surface coordinates and return its index, if there is one.
+    pub fn click(&mut self, y: i32) -> Option<usize> {
+        let y = (y - self.bounds.y) as f64 + self.offset;
+        let index = (y / self.row_height as f64).floor();
+        if index < 0.0 || index as usize >= self.items.len() {
+            return None;
+        }
+        self.select(index as usize);
+        Some(index as usize)
+    }
+
+    /// Move the selection with the arrows and page keys, or to either end with ctrl+Home and
+    /// ctrl+End, as plain Home and End belong to the text field.
+    pub fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> ListEvent {
+        let page = (self.bounds.h / self.row_height).max(1) as isize;
+        match event.keysym {
+            keysyms::KEY_Return | keysyms::KEY_KP_Enter => {
+                return match self.selected {
+                    Some(index) => ListEvent::Activated(index),
+                    None => ListEvent::Ignored,
+                };
+            }
+            keysyms::KEY_Up => self.move_selection(-1),
+            keysyms::KEY_Down => self.move_selection(1),
+            keysyms::KEY_Page_Up => self.move_selection(-page),
+            keysyms::KEY_Page_Down => self.move_selection(page),
+            keysyms::KEY_Home if modifiers.ctrl => self.move_selection(isize::MIN),
+            keysyms::KEY_End if modifiers.ctrl => self.move_selection(isize::MAX),
+            _ => return ListEvent::Ignored,
+        }
+        ListEvent::Changed
+    }
+
+    /// The list's bounds if anything about it changed since the last call.
+    pub fn take_damage(&mut self) -> Option<Rect> {
+        std::mem::take(&mut self.damaged).then_some(self.bounds)
+    }
+
+    /// Draw the rows in view and a scrollbar where the last layout put the list.
+    pub fn paint(&self, canvas: &mut Canvas, font: &mut Font) {
+        let previous = canvas.clip();
+        canvas.set_clip(Some(previous.intersect(self.bounds)));
+        canvas.fill_rect(self.bounds, BACKGROUND);
+
+        let offset = self.offset.round() as i32;
+        for index in self.visible_rows() {
+            let y = self.bounds.y + index as i32 * self.row_height - offset;
+            let row = Rect::new(self.bounds.x, y, self.bounds.w, self.row_height);
+            if self.selected == Some(index) {
+                canvas.fill_rect(row, ACCENT);
+            } else if index % 2 == 1 {
+                canvas.fill_rect(row, STRIPE);
+            }
+            let baseline = y + ROW_PADDING.1 + font.ascent();
+            canvas.text(
+                font,
+                row.x + ROW_PADDING.0,
+                baseline,
+                &self.items[index],
+                FOREGROUND,
+            );
+        }
+
+        let content = self.content_height();
+        if content > self.bounds.h {
+            let length = (self.bounds.h as i64 * self.bounds.h as i64 / content as i64) as i32;
+            let length = length.max(SCROLLBAR_MIN_LENGTH);
+            let travel = (self.bounds.h - length) as f64;
+            let y = self.bounds.y + (self.offset / self.max_offset() * travel).round() as i32;
+            let thumb = Rect::new(
+                self.bounds.right() - SCROLLBAR_WIDTH - 2,
+                y,
+                SCROLLBAR_WIDTH,
+                length,
+            );
+            canvas.fill_rounded_rect(thumb, SCROLLBAR_WIDTH as f32 / 2.0, SCROLLBAR);
+        }
+
+        canvas.set_clip(Some(previous));
+    }
+
+    /// The rows at least partly in view.
+    fn visible_rows(&self) -> Range<usize> {
+        let offset = self.offset.round() as i32;
+        let first = (offset / self.row_height) as usize;
+        let last = ((offset + self.bounds.h) / self.row_height + 1) as usize;
+        first.min(self.items.len())..last.min(self.items.len())
+    }
+
+    fn content_height(&self) -> i32 {
+        self.items.len() as i32 * self.row_height
+    }
+
+    fn max_offset(&self) -> f64 {
+        (self.content_height() - self.bounds.h).max(0) as f64
+    }
+
+    fn scroll_to(&mut self, offset: f64) {
+        let offset = offset.clamp(0.0, self.max_offset());
+        if offset != self.offset {
+            self.offset = offset;
+            self.damaged = true;
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let Some(last) = self.items.len().checked_sub(1) else {
+            return;
+        };
+        let index = match self.selected {
+            Some(selected) => selected.saturating_add_signed(delta).min(last),
+            // The first move selects the top row in view.
+            None => ((self.offset / self.row_height as f64).ceil() as usize).min(last),
+        };
+        self.select(index);
+    }
+
+    /// Select `index` and scroll it into view.
+    fn select(&mut self, index: usize) {
+        if self.selected != Some(index) {
+            self.selected = Some(index);
+            self.damaged = true;
+        }
+        let top = (index as i32 * self.row_height) as f64;
+        let bottom = top + (self.row_height - self.bounds.h) as f64;
+        self.scroll_to(self.offset.clamp(bottom, top.max(bottom)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROW: i32 = 20;
+
+    /// `rows` numbered items in a view `height` pixels high, laid out without a font.
+    fn list(rows: usize, height: i32) -> ListView {
+        let mut list = ListView::new((0..rows).map(|row| row.to_string()).collect());
+        list.bounds = Rect::new(0, 10, 100, height);
+        list.row_height = ROW;
+        list.take_damage();
+        list
+    }
+
+    fn key(list: &mut ListView, keysym: u32, ctrl: bool) -> ListEvent {
+        let event = KeyEvent {
+            time: 0,
+            raw_code: 0,
+            keysym,
+            utf8: None,
+        };
+        let modifiers = Modifiers {
+            ctrl,
+            ..Modifiers::default()
+        };
+        list.handle_key(&event, &modifiers)
+    }
+
+    #[test]
+    fn only_rows_in_view_are_visible() {
+        let mut list = list(100_000, 100);
+        assert_eq!(list.visible_rows(), 0..6);
+        list.scroll_by(30.0);
+        assert_eq!(list.visible_rows(), 1..7);
+        list.scroll_rows(50_000);
+        assert_eq!(list.visible_rows(), 50_001..50_007);
+        // Clamped to the last page.
+        list.scroll_rows(100_000);
+        assert_eq!(list.offset, (100_000 * ROW - 100) as f64);
+        assert_eq!(list.visible_rows(), 99_995..100_000);
+
+        assert_eq!(self::list(3, 100).visible_rows(), 0..3);
+        assert_eq!(self::list(0, 100).visible_rows(), 0..0);
+    }
+
+    #[test]
+    fn scrolling_stays_within_the_content() {
+        let mut list = list(10, 100);
+        list.scroll_by(-50.0);
+        assert_eq!(list.offset, 0.0);
+        assert_eq!(list.take_damage(), None);
+        list.scroll_by(1000.0);
+        assert_eq!(list.offset, 100.0);
+        assert_eq!(list.take_damage(), Some(list.bounds));
+
+        // Everything fits, so there is nothing to scroll.
+        let mut short = self::list(3, 100);
+        short.scroll_by(40.0);
+        assert_eq!(short.offset, 0.0);
+    }
+
+    #[test]
+    fn clicks_select_the_row_under_the_pointer() {
+        let mut list = list(10, 100);
+        // Above the list.
+        assert_eq!(list.click(5), None);
+        list.scroll_by(15.0);
+        // 10 pixels down the view, 15 scrolled: the second row.
+        assert_eq!(list.click(20), Some(1));
+        assert_eq!(list.selected, Some(1));
+        list.scroll_by(1000.0);
+        assert_eq!(list.click(109), Some(9));
+        assert_eq!(self::list(2, 100).click(80), None);
+    }
+
+    #[test]
+    fn keys_move_the_selection_into_view() {
+        let mut list = list(100, 100);
+        list.scroll_by(45.0);
+        assert_eq!(
+            key(&mut list, keysyms::KEY_Return, false),
+            ListEvent::Ignored
+        );
+        // The first move picks the top row fully in view.
+        assert_eq!(key(&mut list, keysyms::KEY_Down, false), ListEvent::Changed);
+        assert_eq!(list.selected, Some(3));
+
+        assert_eq!(
+            key(&mut list, keysyms::KEY_Page_Down, false),
+            ListEvent::Changed
+        );
+        assert_eq!(list.selected, Some(8));
+        // Scrolled just far enough for the row's bottom edge.
+        assert_eq!(list.offset, (9 * ROW - 100) as f64);
+
+        key(&mut list, keysyms::KEY_End, true);
+        assert_eq!(list.selected, Some(99));
+        assert_eq!(list.visible_rows().end, 100);
+        key(&mut list, keysyms::KEY_Down, false);
+        assert_eq!(list.selected, Some(99));
+
+        key(&mut list, keysyms::KEY_Home, true);
+        assert_eq!((list.selected, list.offset), (Some(0), 0.0));
+        key(&mut list, keysyms::KEY_Up, false);
+        assert_eq!(list.selected, Some(0));
+
+        assert_eq!(key(&mut list, keysyms::KEY_Home, false), ListEvent::Ignored);
+        assert_eq!(
+            key(&mut list, keysyms::KEY_Return, false),
+            ListEvent::Activated(0)
+        );
+    }
+}
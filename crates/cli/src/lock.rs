@@ -0,0 +1,762 @@
+use std::sync::Arc;
+
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::calloop::{
+        channel::{self, Channel, Sender},
+        EventLoop, LoopHandle,
+    },
+    reexports::client::{
+        globals::{registry_queue_init, GlobalList},
+        protocol::{wl_keyboard, wl_output, wl_seat, wl_shm, wl_surface},
+        Connection, Dispatch, QueueHandle, WaylandSource,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Modifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    shm::slot::SlotPool,
+    shm::{Shm, ShmHandler},
+};
+use tracing::{error, info, warn};
+use wayland_protocols::ext::session_lock::v1::client::{
+    ext_session_lock_manager_v1::{self, ExtSessionLockManagerV1},
+    ext_session_lock_surface_v1::{self, ExtSessionLockSurfaceV1},
+    ext_session_lock_v1::{self, ExtSessionLockV1},
+};
+use xkbcommon::xkb::keysyms;
+use zeroize::Zeroizing;
+
+use crate::auth::{AuthError, Authenticator, PamAuthenticator};
+use crate::commands::LockArgs;
+use crate::error::AppResult;
+
+const BACKGROUND: u32 = 0xFF20_2020;
+const PROMPT_BACKGROUND: u32 = 0xFF30_3030;
+const DOT: u32 = 0xFFE0_E0E0;
+
+/// Bytes a password may take. The buffer is allocated this large up front and never grows, as
+/// growing would leave copies of the password behind in memory that is not wiped.
+const PASSWORD_CAPACITY: usize = 1024;
+
+/// Where the password prompt is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PromptState {
+    Idle,
+    Verifying,
+    Failed,
+}
+
+impl PromptState {
+    fn border(self) -> u32 {
+        match self {
+            PromptState::Idle => 0xFF80_8080,
+            PromptState::Verifying => 0xFF30_60C0,
+            PromptState::Failed => 0xFFC0_3030,
+        }
+    }
+}
+
+/// What a key press did to the [`Prompt`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PromptEvent {
+    Ignored,
+    Changed,
+    /// Enter was pressed, verify this password.
+    Submit(Zeroizing<String>),
+}
+
+/// The password being typed and whether it is being verified, wiped from memory when dropped.
+pub(crate) struct Prompt {
+    password: Zeroizing<String>,
+    state: PromptState,
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Prompt {
+            password: empty_password(),
+            state: PromptState::Idle,
+        }
+    }
+}
+
+fn empty_password() -> Zeroizing<String> {
+    Zeroizing::new(String::with_capacity(PASSWORD_CAPACITY))
+}
+
+impl Prompt {
+    pub fn state(&self) -> PromptState {
+        self.state
+    }
+
+    /// Characters typed, to show as many dots.
+    pub fn len(&self) -> usize {
+        self.password.chars().count()
+    }
+
+    /// Edit the password with the key `keysym` typing `utf8`, or submit it on Enter. Keys are
+    /// ignored while a password is being verified.
+    pub fn key(&mut self, keysym: u32, utf8: Option<&str>) -> PromptEvent {
+        if self.state == PromptState::Verifying {
+            return PromptEvent::Ignored;
+        }
+
+        match keysym {
+            keysyms::KEY_Return | keysyms::KEY_KP_Enter => {
+                self.state = PromptState::Verifying;
+                let password = std::mem::replace(&mut self.password, empty_password());
+                return PromptEvent::Submit(password);
+            }
+            keysyms::KEY_Escape => self.password.clear(),
+            keysyms::KEY_BackSpace => {
+                self.password.pop();
+            }
+            _ => match utf8 {
+                Some(text) if !text.chars().any(char::is_control) => {
+                    if self.password.len() + text.len() > self.password.capacity() {
+                        return PromptEvent::Ignored;
+                    }
+                    self.password.push_str(text)
+                }
+                _ => return PromptEvent::Ignored,
+            },
+        }
+
+        self.state = PromptState::Idle;
+        PromptEvent::Changed
+    }
+
+    /// The result of verifying the submitted password, returns whether to unlock.
+    pub fn authenticated(&mut self, result: &Result<(), AuthError>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(_) => {
+                self.state = PromptState::Failed;
+                false
+            }
+        }
+    }
+}
+
+/// The lock surface shown on one output.
+pub(crate) struct LockSurface {
+    pub(crate) output: wl_output::WlOutput,
+    pub(crate) surface: wl_surface::WlSurface,
+    pub(crate) lock_surface: ExtSessionLockSurfaceV1,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+pub(crate) struct SimpleLock {
+    pub(crate) registry_state: RegistryState,
+    pub(crate) seat_state: SeatState,
+    pub(crate) output_state: OutputState,
+    pub(crate) compositor_state: CompositorState,
+    pub(crate) shm: Shm,
+
+    pub(crate) exit: bool,
+    pub(crate) pool: SlotPool,
+    pub(crate) lock: Option<ExtSessionLockV1>,
+    pub(crate) locked: bool,
+    pub(crate) surfaces: Vec<LockSurface>,
+    pub(crate) keyboard: Option<wl_keyboard::WlKeyboard>,
+    pub(crate) user: String,
+    pub(crate) prompt: Prompt,
+    pub(crate) authenticator: Arc<dyn Authenticator>,
+    pub(crate) results: Sender<Result<(), AuthError>>,
+    pub(crate) loop_handle: LoopHandle<'static, SimpleLock>,
+}
+
+pub(crate) fn run(args: LockArgs) -> AppResult<()> {
+    let connection = Connection::connect_to_env()?;
+
+    let (globals, queue) = registry_queue_init(&connection)?;
+    let qh = queue.handle();
+    let mut event_loop = EventLoop::<SimpleLock>::try_new().expect("Failed to create event loop");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(queue)
+        .unwrap()
+        .insert(loop_handle)
+        .unwrap();
+
+    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl_shm not available");
+    let manager: ExtSessionLockManagerV1 = globals
+        .bind(&qh, 1..=1, ())
+        .expect("ext_session_lock_manager_v1 not available");
+
+    let pool = SlotPool::new(256 * 256 * 4, &shm).expect("Failed to create pool");
+    let authenticator = Arc::new(PamAuthenticator {
+        service: args.pam_service,
+    });
+
+    let (results, channel) = channel::channel();
+    let mut simple_lock = SimpleLock::init(
+        &globals,
+        &qh,
+        event_loop.handle(),
+        compositor,
+        shm,
+        pool,
+        authenticator,
+        results,
+    );
+    simple_lock.insert_results(channel);
+
+    // Surfaces are created per output as the outputs are announced.
+    simple_lock.lock = Some(manager.lock(&qh, ()));
+
+    loop {
+        event_loop.dispatch(None, &mut simple_lock)?;
+
+        if simple_lock.exit {
+            break;
+        }
+    }
+
+    // Make sure the unlock request reaches the compositor before we go away.
+    connection.roundtrip()?;
+
+    Ok(())
+}
+
+impl SimpleLock {
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        globals: &GlobalList,
+        qh: &QueueHandle<Self>,
+        loop_handle: LoopHandle<'static, SimpleLock>,
+        compositor_state: CompositorState,
+        shm: Shm,
+        pool: SlotPool,
+        authenticator: Arc<dyn Authenticator>,
+        results: Sender<Result<(), AuthError>>,
+    ) -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .unwrap_or_default();
+
+        SimpleLock {
+            registry_state: RegistryState::new(globals),
+            seat_state: SeatState::new(globals, qh),
+            output_state: OutputState::new(globals, qh),
+            compositor_state,
+            shm,
+
+            exit: false,
+            pool,
+            lock: None,
+            locked: false,
+            surfaces: Vec::new(),
+            keyboard: None,
+            user,
+            prompt: Prompt::default(),
+            authenticator,
+            results,
+            loop_handle,
+        }
+    }
+
+    fn insert_results(&self, channel: Channel<Result<(), AuthError>>) {
+        self.loop_handle
+            .insert_source(channel, |event, _, lock| {
+                if let channel::Event::Msg(result) = event {
+                    lock.authenticated(result);
+                }
+            })
+            .expect("Failed to insert authentication channel");
+    }
+
+    fn create_surface(&mut self, qh: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        let Some(lock) = &self.lock else {
+            return;
+        };
+
+        let surface = self.compositor_state.create_surface(qh);
+        let lock_surface = lock.get_lock_surface(&surface, &output, qh, ());
+        self.surfaces.push(LockSurface {
+            output,
+            surface,
+            lock_surface,
+            width: 0,
+            height: 0,
+        });
+    }
+
+    /// Verify the typed password off the event loop, since PAM may sleep after a failure.
+    fn submit(&mut self, password: Zeroizing<String>) {
+        let authenticator = self.authenticator.clone();
+        let results = self.results.clone();
+        let user = self.user.clone();
+        std::thread::spawn(move || {
+            let result = authenticator.authenticate(&user, &password);
+            // Wipe the password before the result is even handled.
+            drop(password);
+            let _ = results.send(result);
+        });
+        self.draw_all();
+    }
+
+    fn authenticated(&mut self, result: Result<(), AuthError>) {
+        if self.prompt.authenticated(&result) {
+            info!("Authenticated, unlocking");
+            self.unlock();
+        } else {
+            if let Err(error) = result {
+                warn!("{error}");
+            }
+            self.draw_all();
+        }
+    }
+
+    fn unlock(&mut self) {
+        for surface in self.surfaces.drain(..) {
+            surface.lock_surface.destroy();
+            surface.surface.destroy();
+        }
+        if let Some(lock) = self.lock.take() {
+            if self.locked {
+                lock.unlock_and_destroy();
+            } else {
+                lock.destroy();
+            }
+        }
+        self.exit = true;
+    }
+
+    fn draw_all(&mut self) {
+        for index in 0..self.surfaces.len() {
+            self.draw(index);
+        }
+    }
+
+    pub fn draw(&mut self, index: usize) {
+        let surface = &self.surfaces[index];
+        let (width, height) = (surface.width, surface.height);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let (buffer, canvas) = self
+            .pool
+            .create_buffer(
+                width as i32,
+                height as i32,
+                width as i32 * 4,
+                wl_shm::Format::Argb8888,
+            )
+            .expect("create buffer");
+
+        let mut fill = |x0: u32, y0: u32, w: u32, h: u32, color: u32| {
+            for y in y0.min(height)..(y0 + h).min(height) {
+                let row = (y * width) as usize * 4;
+                for x in x0.min(width)..(x0 + w).min(width) {
+                    let offset = row + x as usize * 4;
+                    canvas[offset..offset + 4].copy_from_slice(&color.to_le_bytes());
+                }
+            }
+        };
+
+        fill(0, 0, width, height, BACKGROUND);
+
+        // A bordered box in the middle, with one dot per typed character.
+        let (box_width, box_height, border) = (320.min(width), 64.min(height), 3);
+        let (box_x, box_y) = ((width - box_width) / 2, (height - box_height) / 2);
+        fill(
+            box_x,
+            box_y,
+            box_width,
+            box_height,
+            self.prompt.state().border(),
+        );
+        fill(
+            box_x + border,
+            box_y + border,
+            box_width.saturating_sub(2 * border),
+            box_height.saturating_sub(2 * border),
+            PROMPT_BACKGROUND,
+        );
+
+        let (dot, spacing) = (12, 20);
+        let dots = (self.prompt.len() as u32).min((box_width / spacing).saturating_sub(1));
+        let dots_x = (width - dots * spacing) / 2;
+        for dot_index in 0..dots {
+            let x = dots_x + dot_index * spacing + (spacing - dot) / 2;
+            fill(x, height.saturating_sub(dot) / 2, dot, dot, DOT);
+        }
+
+        surface
+            .surface
+            .damage_buffer(0, 0, width as i32, height as i32);
+        buffer.attach_to(&surface.surface).expect("buffer attach");
+        surface.surface.commit();
+    }
+}
+
+impl Dispatch<ExtSessionLockManagerV1, ()> for SimpleLock {
+    fn event(
+        _: &mut Self,
+        _: &ExtSessionLockManagerV1,
+        _: ext_session_lock_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        unreachable!("ext_session_lock_manager_v1 has no events")
+    }
+}
+
+impl Dispatch<ExtSessionLockV1, ()> for SimpleLock {
+    fn event(
+        state: &mut Self,
+        _: &ExtSessionLockV1,
+        event: ext_session_lock_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_session_lock_v1::Event::Locked => {
+                info!("Session locked");
+                state.locked = true;
+            }
+            ext_session_lock_v1::Event::Finished => {
+                if state.locked {
+                    warn!("Session lock finished by the compositor");
+                } else {
+                    error!("The compositor refused to lock the session");
+                }
+                state.unlock();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtSessionLockSurfaceV1, ()> for SimpleLock {
+    fn event(
+        state: &mut Self,
+        lock_surface: &ExtSessionLockSurfaceV1,
+        event: ext_session_lock_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_session_lock_surface_v1::Event::Configure {
+            serial,
+            width,
+            height,
+        } = event
+        {
+            let Some(index) = state
+                .surfaces
+                .iter()
+                .position(|s| &s.lock_surface == lock_surface)
+            else {
+                return;
+            };
+
+            lock_surface.ack_configure(serial);
+            state.surfaces[index].width = width;
+            state.surfaces[index].height = height;
+            state.draw(index);
+        }
+    }
+}
+
+impl CompositorHandler for SimpleLock {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_factor: i32,
+    ) {
+        // Not needed for this example.
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        // Lock surfaces are only redrawn when the prompt changes.
+    }
+}
+
+impl OutputHandler for SimpleLock {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        // Outputs plugged in while locked need a lock surface too, or they would show the session.
+        self.create_surface(qh, output);
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.surfaces.retain(|surface| {
+            if surface.output != output {
+                return true;
+            }
+            surface.lock_surface.destroy();
+            surface.surface.destroy();
+            false
+        });
+    }
+}
+
+impl SeatHandler for SimpleLock {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            info!("Set keyboard capability");
+            let keyboard = self
+                .seat_state
+                .get_keyboard(qh, &seat, None)
+                .expect("Failed to create keyboard");
+            self.keyboard = Some(keyboard);
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _: &QueueHandle<Self>,
+        _: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard && self.keyboard.is_some() {
+            info!("Unset keyboard capability");
+            self.keyboard.take().unwrap().release();
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for SimpleLock {
+    fn enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _: u32,
+        _: &[u32],
+        _keysyms: &[u32],
+    ) {
+    }
+
+    fn leave(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _: u32,
+    ) {
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        event: KeyEvent,
+    ) {
+        match self.prompt.key(event.keysym, event.utf8.as_deref()) {
+            PromptEvent::Ignored => {}
+            PromptEvent::Changed => self.draw_all(),
+            PromptEvent::Submit(password) => self.submit(password),
+        }
+    }
+
+    fn release_key(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        _event: KeyEvent,
+    ) {
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        _modifiers: Modifiers,
+    ) {
+    }
+}
+
+impl ShmHandler for SimpleLock {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_compositor!(SimpleLock);
+delegate_output!(SimpleLock);
+delegate_shm!(SimpleLock);
+
+delegate_seat!(SimpleLock);
+delegate_keyboard!(SimpleLock);
+
+delegate_registry!(SimpleLock);
+
+impl ProvidesRegistryState for SimpleLock {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::MockAuthenticator;
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for c in text.chars() {
+            let event = prompt.key(c as u32, Some(&c.to_string()));
+            assert_eq!(event, PromptEvent::Changed);
+        }
+    }
+
+    /// Submit what was typed to `authenticator` as the locker does, returning whether it unlocks.
+    fn submit(prompt: &mut Prompt, authenticator: &MockAuthenticator) -> bool {
+        let PromptEvent::Submit(password) = prompt.key(keysyms::KEY_Return, None) else {
+            panic!("Enter did not submit");
+        };
+        assert_eq!(prompt.state(), PromptState::Verifying);
+        assert_eq!(prompt.len(), 0);
+        let result = authenticator.authenticate("user", &password);
+        prompt.authenticated(&result)
+    }
+
+    #[test]
+    fn correct_password_unlocks() {
+        let authenticator = MockAuthenticator::new("hunter2");
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, "hunter2");
+        assert_eq!(prompt.len(), 7);
+        assert!(submit(&mut prompt, &authenticator));
+        assert_eq!(authenticator.attempts(), ["hunter2"]);
+    }
+
+    #[test]
+    fn wrong_password_fails() {
+        let authenticator = MockAuthenticator::new("hunter2");
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, "hunter3");
+        assert!(!submit(&mut prompt, &authenticator));
+        assert_eq!(prompt.state(), PromptState::Failed);
+    }
+
+    #[test]
+    fn retry_after_failure() {
+        let authenticator = MockAuthenticator::new("hunter2");
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, "wrong");
+        assert!(!submit(&mut prompt, &authenticator));
+
+        // Typing again leaves the failed state, and editing keys work on the new attempt.
+        type_text(&mut prompt, "hunter22");
+        assert_eq!(prompt.state(), PromptState::Idle);
+        assert_eq!(
+            prompt.key(keysyms::KEY_BackSpace, None),
+            PromptEvent::Changed
+        );
+        assert!(submit(&mut prompt, &authenticator));
+        assert_eq!(authenticator.attempts(), ["wrong", "hunter2"]);
+    }
+
+    #[test]
+    fn keys_ignored_while_verifying() {
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, "a");
+        assert!(matches!(
+            prompt.key(keysyms::KEY_KP_Enter, None),
+            PromptEvent::Submit(_)
+        ));
+        assert_eq!(prompt.key('b' as u32, Some("b")), PromptEvent::Ignored);
+        assert!(matches!(
+            prompt.key(keysyms::KEY_Return, None),
+            PromptEvent::Ignored
+        ));
+        assert_eq!(prompt.len(), 0);
+    }
+
+    #[test]
+    fn password_never_reallocates() {
+        let mut prompt = Prompt::default();
+        let buffer = prompt.password.as_ptr();
+        type_text(&mut prompt, &"é".repeat(PASSWORD_CAPACITY / 2));
+        // Full: more typing is dropped rather than growing the buffer.
+        assert_eq!(prompt.key('a' as u32, Some("a")), PromptEvent::Ignored);
+        assert_eq!(prompt.len(), PASSWORD_CAPACITY / 2);
+        assert_eq!(prompt.password.as_ptr(), buffer);
+        assert_eq!(prompt.password.capacity(), PASSWORD_CAPACITY);
+
+        let PromptEvent::Submit(password) = prompt.key(keysyms::KEY_Return, None) else {
+            panic!("Enter did not submit");
+        };
+        assert_eq!(password.as_ptr(), buffer);
+        assert_eq!(prompt.password.capacity(), PASSWORD_CAPACITY);
+    }
+
+    #[test]
+    fn escape_clears_and_control_text_is_ignored() {
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, "abc");
+        assert_eq!(
+            prompt.key(keysyms::KEY_Tab, Some("\t")),
+            PromptEvent::Ignored
+        );
+        assert_eq!(prompt.key(keysyms::KEY_Escape, None), PromptEvent::Changed);
+        assert_eq!(prompt.len(), 0);
+    }
+}
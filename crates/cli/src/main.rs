@@ -1,27 +1,68 @@
 use std::time::Duration;
 
+use clap::Parser;
 use smithay_client_toolkit::compositor::CompositorState;
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay_client_toolkit::reexports::calloop::EventLoop;
 use smithay_client_toolkit::reexports::client::{
     globals::registry_queue_init, Connection, WaylandSource,
 };
-use smithay_client_toolkit::shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell};
+use smithay_client_toolkit::shell::wlr_layer::LayerShell;
 use smithay_client_toolkit::shell::xdg::window::WindowDecorations;
 use smithay_client_toolkit::shell::xdg::XdgShell;
 use smithay_client_toolkit::shell::WaylandSurface;
 use smithay_client_toolkit::shm::slot::SlotPool;
 use smithay_client_toolkit::shm::Shm;
+use tracing::warn;
 
+mod animation;
+mod auth;
+mod clipboard;
+mod clock;
+mod commands;
+mod config;
+mod control;
+mod desktop_entry;
+mod dnd;
+mod draw;
 mod error;
+mod font;
+mod fuzzy;
+mod icons;
 mod layer;
+mod layer_properties;
+mod list_view;
+mod lock;
+mod markup;
+mod menu;
+mod notification_server;
+mod notifications;
+mod osd;
+mod pixel;
+mod presentation;
+mod primary_selection;
+mod process;
+mod redraw;
+mod regions;
+mod scroll;
+mod text_buffer;
+mod theme;
+mod wallpaper;
+mod widget;
 mod window;
+mod xdg;
 
+use crate::commands::{Args, Commands, LayerArgs, WindowArgs};
+use crate::config::{Action, Config};
+use crate::control::ControlSocket;
 use crate::error::AppResult;
 use crate::layer::SimpleLayer;
+use crate::layer_properties::{Margin, Placement};
+use crate::list_view::ListView;
+use crate::menu::Menu;
 use crate::window::SimpleWindow;
 
-#[allow(dead_code)]
-fn simple_window() -> AppResult<()> {
+fn simple_window(config: Config, args: WindowArgs) -> AppResult<()> {
     let connection = Connection::connect_to_env()?;
 
     let (globals, queue) = registry_queue_init(&connection)?;
@@ -40,18 +81,48 @@ fn simple_window() -> AppResult<()> {
     let surface = compositor.create_surface(&qh);
     let window = xdg_shell.create_window(surface, WindowDecorations::RequestServer, &qh);
 
-    window.set_title("a wayland window");
+    window.set_title(config.window.title);
     window.set_app_id("dev.rubek.experiments.wayland.SimpleWindow");
     window.set_min_size(Some((256, 256)));
 
     window.commit();
 
     let pool = SlotPool::new(256 * 256 * 4, &shm).expect("Failed to create pool");
-    let mut simple_window =
-        SimpleWindow::init(&globals, &qh, event_loop.handle(), shm, pool, window);
+    let mut simple_window = SimpleWindow::init(
+        &globals,
+        &qh,
+        event_loop.handle(),
+        compositor,
+        shm,
+        pool,
+        window,
+    );
+    simple_window.width = config.window.width;
+    simple_window.height = config.window.height;
+    simple_window.format = config.render.format;
+    simple_window.natural_scroll = config.pointer.natural_scroll;
+    simple_window.animation = config.animation;
+    simple_window.stats_keys = config
+        .keybindings
+        .into_iter()
+        .filter(|(_, action)| *action == Action::ToggleStats)
+        .map(|(binding, _)| binding)
+        .collect();
+    simple_window.font = config
+        .font
+        .load()
+        .map_err(|error| warn!("Controls will not be drawn: {error}"))
+        .ok();
+    simple_window.list = ListView::new(match args.list {
+        Some(path) => std::fs::read_to_string(path)?
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        None => (1..=args.rows).map(|row| format!("Row {row}")).collect(),
+    });
 
     loop {
-        event_loop.dispatch(Duration::from_millis(16), &mut simple_window)?;
+        event_loop.dispatch(None, &mut simple_window)?;
 
         if simple_window.exit {
             break;
@@ -61,11 +132,30 @@ fn simple_window() -> AppResult<()> {
     Ok(())
 }
 
-fn simple_layer() -> AppResult<()> {
+/// The config file's contents, or the defaults without a config directory.
+///
+/// A broken config is reported with its location and exits, rather than being half applied.
+fn load_config() -> Config {
+    match config::path().map(|path| Config::load(&path)).transpose() {
+        Ok(config) => config.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn simple_layer(config: Config, overrides: LayerArgs, menu: Option<Menu>) -> AppResult<()> {
     let connection = Connection::connect_to_env()?;
 
-    let (globals, mut queue) = registry_queue_init(&connection)?;
+    let (globals, queue) = registry_queue_init(&connection)?;
     let qh = queue.handle();
+    let mut event_loop = EventLoop::<SimpleLayer>::try_new().expect("Failed to create event loop");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(queue)
+        .unwrap()
+        .insert(loop_handle)
+        .unwrap();
 
     let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
 
@@ -75,34 +165,112 @@ fn simple_layer() -> AppResult<()> {
 
     let surface = compositor.create_surface(&qh);
 
+    let properties = match &menu {
+        // Centered above everything else, taking all keyboard input until it closes.
+        Some(menu) => Placement::CenteredOverlay.properties(menu.size(), Margin::default()),
+        None => config.bar.properties(&overrides),
+    };
+    properties.validate().map_err(anyhow::Error::msg)?;
+    let namespace = menu.as_ref().map_or("simple_layer", Menu::namespace);
     let layer =
-        layer_shell.create_layer_surface(&qh, surface, Layer::Top, Some("simple_layer"), None);
-
-    layer.set_anchor(Anchor::LEFT | Anchor::TOP | Anchor::RIGHT);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
-    layer.set_exclusive_zone(32);
-    layer.set_size(2560, 32);
-
+        layer_shell.create_layer_surface(&qh, surface, properties.layer, Some(namespace), None);
+    properties.apply(None, &layer);
     layer.commit();
 
     let pool = SlotPool::new(256 * 256 * 4, &shm).expect("Failed to create pool");
 
-    let mut simple_layer = SimpleLayer::init(&globals, &qh, shm, pool, layer);
+    let is_menu = menu.is_some();
+    let mut simple_layer = SimpleLayer::init(
+        &globals,
+        &qh,
+        event_loop.handle(),
+        shm,
+        pool,
+        layer,
+        properties,
+        menu,
+    );
+
+    // Only the bar is long-running enough to be controlled and reconfigured.
+    if !is_menu {
+        simple_layer.font = config
+            .font
+            .load()
+            .map_err(|error| warn!("Text will not be drawn: {error}"))
+            .ok();
+        simple_layer.control = Some(ControlSocket::bind(&event_loop.handle())?);
+        // The bar only draws when something changes, and the clocks change on their own.
+        event_loop
+            .handle()
+            .insert_source(Timer::immediate(), |_, _, layer| {
+                layer.update_clocks();
+                TimeoutAction::ToDuration(Duration::from_secs(1))
+            })
+            .map_err(|error| error.error)?;
+        if let Some(path) = config::path() {
+            if let Err(error) = config::watch(&event_loop.handle(), path, config.clone()) {
+                warn!("Config changes will not be applied until restarting: {error}");
+            }
+        }
+        simple_layer.config = config;
+        simple_layer.overrides = overrides;
+    }
 
     loop {
-        queue.blocking_dispatch(&mut simple_layer)?;
+        event_loop.dispatch(None, &mut simple_layer)?;
 
         if simple_layer.exit {
             break;
         }
     }
 
+    // Like dmenu, report a dismissed menu through the exit status.
+    if simple_layer.cancelled {
+        std::process::exit(1);
+    }
+
     Ok(())
 }
 
 fn main() -> AppResult<()> {
-    tracing_subscriber::fmt::init();
+    // Logs go to stderr, stdout is for the output of dmenu and msg.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
-    // simple_window()
-    simple_layer()
+    let args = Args::parse();
+    match args
+        .command
+        .unwrap_or_else(|| Commands::Layer(LayerArgs::default()))
+    {
+        Commands::Layer(args) => simple_layer(load_config(), args, None),
+        Commands::Launcher => {
+            let config = load_config();
+            let menu = Menu::launcher(config.font.load()?, config.icons.load());
+            simple_layer(config, LayerArgs::default(), Some(menu))
+        }
+        Commands::Dmenu(args) => {
+            let config = load_config();
+            let font = config.font.load()?;
+            let menu = Menu::dmenu(font, args.prompt, args.lines, args.case_insensitive)?;
+            simple_layer(config, LayerArgs::default(), Some(menu))
+        }
+        Commands::Window(args) => simple_window(load_config(), args),
+        Commands::Wallpaper(args) => wallpaper::run(args),
+        Commands::Lock(args) => lock::run(args),
+        Commands::Osd(args) => osd::run(args),
+        Commands::Notify(args) => notifications::run(args, load_config().icons),
+        Commands::Msg(args) => {
+            let reply = control::send(&args.command)?;
+            if let Some(state) = &reply.state {
+                let state = serde_json::to_string_pretty(state).expect("serialize state");
+                println!("{state}");
+            }
+            if let Some(error) = &reply.error {
+                eprintln!("{error}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
 }
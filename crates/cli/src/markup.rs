@@ -0,0 +1,332 @@
+/// How a run of text is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Style {
+    pub(crate) bold: bool,
+    pub(crate) underline: bool,
+}
+
+/// Text sharing one style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Span {
+    pub(crate) text: String,
+    pub(crate) style: Style,
+}
+
+/// Parse the body markup subset of the notification spec.
+///
+/// `<b>` and `<u>` are honoured, `<i>`, `<a>` and `<img>` are accepted but their text is shown
+/// plainly, and any other tag is stripped. Entities are decoded.
+pub(crate) fn parse(markup: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut bold = 0usize;
+    let mut underline = 0usize;
+    let mut rest = markup;
+
+    while !rest.is_empty() {
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        let (text, tail) = rest.split_at(text_end);
+        if !text.is_empty() {
+            let style = Style {
+                bold: bold > 0,
+                underline: underline > 0,
+            };
+            push(&mut spans, decode_entities(text), style);
+        }
+
+        // A `<` without a matching `>` is plain text.
+        let Some(tag_end) = tail.find('>') else {
+            if !tail.is_empty() {
+                let style = Style {
+                    bold: bold > 0,
+                    underline: underline > 0,
+                };
+                push(&mut spans, decode_entities(tail), style);
+            }
+            break;
+        };
+
+        let tag = tail[1..tag_end].trim();
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let counter = match name.as_str() {
+            "b" => Some(&mut bold),
+            "u" => Some(&mut underline),
+            _ => None,
+        };
+        if let Some(counter) = counter {
+            *counter = if closing {
+                counter.saturating_sub(1)
+            } else {
+                *counter + 1
+            };
+        }
+
+        rest = &tail[tag_end + 1..];
+    }
+
+    spans
+}
+
+/// Break spans into lines no wider than `max_width`, at whitespace and at newlines.
+///
+/// Words run on across spans, so only whitespace in the text separates them; each run of it
+/// becomes a single space in its own style. A single word wider than a line is kept whole.
+pub(crate) fn wrap(
+    spans: &[Span],
+    max_width: i32,
+    mut measure: impl FnMut(&str) -> i32,
+) -> Vec<Vec<Span>> {
+    let mut lines = Lines {
+        lines: vec![Vec::new()],
+        width: 0,
+        max_width,
+        space: measure(" "),
+    };
+    // The word being read and the style of the whitespace before it.
+    let mut word: Vec<Span> = Vec::new();
+    let mut gap: Option<Style> = None;
+
+    for span in spans {
+        for c in span.text.chars() {
+            if !c.is_whitespace() {
+                let mut buffer = [0; 4];
+                push(
+                    &mut word,
+                    c.encode_utf8(&mut buffer).to_string(),
+                    span.style,
+                );
+                continue;
+            }
+            if !word.is_empty() {
+                lines.place(std::mem::take(&mut word), gap.take(), &mut measure);
+            }
+            if c == '\n' {
+                lines.lines.push(Vec::new());
+                lines.width = 0;
+                gap = None;
+            } else {
+                gap.get_or_insert(span.style);
+            }
+        }
+    }
+    if !word.is_empty() {
+        lines.place(word, gap, &mut measure);
+    }
+
+    lines.lines
+}
+
+/// The lines wrapped so far.
+struct Lines {
+    lines: Vec<Vec<Span>>,
+    /// Width of the last line.
+    width: i32,
+    max_width: i32,
+    space: i32,
+}
+
+impl Lines {
+    /// Add `word` to the last line, after a space in the style `gap` if it followed whitespace,
+    /// or start a new line with it if it does not fit.
+    fn place(
+        &mut self,
+        word: Vec<Span>,
+        gap: Option<Style>,
+        measure: &mut impl FnMut(&str) -> i32,
+    ) {
+        let word_width: i32 = word.iter().map(|piece| measure(&piece.text)).sum();
+        let line = self.lines.last_mut().unwrap();
+        let gap = gap.filter(|_| !line.is_empty());
+        let needed = word_width + gap.map_or(0, |_| self.space);
+
+        if !line.is_empty() && self.width + needed > self.max_width {
+            self.lines.push(word);
+            self.width = word_width;
+            return;
+        }
+
+        if let Some(style) = gap {
+            push(line, " ".to_string(), style);
+        }
+        for piece in word {
+            push(line, piece.text, piece.style);
+        }
+        self.width += needed;
+    }
+}
+
+/// Append text, merging it into the last span when the style matches.
+fn push(spans: &mut Vec<Span>, text: String, style: Style) {
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(&text),
+        _ => spans.push(Span { text, style }),
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Style = Style {
+        bold: false,
+        underline: false,
+    };
+    const BOLD: Style = Style {
+        bold: true,
+        underline: false,
+    };
+
+    fn span(text: &str, style: Style) -> Span {
+        Span {
+            text: text.to_string(),
+            style,
+        }
+    }
+
+    /// `markup` wrapped to `max_width` characters, one per pixel.
+    fn wrapped(markup: &str, max_width: i32) -> Vec<Vec<Span>> {
+        wrap(&parse(markup), max_width, |text| {
+            text.chars().count() as i32
+        })
+    }
+
+    /// The text of each wrapped line.
+    fn lines(markup: &str, max_width: i32) -> Vec<String> {
+        wrapped(markup, max_width)
+            .iter()
+            .map(|line| line.iter().map(|span| span.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tags_style_the_text_between_them() {
+        assert_eq!(
+            parse("a <b>bold <u>both</u></b> <B>caps</B>"),
+            [
+                span("a ", PLAIN),
+                span("bold ", BOLD),
+                span(
+                    "both",
+                    Style {
+                        bold: true,
+                        underline: true,
+                    }
+                ),
+                span(" ", PLAIN),
+                span("caps", BOLD),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_tags_are_stripped() {
+        assert_eq!(
+            parse("<i>it</i> <a href=\"x\">link</a><img src=\"y\"/> <blink>x</blink>"),
+            [span("it link x", PLAIN)]
+        );
+        // Stray closing tags do not underflow, an unclosed `<` is text.
+        assert_eq!(parse("</b>a < b"), [span("a < b", PLAIN)]);
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        assert_eq!(
+            parse("&lt;b&gt; &amp;&amp; &quot;&apos; &#65;&#x42;&#X43;"),
+            [span("<b> && \"' ABC", PLAIN)]
+        );
+        // Unknown, invalid or unterminated entities stay as they are.
+        assert_eq!(
+            parse("&nbsp; &#xD800; &#zz; & &amp"),
+            [span("&nbsp; &#xD800; &#zz; & &amp", PLAIN)]
+        );
+    }
+
+    #[test]
+    fn words_run_on_across_spans() {
+        assert_eq!(
+            wrapped("foo<b>bar</b>", 80),
+            [vec![span("foo", PLAIN), span("bar", BOLD)]]
+        );
+        assert_eq!(
+            wrapped("<b>Bold</b>: text", 80),
+            [vec![span("Bold", BOLD), span(": text", PLAIN)]]
+        );
+        // The space keeps the style it has in the text.
+        assert_eq!(
+            wrapped("<b>Bold </b>text", 80),
+            [vec![span("Bold ", BOLD), span("text", PLAIN)]]
+        );
+        assert_eq!(lines("a<b> </b><u>b</u>", 80), ["a b"]);
+    }
+
+    #[test]
+    fn lines_break_at_whitespace_only() {
+        assert_eq!(
+            lines("the <b>quick</b>brown fox", 10),
+            ["the", "quickbrown", "fox"]
+        );
+        assert_eq!(lines("  spaced   out  ", 80), ["spaced out"]);
+        // Too long for any line.
+        assert_eq!(lines("a verylongword b", 4), ["a", "verylongword", "b"]);
+    }
+
+    #[test]
+    fn newlines_always_break() {
+        assert_eq!(
+            lines("one\ntwo <b>three\n\nfour</b>", 80),
+            ["one", "two three", "", "four"]
+        );
+        assert_eq!(lines("", 80), [""]);
+    }
+}
@@ -0,0 +1,430 @@
+use std::io::{self, Read, Write};
+
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Modifiers};
+use xkbcommon::xkb::keysyms;
+
+use crate::desktop_entry::{self, DesktopEntry};
+use crate::draw::{fill_rect, Canvas};
+use crate::font::Font;
+use crate::fuzzy;
+use crate::icons::Icons;
+use crate::text_buffer::{EditAction, TextBuffer};
+
+const WIDTH: u32 = 640;
+const PADDING: i32 = 12;
+const ROW_PADDING: i32 = 6;
+const LAUNCHER_LINES: usize = 10;
+
+const BACKGROUND: u32 = 0xFF_20_20_28;
+const FOREGROUND: u32 = 0xFF_E0_E0_E0;
+const DIM: u32 = 0xFF_80_80_90;
+const ACCENT: u32 = 0xFF_30_60_C0;
+
+/// What happens to the chosen item.
+pub(crate) enum MenuAction {
+    /// Start the application, the items are the entries' names.
+    Launch(Vec<DesktopEntry>),
+    /// Print the item, or the typed text, to stdout.
+    Print,
+}
+
+/// What a key press asked of the surface showing a [`Menu`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum MenuEvent {
+    Ignored,
+    Changed,
+    Paste,
+    /// Choose the highlighted item.
+    Accept,
+    /// Choose the typed text instead of the highlighted item.
+    AcceptInput,
+    Cancel,
+}
+
+/// A text field above a list of items, filtered fuzzily by what was typed.
+pub(crate) struct Menu {
+    pub(crate) action: MenuAction,
+    prompt: String,
+    items: Vec<String>,
+    lines: usize,
+    ignore_case: bool,
+    query: TextBuffer,
+    /// Indices into `items` of the entries matching the query, best match first.
+    matches: Vec<usize>,
+    /// Index into `matches`.
+    selected: usize,
+    scroll: usize,
+    font: Font,
+    /// Drawn before each item for the launcher.
+    icons: Option<Icons>,
+}
+
+impl Menu {
+    pub fn new(
+        font: Font,
+        prompt: String,
+        items: Vec<String>,
+        lines: usize,
+        ignore_case: bool,
+        action: MenuAction,
+    ) -> Self {
+        let mut menu = Menu {
+            action,
+            prompt,
+            items,
+            lines: lines.max(1),
+            ignore_case,
+            query: TextBuffer::default(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            font,
+            icons: None,
+        };
+        menu.filter();
+        menu
+    }
+
+    /// A menu of the installed applications.
+    pub fn launcher(font: Font, icons: Icons) -> Self {
+        let entries = desktop_entry::scan();
+        let items = entries.iter().map(|entry| entry.name.clone()).collect();
+        let mut menu = Self::new(
+            font,
+            "Run".to_string(),
+            items,
+            LAUNCHER_LINES,
+            true,
+            MenuAction::Launch(entries),
+        );
+        menu.icons = Some(icons);
+        menu
+    }
+
+    /// A menu of the lines read from stdin, printing the choice like `dmenu`.
+    pub fn dmenu(font: Font, prompt: String, lines: usize, ignore_case: bool) -> io::Result<Self> {
+        let mut input = String::new();
+        io::stdin().lock().read_to_string(&mut input)?;
+        let items = input.lines().map(str::to_string).collect();
+        Ok(Self::new(
+            font,
+            prompt,
+            items,
+            lines,
+            ignore_case,
+            MenuAction::Print,
+        ))
+    }
+
+    pub fn namespace(&self) -> &'static str {
+        match self.action {
+            MenuAction::Launch(_) => "launcher",
+            MenuAction::Print => "dmenu",
+        }
+    }
+
+    /// Surface size fitting the text field and `lines` rows.
+    pub fn size(&self) -> (u32, u32) {
+        let height = PADDING * 2 + self.row_height() * (self.lines as i32 + 1);
+        (WIDTH, height as u32)
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> MenuEvent {
+        let page = self.lines as isize;
+        match event.keysym {
+            keysyms::KEY_Escape => return MenuEvent::Cancel,
+            keysyms::KEY_Return | keysyms::KEY_KP_Enter if modifiers.shift => {
+                return MenuEvent::AcceptInput
+            }
+            keysyms::KEY_Return | keysyms::KEY_KP_Enter => return MenuEvent::Accept,
+            keysyms::KEY_Up | keysyms::KEY_ISO_Left_Tab => self.move_selection(-1),
+            keysyms::KEY_Down | keysyms::KEY_Tab => self.move_selection(1),
+            keysyms::KEY_p if modifiers.ctrl => self.move_selection(-1),
+            keysyms::KEY_n if modifiers.ctrl => self.move_selection(1),
+            keysyms::KEY_Page_Up => self.move_selection(-page),
+            keysyms::KEY_Page_Down => self.move_selection(page),
+            _ => {
+                return match self.query.handle_key(event, modifiers) {
+                    EditAction::Edited => {
+                        self.filter();
+                        MenuEvent::Changed
+                    }
+                    EditAction::Paste => MenuEvent::Paste,
+                    EditAction::Copy | EditAction::Ignored => MenuEvent::Ignored,
+                }
+            }
+        }
+        MenuEvent::Changed
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        // Only the first line of pasted text makes sense as a query.
+        self.query.insert(text.lines().next().unwrap_or_default());
+        self.filter();
+    }
+
+    pub fn query(&self) -> &str {
+        self.query.text()
+    }
+
+    pub fn item(&self, index: usize) -> &str {
+        &self.items[index]
+    }
+
+    /// Write the highlighted item, or the typed text if `input` is set or nothing matches, as
+    /// the one line a script reading the menu's output gets.
+    pub fn print_choice(&self, out: &mut impl Write, input: bool) -> io::Result<()> {
+        let choice = match self.selected().filter(|_| !input) {
+            Some(selected) => self.item(selected),
+            None => self.query(),
+        };
+        writeln!(out, "{choice}")
+    }
+
+    /// Index into the items of the highlighted match.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    pub fn draw(&mut self, canvas: &mut [u8], width: u32, height: u32) {
+        fill_rect(
+            canvas,
+            width,
+            height,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            BACKGROUND,
+        );
+
+        let row_height = self.row_height();
+        let ascent = self.font.ascent();
+        let line_height = self.font.line_height();
+
+        // The text field: prompt, query, cursor and match count.
+        let baseline = PADDING + ROW_PADDING + ascent;
+        let mut x = PADDING;
+        if !self.prompt.is_empty() {
+            x = self
+                .font
+                .draw(canvas, width, height, x, baseline, &self.prompt, ACCENT);
+            x += PADDING;
+        }
+        let cursor = x + self.font.measure(&self.query.text()[..self.query.cursor()]);
+        self.font.draw(
+            canvas,
+            width,
+            height,
+            x,
+            baseline,
+            self.query.text(),
+            FOREGROUND,
+        );
+        fill_rect(
+            canvas,
+            width,
+            height,
+            cursor,
+            PADDING + ROW_PADDING,
+            2,
+            line_height,
+            FOREGROUND,
+        );
+
+        let count = format!("{}/{}", self.matches.len(), self.items.len());
+        let count_x = width as i32 - PADDING - self.font.measure(&count);
+        self.font
+            .draw(canvas, width, height, count_x, baseline, &count, DIM);
+
+        // Keep the selection on screen.
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + self.lines {
+            self.scroll = self.selected + 1 - self.lines;
+        }
+
+        // Items line up after the icon column, whether or not they have an icon.
+        let icon_size = line_height as u32;
+        let text_x = match self.icons {
+            Some(_) => PADDING + line_height + ROW_PADDING,
+            None => PADDING,
+        };
+
+        let visible = self.matches.iter().skip(self.scroll).take(self.lines);
+        for (row, &item) in visible.enumerate() {
+            let top = PADDING + row_height * (row as i32 + 1);
+            if self.scroll + row == self.selected {
+                fill_rect(
+                    canvas,
+                    width,
+                    height,
+                    PADDING / 2,
+                    top,
+                    width as i32 - PADDING,
+                    row_height,
+                    ACCENT,
+                );
+            }
+            if let (Some(icons), MenuAction::Launch(entries)) = (&mut self.icons, &self.action) {
+                // The surface is drawn at scale 1.
+                let icon = entries[item]
+                    .icon
+                    .as_deref()
+                    .and_then(|name| icons.get(name, icon_size, 1));
+                if let Some(icon) = icon {
+                    let (x, y) = (
+                        PADDING + (line_height - icon.width as i32) / 2,
+                        top + ROW_PADDING + (line_height - icon.height as i32) / 2,
+                    );
+                    Canvas::new(canvas, width, height).blit(&icon, x, y, 1.0);
+                }
+            }
+            self.font.draw(
+                canvas,
+                width,
+                height,
+                text_x,
+                top + ROW_PADDING + ascent,
+                &self.items[item],
+                FOREGROUND,
+            );
+        }
+    }
+
+    fn row_height(&self) -> i32 {
+        self.font.line_height() + ROW_PADDING * 2
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Recompute the matches for the current query.
+    fn filter(&mut self) {
+        self.matches = matches(self.query.text(), &self.items, self.ignore_case);
+        self.selected = 0;
+        self.scroll = 0;
+    }
+}
+
+/// Indices of the `items` matching `query`, best match first and in item order among equal
+/// scores.
+fn matches(query: &str, items: &[String], ignore_case: bool) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            fuzzy::score(query, item, ignore_case).map(|score| (score, index))
+        })
+        .collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dmenu(items: &[&str]) -> Option<Menu> {
+        let font = Font::load(16.0)
+            .map_err(|error| eprintln!("No font, skipping: {error}"))
+            .ok()?;
+        let items = items.iter().map(|item| item.to_string()).collect();
+        Some(Menu::new(
+            font,
+            String::new(),
+            items,
+            10,
+            false,
+            MenuAction::Print,
+        ))
+    }
+
+    fn key(menu: &mut Menu, keysym: u32, utf8: Option<&str>, shift: bool) -> MenuEvent {
+        let event = KeyEvent {
+            time: 0,
+            raw_code: 0,
+            keysym,
+            utf8: utf8.map(str::to_string),
+        };
+        let modifiers = Modifiers {
+            shift,
+            ..Modifiers::default()
+        };
+        menu.handle_key(&event, &modifiers)
+    }
+
+    fn printed(menu: &Menu, input: bool) -> String {
+        let mut out = Vec::new();
+        menu.print_choice(&mut out, input).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dmenu_prints_only_the_chosen_line() {
+        let Some(mut menu) = dmenu(&["alpha", "beta", "gamma"]) else {
+            return;
+        };
+        assert_eq!(
+            key(&mut menu, keysyms::KEY_Down, None, false),
+            MenuEvent::Changed
+        );
+        assert_eq!(
+            key(&mut menu, keysyms::KEY_Return, None, false),
+            MenuEvent::Accept
+        );
+        assert_eq!(printed(&menu, false), "beta\n");
+
+        key(&mut menu, keysyms::KEY_g, Some("g"), false);
+        assert_eq!(printed(&menu, false), "gamma\n");
+        // Shift+Enter takes the typed text over the match.
+        assert_eq!(
+            key(&mut menu, keysyms::KEY_Return, None, true),
+            MenuEvent::AcceptInput
+        );
+        assert_eq!(printed(&menu, true), "g\n");
+
+        key(&mut menu, keysyms::KEY_z, Some("z"), false);
+        assert_eq!(menu.selected(), None);
+        assert_eq!(printed(&menu, false), "gz\n");
+    }
+
+    fn filtered<'a>(query: &str, items: &[&'a str], ignore_case: bool) -> Vec<&'a str> {
+        let owned: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+        matches(query, &owned, ignore_case)
+            .into_iter()
+            .map(|index| items[index])
+            .collect()
+    }
+
+    #[test]
+    fn empty_query_keeps_every_item_in_order() {
+        let items = ["Firefox", "Files", "Terminal"];
+        assert_eq!(filtered("", &items, false), items);
+        assert!(filtered("", &[], false).is_empty());
+    }
+
+    #[test]
+    fn best_matches_come_first() {
+        let items = ["Disks", "Files", "Firefox", "File Roller"];
+        assert_eq!(
+            filtered("fi", &items, true),
+            ["Files", "Firefox", "File Roller"]
+        );
+        assert_eq!(filtered("fr", &items, true), ["File Roller", "Firefox"]);
+    }
+
+    #[test]
+    fn case_sensitivity_is_optional() {
+        let items = ["firefox", "Firefox"];
+        assert_eq!(filtered("Fire", &items, false), ["Firefox"]);
+        assert_eq!(filtered("FIRE", &items, true), items);
+    }
+
+    #[test]
+    fn nothing_matches_an_unrelated_query() {
+        assert!(filtered("xyz", &["Firefox", "Files"], true).is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use smithay_client_toolkit::reexports::calloop::channel::Sender;
+use tracing::{info, warn};
+use zbus::{blocking::Connection, interface, zvariant::OwnedValue};
+
+pub(crate) const BUS_NAME: &str = "org.freedesktop.Notifications";
+pub(crate) const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+/// Why a notification went away, as reported by `NotificationClosed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    Closed = 3,
+}
+
+/// A notification as received over D-Bus.
+#[derive(Debug, Clone)]
+pub(crate) struct Notification {
+    pub(crate) id: u32,
+    pub(crate) app_name: String,
+    pub(crate) summary: String,
+    /// Icon name, absolute path or `file://` URI, empty for none.
+    pub(crate) icon: String,
+    /// Body in the spec's markup subset.
+    pub(crate) body: String,
+    /// Action keys and their labels.
+    pub(crate) actions: Vec<(String, String)>,
+    pub(crate) urgency: Urgency,
+    /// Milliseconds until the notification expires, `-1` for the server default and `0` for never.
+    pub(crate) expire_timeout: i32,
+}
+
+/// What the bus asked of the daemon's event loop.
+#[derive(Debug)]
+pub(crate) enum Request {
+    Notify(Notification),
+    Close(u32),
+}
+
+/// The `org.freedesktop.Notifications` interface.
+///
+/// Method calls arrive on zbus' own thread and are forwarded to the event loop through a channel.
+pub(crate) struct NotificationServer {
+    next_id: AtomicU32,
+    /// Notifications handed out and not closed yet, the only ones `replaces_id` can replace.
+    live: Mutex<HashSet<u32>>,
+    requests: Sender<Request>,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl NotificationServer {
+    fn get_capabilities(&self) -> Vec<&'static str> {
+        vec!["actions", "body", "body-markup"]
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: String,
+        replaces_id: u32,
+        app_icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        expire_timeout: i32,
+    ) -> u32 {
+        let id = {
+            let mut live = self.live.lock().unwrap();
+            if live.contains(&replaces_id) {
+                replaces_id
+            } else {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                live.insert(id);
+                id
+            }
+        };
+        let urgency = match hints
+            .get("urgency")
+            .and_then(|value| value.downcast_ref::<u8>().ok())
+        {
+            Some(0) => Urgency::Low,
+            Some(2) => Urgency::Critical,
+            _ => Urgency::Normal,
+        };
+        // An image given as a hint is more specific than the application's icon.
+        let icon = hints
+            .get("image-path")
+            .or_else(|| hints.get("image_path"))
+            .and_then(|value| value.downcast_ref::<String>().ok())
+            .filter(|path| !path.is_empty())
+            .unwrap_or(app_icon);
+        let actions = actions
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        let notification = Notification {
+            id,
+            app_name,
+            summary,
+            icon,
+            body,
+            actions,
+            urgency,
+            expire_timeout,
+        };
+        info!("Notify {notification:?}");
+        self.send(Request::Notify(notification));
+        id
+    }
+
+    fn close_notification(&self, id: u32) {
+        self.send(Request::Close(id));
+    }
+
+    fn get_server_information(&self) -> (String, String, String, String) {
+        (
+            env!("CARGO_PKG_NAME").to_string(),
+            "rubek".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+            "1.2".to_string(),
+        )
+    }
+}
+
+impl NotificationServer {
+    fn send(&self, request: Request) {
+        if let Err(error) = self.requests.send(request) {
+            warn!("Event loop is gone, dropping {:?}", error.0);
+        }
+    }
+}
+
+/// Own the notifications name on the session bus, or the bus at `address`.
+pub(crate) fn serve(address: Option<&str>, requests: Sender<Request>) -> zbus::Result<Connection> {
+    let server = NotificationServer {
+        next_id: AtomicU32::new(1),
+        live: Mutex::default(),
+        requests,
+    };
+    let builder = match address {
+        Some(address) => zbus::blocking::connection::Builder::address(address)?,
+        None => zbus::blocking::connection::Builder::session()?,
+    };
+    builder
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, server)?
+        .build()
+}
+
+/// Tell clients that notification `id` is gone, which also frees it from being replaced.
+pub(crate) fn notification_closed(connection: &Connection, id: u32, reason: CloseReason) {
+    match connection
+        .object_server()
+        .interface::<_, NotificationServer>(OBJECT_PATH)
+    {
+        Ok(server) => {
+            server.get().live.lock().unwrap().remove(&id);
+        }
+        Err(error) => warn!("Failed to find the notification server: {error}"),
+    }
+
+    let body = (id, reason as u32);
+    let result = connection.emit_signal(
+        None::<&str>,
+        OBJECT_PATH,
+        BUS_NAME,
+        "NotificationClosed",
+        &body,
+    );
+    if let Err(error) = result {
+        warn!("Failed to emit NotificationClosed: {error}");
+    }
+}
+
+pub(crate) fn action_invoked(connection: &Connection, id: u32, action_key: &str) {
+    let body = (id, action_key);
+    let result =
+        connection.emit_signal(None::<&str>, OBJECT_PATH, BUS_NAME, "ActionInvoked", &body);
+    if let Err(error) = result {
+        warn!("Failed to emit ActionInvoked: {error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, BufRead, BufReader},
+        process::{Child, Command, Stdio},
+    };
+
+    use smithay_client_toolkit::reexports::calloop::channel::{self, Channel};
+
+    use super::*;
+
+    /// A private bus, stopped when dropped.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// Start `dbus-daemon`, or `None` when it is not installed.
+        fn start() -> Option<Bus> {
+            let spawned = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .arg("--address=unix:tmpdir=/tmp")
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut daemon = match spawned {
+                Ok(daemon) => daemon,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+                Err(error) => panic!("Failed to start dbus-daemon: {error}"),
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Some(Bus {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    fn notify(client: &Connection, replaces_id: u32) -> u32 {
+        let actions: Vec<&str> = Vec::new();
+        let hints: HashMap<&str, zbus::zvariant::Value> = HashMap::new();
+        client
+            .call_method(
+                Some(BUS_NAME),
+                OBJECT_PATH,
+                Some(BUS_NAME),
+                "Notify",
+                &(
+                    "test",
+                    replaces_id,
+                    "",
+                    "summary",
+                    "body",
+                    actions,
+                    hints,
+                    -1,
+                ),
+            )
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn replaces_only_live_notifications() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let (requests, _channel): (_, Channel<Request>) = channel::channel();
+        let server = serve(Some(&bus.address), requests).unwrap();
+        let client = zbus::blocking::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let first = notify(&client, 0);
+        assert_eq!(notify(&client, first), first);
+
+        // An id never handed out gets a fresh one instead of being taken over.
+        let unknown = first + 100;
+        let second = notify(&client, unknown);
+        assert_ne!(second, unknown);
+        assert_ne!(second, first);
+        assert_eq!(notify(&client, second), second);
+
+        // As does one that has been closed since.
+        notification_closed(&server, first, CloseReason::Dismissed);
+        let third = notify(&client, first);
+        assert!(![first, second, unknown].contains(&third));
+    }
+}
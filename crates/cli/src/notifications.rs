@@ -0,0 +1,672 @@
+use std::{rc::Rc, time::Duration};
+
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_layer, delegate_output, delegate_pointer, delegate_registry,
+    delegate_seat, delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::calloop::{
+        channel::{self, Channel},
+        timer::{TimeoutAction, Timer},
+        EventLoop, LoopHandle, RegistrationToken,
+    },
+    reexports::client::{
+        globals::{registry_queue_init, GlobalList},
+        protocol::{wl_output, wl_pointer, wl_seat, wl_shm, wl_surface},
+        Connection, QueueHandle, WaylandSource,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        pointer::{PointerEvent, PointerEventKind, PointerHandler, BTN_LEFT},
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    shm::slot::SlotPool,
+    shm::{Shm, ShmHandler},
+};
+use tracing::{info, warn};
+
+use crate::commands::{Corner, NotifyArgs};
+use crate::config::IconConfig;
+use crate::draw::{fill_rect, Canvas, Image};
+use crate::error::AppResult;
+use crate::font::Font;
+use crate::icons::Icons;
+use crate::markup::{self, Span};
+use crate::notification_server::{self, CloseReason, Notification, Request, Urgency};
+
+const WIDTH: u32 = 360;
+const PADDING: i32 = 12;
+/// Space between popups, and between the stack and the screen edge.
+const GAP: i32 = 10;
+const BORDER: i32 = 2;
+const BUTTON_HEIGHT: i32 = 28;
+const MAX_BODY_LINES: usize = 6;
+const ICON_SIZE: u32 = 32;
+
+const BACKGROUND: u32 = 0xFF_20_20_28;
+const FOREGROUND: u32 = 0xFF_E0_E0_E0;
+const DIM: u32 = 0xFF_80_80_90;
+const BUTTON: u32 = 0xFF_40_40_4C;
+
+/// The action invoked by clicking a notification outside of its buttons.
+const DEFAULT_ACTION: &str = "default";
+
+fn border_color(urgency: Urgency) -> u32 {
+    match urgency {
+        Urgency::Low => 0xFF_50_50_5C,
+        Urgency::Normal => 0xFF_30_60_C0,
+        Urgency::Critical => 0xFF_C0_30_30,
+    }
+}
+
+/// How far text moves right to make room for an icon.
+fn icon_indent(icon: bool) -> i32 {
+    match icon {
+        true => ICON_SIZE as i32 + PADDING,
+        false => 0,
+    }
+}
+
+/// A notification shown on its own layer surface.
+pub(crate) struct Popup {
+    pub(crate) notification: Notification,
+    pub(crate) layer: LayerSurface,
+    pub(crate) height: u32,
+    pub(crate) configured: bool,
+    /// Action buttons as `(x, y, width, height, key)`.
+    buttons: Vec<(i32, i32, i32, i32, String)>,
+    body: Vec<Vec<Span>>,
+    /// Shown left of the text.
+    icon: Option<Rc<Image>>,
+    timer: Option<RegistrationToken>,
+}
+
+pub(crate) struct SimpleNotifications {
+    pub(crate) registry_state: RegistryState,
+    pub(crate) seat_state: SeatState,
+    pub(crate) output_state: OutputState,
+    pub(crate) compositor_state: CompositorState,
+    pub(crate) layer_shell: LayerShell,
+    pub(crate) shm: Shm,
+
+    pub(crate) pool: SlotPool,
+    /// Newest first, nearest to the corner.
+    pub(crate) popups: Vec<Popup>,
+    pub(crate) pointer: Option<wl_pointer::WlPointer>,
+    pub(crate) corner: Corner,
+    pub(crate) default_timeout: Duration,
+    bus: zbus::blocking::Connection,
+    font: Font,
+    icons: Icons,
+    qh: QueueHandle<SimpleNotifications>,
+    loop_handle: LoopHandle<'static, SimpleNotifications>,
+}
+
+pub(crate) fn run(args: NotifyArgs, icons: IconConfig) -> AppResult<()> {
+    let connection = Connection::connect_to_env()?;
+
+    let (globals, queue) = registry_queue_init(&connection)?;
+    let qh = queue.handle();
+    let mut event_loop =
+        EventLoop::<SimpleNotifications>::try_new().expect("Failed to create event loop");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(queue)
+        .unwrap()
+        .insert(loop_handle.clone())
+        .unwrap();
+
+    let (requests, channel) = channel::channel();
+    let bus = notification_server::serve(args.address.as_deref(), requests)?;
+    info!("Serving {}", notification_server::BUS_NAME);
+
+    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let layer_shell = LayerShell::bind(&globals, &qh).expect("layer shell not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl_shm not available");
+    let pool = SlotPool::new(WIDTH as usize * 128 * 4, &shm).expect("Failed to create pool");
+
+    let mut daemon = SimpleNotifications::init(
+        &globals,
+        &qh,
+        loop_handle.clone(),
+        compositor,
+        layer_shell,
+        shm,
+        pool,
+        bus,
+        Font::load(15.0)?,
+        icons.load(),
+    );
+    daemon.corner = args.corner;
+    daemon.default_timeout = Duration::from_millis(args.default_timeout);
+    daemon.insert_requests(channel);
+
+    event_loop.run(None, &mut daemon, |_| {})?;
+
+    Ok(())
+}
+
+impl SimpleNotifications {
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        globals: &GlobalList,
+        qh: &QueueHandle<Self>,
+        loop_handle: LoopHandle<'static, SimpleNotifications>,
+        compositor_state: CompositorState,
+        layer_shell: LayerShell,
+        shm: Shm,
+        pool: SlotPool,
+        bus: zbus::blocking::Connection,
+        font: Font,
+        icons: Icons,
+    ) -> Self {
+        SimpleNotifications {
+            registry_state: RegistryState::new(globals),
+            seat_state: SeatState::new(globals, qh),
+            output_state: OutputState::new(globals, qh),
+            compositor_state,
+            layer_shell,
+            shm,
+
+            pool,
+            popups: Vec::new(),
+            pointer: None,
+            corner: Corner::TopRight,
+            default_timeout: Duration::from_secs(5),
+            bus,
+            font,
+            icons,
+            qh: qh.clone(),
+            loop_handle,
+        }
+    }
+
+    fn insert_requests(&self, channel: Channel<Request>) {
+        let result = self
+            .loop_handle
+            .insert_source(channel, |event, _, daemon| match event {
+                channel::Event::Msg(Request::Notify(notification)) => daemon.notify(notification),
+                channel::Event::Msg(Request::Close(id)) => daemon.close(id, CloseReason::Closed),
+                channel::Event::Closed => {}
+            });
+        if let Err(error) = result {
+            warn!("Failed to listen for D-Bus requests: {}", error.error);
+        }
+    }
+
+    /// Show a new notification, or update the one it replaces.
+    fn notify(&mut self, notification: Notification) {
+        let id = notification.id;
+        // The surface is drawn at scale 1.
+        let icon = Some(notification.icon.as_str())
+            .filter(|icon| !icon.is_empty())
+            .and_then(|icon| self.icons.get(icon, ICON_SIZE, 1));
+        let body = self.layout_body(&notification, icon.is_some());
+        let height = self.popup_height(&notification, body.len());
+
+        let index = match self.popups.iter().position(|p| p.notification.id == id) {
+            Some(index) => {
+                let popup = &mut self.popups[index];
+                popup.notification = notification;
+                popup.body = body;
+                popup.icon = icon;
+                if popup.height != height {
+                    popup.height = height;
+                    popup.layer.set_size(WIDTH, height);
+                }
+                index
+            }
+            None => {
+                let surface = self.compositor_state.create_surface(&self.qh);
+                let layer = self.layer_shell.create_layer_surface(
+                    &self.qh,
+                    surface,
+                    Layer::Overlay,
+                    Some("notification"),
+                    None,
+                );
+                layer.set_anchor(self.corner.anchor());
+                layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+                layer.set_size(WIDTH, height);
+
+                self.popups.insert(
+                    0,
+                    Popup {
+                        notification,
+                        layer,
+                        height,
+                        configured: false,
+                        buttons: Vec::new(),
+                        body,
+                        icon,
+                        timer: None,
+                    },
+                );
+                0
+            }
+        };
+
+        self.start_timer(index);
+        self.restack();
+        if self.popups[index].configured {
+            self.draw(index);
+        }
+    }
+
+    /// Remove a notification and tell the bus why.
+    fn close(&mut self, id: u32, reason: CloseReason) {
+        let Some(index) = self.popups.iter().position(|p| p.notification.id == id) else {
+            return;
+        };
+
+        info!("Closing notification {id}: {reason:?}");
+        let popup = self.popups.remove(index);
+        if let Some(timer) = popup.timer {
+            self.loop_handle.remove(timer);
+        }
+        notification_server::notification_closed(&self.bus, id, reason);
+        self.restack();
+    }
+
+    /// Expire the popup after its timeout, replacing any earlier timer.
+    fn start_timer(&mut self, index: usize) {
+        let popup = &mut self.popups[index];
+        if let Some(timer) = popup.timer.take() {
+            self.loop_handle.remove(timer);
+        }
+
+        let timeout = match popup.notification.expire_timeout {
+            0 => return,
+            timeout if timeout > 0 => Duration::from_millis(timeout as u64),
+            // Critical notifications stay until dismissed unless they ask otherwise.
+            _ if popup.notification.urgency == Urgency::Critical => return,
+            _ => self.default_timeout,
+        };
+
+        let id = popup.notification.id;
+        let result =
+            self.loop_handle
+                .insert_source(Timer::from_duration(timeout), move |_, _, daemon| {
+                    if let Some(popup) = daemon.popups.iter_mut().find(|p| p.notification.id == id)
+                    {
+                        popup.timer = None;
+                    }
+                    daemon.close(id, CloseReason::Expired);
+                    TimeoutAction::Drop
+                });
+        match result {
+            Ok(token) => self.popups[index].timer = Some(token),
+            Err(error) => warn!("Failed to start the expiry timer: {}", error.error),
+        }
+    }
+
+    /// Move every popup to its place in the stack, counting from the corner.
+    fn restack(&mut self) {
+        let mut offset = GAP;
+        for popup in &self.popups {
+            let (top, right, bottom, left) = match self.corner {
+                Corner::TopLeft => (offset, 0, 0, GAP),
+                Corner::TopRight => (offset, GAP, 0, 0),
+                Corner::BottomLeft => (0, 0, offset, GAP),
+                Corner::BottomRight => (0, GAP, offset, 0),
+            };
+            popup.layer.set_margin(top, right, bottom, left);
+            popup.layer.commit();
+            offset += popup.height as i32 + GAP;
+        }
+    }
+
+    fn layout_body(&mut self, notification: &Notification, icon: bool) -> Vec<Vec<Span>> {
+        let spans = markup::parse(&notification.body);
+        let max_width = WIDTH as i32 - 2 * (PADDING + BORDER) - icon_indent(icon);
+        let mut lines = markup::wrap(&spans, max_width, |text| self.font.measure(text));
+        lines.retain(|line| !line.is_empty());
+        lines.truncate(MAX_BODY_LINES);
+        lines
+    }
+
+    fn popup_height(&self, notification: &Notification, body_lines: usize) -> u32 {
+        let line_height = self.font.line_height();
+        // App name, summary and the body.
+        let mut height = 2 * (PADDING + BORDER) + line_height * (2 + body_lines as i32);
+        if notification
+            .actions
+            .iter()
+            .any(|(key, _)| key != DEFAULT_ACTION)
+        {
+            height += PADDING + BUTTON_HEIGHT;
+        }
+        height as u32
+    }
+
+    pub fn draw(&mut self, index: usize) {
+        let popup = &mut self.popups[index];
+        let (width, height) = (WIDTH, popup.height);
+
+        let (buffer, canvas) = self
+            .pool
+            .create_buffer(
+                width as i32,
+                height as i32,
+                width as i32 * 4,
+                wl_shm::Format::Argb8888,
+            )
+            .expect("create buffer");
+
+        let (w, h) = (width as i32, height as i32);
+        let border = border_color(popup.notification.urgency);
+        fill_rect(canvas, width, height, 0, 0, w, h, border);
+        fill_rect(
+            canvas,
+            width,
+            height,
+            BORDER,
+            BORDER,
+            w - 2 * BORDER,
+            h - 2 * BORDER,
+            BACKGROUND,
+        );
+
+        let font = &mut self.font;
+        let edge = PADDING + BORDER;
+        if let Some(icon) = &popup.icon {
+            Canvas::new(canvas, width, height).blit(icon, edge, edge, 1.0);
+        }
+        let left = edge + icon_indent(popup.icon.is_some());
+        let line_height = font.line_height();
+        let mut baseline = edge + font.ascent();
+
+        let app_name = popup.notification.app_name.as_str();
+        font.draw(canvas, width, height, left, baseline, app_name, DIM);
+        baseline += line_height;
+
+        // The summary is plain text, drawn bold.
+        let summary = popup.notification.summary.as_str();
+        font.draw(canvas, width, height, left, baseline, summary, FOREGROUND);
+        font.draw(
+            canvas,
+            width,
+            height,
+            left + 1,
+            baseline,
+            summary,
+            FOREGROUND,
+        );
+        baseline += line_height;
+
+        for line in &popup.body {
+            let mut x = left;
+            for span in line {
+                let end = font.draw(canvas, width, height, x, baseline, &span.text, FOREGROUND);
+                if span.style.bold {
+                    font.draw(
+                        canvas,
+                        width,
+                        height,
+                        x + 1,
+                        baseline,
+                        &span.text,
+                        FOREGROUND,
+                    );
+                }
+                if span.style.underline {
+                    fill_rect(
+                        canvas,
+                        width,
+                        height,
+                        x,
+                        baseline + 2,
+                        end - x,
+                        1,
+                        FOREGROUND,
+                    );
+                }
+                x = end;
+            }
+            baseline += line_height;
+        }
+
+        // Buttons share the bottom row evenly.
+        popup.buttons.clear();
+        let actions: Vec<_> = popup
+            .notification
+            .actions
+            .iter()
+            .filter(|(key, _)| key != DEFAULT_ACTION)
+            .collect();
+        if !actions.is_empty() {
+            let count = actions.len() as i32;
+            let button_width = (w - 2 * edge - GAP * (count - 1)) / count;
+            let top = h - edge - BUTTON_HEIGHT;
+            for (index, (key, label)) in actions.into_iter().enumerate() {
+                let x = edge + index as i32 * (button_width + GAP);
+                fill_rect(
+                    canvas,
+                    width,
+                    height,
+                    x,
+                    top,
+                    button_width,
+                    BUTTON_HEIGHT,
+                    BUTTON,
+                );
+
+                let label_x = x + (button_width - font.measure(label)).max(0) / 2;
+                let label_y = top + (BUTTON_HEIGHT - line_height) / 2 + font.ascent();
+                font.draw(canvas, width, height, label_x, label_y, label, FOREGROUND);
+                popup
+                    .buttons
+                    .push((x, top, button_width, BUTTON_HEIGHT, key.clone()));
+            }
+        }
+
+        let surface = popup.layer.wl_surface();
+        surface.damage_buffer(0, 0, w, h);
+        buffer.attach_to(surface).expect("buffer attach");
+        popup.layer.commit();
+    }
+
+    /// A left click invokes the button under the pointer, or the default action, then dismisses.
+    fn click(&mut self, surface: &wl_surface::WlSurface, (x, y): (f64, f64), button: u32) {
+        let Some(popup) = self.popups.iter().find(|p| p.layer.wl_surface() == surface) else {
+            return;
+        };
+        let id = popup.notification.id;
+
+        if button == BTN_LEFT {
+            let (x, y) = (x as i32, y as i32);
+            let pressed = popup
+                .buttons
+                .iter()
+                .find(|(bx, by, bw, bh, _)| x >= *bx && x < bx + bw && y >= *by && y < by + bh)
+                .map(|(_, _, _, _, key)| key.as_str())
+                .or_else(|| {
+                    popup
+                        .notification
+                        .actions
+                        .iter()
+                        .find(|(key, _)| key == DEFAULT_ACTION)
+                        .map(|(key, _)| key.as_str())
+                });
+            if let Some(key) = pressed {
+                info!("Invoking action {key:?} of notification {id}");
+                notification_server::action_invoked(&self.bus, id, key);
+            }
+        }
+
+        self.close(id, CloseReason::Dismissed);
+    }
+}
+
+impl Corner {
+    fn anchor(self) -> Anchor {
+        match self {
+            Corner::TopLeft => Anchor::TOP | Anchor::LEFT,
+            Corner::TopRight => Anchor::TOP | Anchor::RIGHT,
+            Corner::BottomLeft => Anchor::BOTTOM | Anchor::LEFT,
+            Corner::BottomRight => Anchor::BOTTOM | Anchor::RIGHT,
+        }
+    }
+}
+
+impl CompositorHandler for SimpleNotifications {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_factor: i32,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        // Popups are only redrawn when their notification changes.
+    }
+}
+
+impl OutputHandler for SimpleNotifications {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl LayerShellHandler for SimpleNotifications {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, layer: &LayerSurface) {
+        if let Some(popup) = self.popups.iter().find(|p| &p.layer == layer) {
+            let id = popup.notification.id;
+            self.close(id, CloseReason::Dismissed);
+        }
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        _configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        if let Some(index) = self.popups.iter().position(|p| &p.layer == layer) {
+            self.popups[index].configured = true;
+            self.draw(index);
+        }
+    }
+}
+
+impl SeatHandler for SimpleNotifications {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            info!("Set pointer capability");
+            let pointer = self
+                .seat_state
+                .get_pointer(qh, &seat)
+                .expect("Failed to create pointer");
+            self.pointer = Some(pointer);
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _: &QueueHandle<Self>,
+        _: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Pointer && self.pointer.is_some() {
+            info!("Unset pointer capability");
+            self.pointer.take().unwrap().release();
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl PointerHandler for SimpleNotifications {
+    fn pointer_frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _pointer: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            if let PointerEventKind::Press { button, .. } = event.kind {
+                self.click(&event.surface, event.position, button);
+            }
+        }
+    }
+}
+
+impl ShmHandler for SimpleNotifications {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_compositor!(SimpleNotifications);
+delegate_output!(SimpleNotifications);
+delegate_shm!(SimpleNotifications);
+
+delegate_seat!(SimpleNotifications);
+delegate_pointer!(SimpleNotifications);
+
+delegate_layer!(SimpleNotifications);
+
+delegate_registry!(SimpleNotifications);
+
+impl ProvidesRegistryState for SimpleNotifications {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
@@ -0,0 +1,535 @@
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::calloop::{
+        generic::Generic,
+        timer::{TimeoutAction, Timer},
+        EventLoop, Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+    },
+    reexports::client::{
+        globals::{registry_queue_init, GlobalList},
+        protocol::{wl_output, wl_shm, wl_surface},
+        Connection, QueueHandle, WaylandSource,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    shell::{
+        wlr_layer::{
+            KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    shm::slot::SlotPool,
+    shm::{Shm, ShmHandler},
+};
+use tracing::{info, warn};
+
+use crate::clipboard::read_pipe;
+use crate::commands::{OsdArgs, OsdIcon};
+use crate::draw::fill_rect;
+use crate::error::AppResult;
+use crate::font::Font;
+use crate::xdg;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 96;
+const PADDING: i32 = 16;
+const ICON_SIZE: i32 = HEIGHT as i32 - PADDING * 2;
+const BAR_HEIGHT: i32 = 8;
+
+const BACKGROUND: u32 = 0xFF_20_20_28;
+const FOREGROUND: u32 = 0xFF_E0_E0_E0;
+const TRACK: u32 = 0xFF_40_40_4C;
+const ACCENT: u32 = 0xFF_30_60_C0;
+
+/// A value to show, sent as one `value<TAB>icon<TAB>label` line over the OSD socket.
+#[derive(Debug, Clone)]
+pub(crate) struct OsdUpdate {
+    pub(crate) value: u32,
+    pub(crate) icon: Option<OsdIcon>,
+    pub(crate) label: String,
+}
+
+impl OsdUpdate {
+    fn to_line(&self) -> String {
+        let icon = self
+            .icon
+            .and_then(|icon| icon.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        format!("{}\t{icon}\t{}", self.value, self.label.replace('\n', " "))
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, '\t');
+        let value = fields.next()?.trim().parse().ok()?;
+        let icon = fields
+            .next()
+            .filter(|icon| !icon.is_empty())
+            .and_then(|icon| OsdIcon::from_str(icon, true).ok());
+        let label = fields.next().unwrap_or_default().to_string();
+        Some(OsdUpdate { value, icon, label })
+    }
+}
+
+/// Whether the surface is on screen.
+///
+/// Hiding attaches a null buffer, which unmaps the layer surface; showing it again needs an
+/// empty commit and a fresh configure before the next buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visibility {
+    Hidden,
+    Configuring,
+    Shown,
+}
+
+pub(crate) struct SimpleOsd {
+    pub(crate) registry_state: RegistryState,
+    pub(crate) output_state: OutputState,
+    pub(crate) shm: Shm,
+
+    pub(crate) exit: bool,
+    pub(crate) pool: SlotPool,
+    pub(crate) layer: LayerSurface,
+    visibility: Visibility,
+    pub(crate) update: OsdUpdate,
+    pub(crate) timeout: Duration,
+    hide_timer: Option<RegistrationToken>,
+    font: Font,
+    icon_font: Font,
+    loop_handle: LoopHandle<'static, SimpleOsd>,
+}
+
+fn socket_path() -> io::Result<PathBuf> {
+    xdg::runtime_socket("osd")
+}
+
+/// Hand the value to a running OSD, or become the OSD if none is running.
+pub(crate) fn run(args: OsdArgs) -> AppResult<()> {
+    let update = OsdUpdate {
+        value: args.value,
+        icon: args.icon,
+        label: args.label,
+    };
+
+    let path = socket_path()?;
+    match UnixStream::connect(&path) {
+        Ok(mut stream) => {
+            writeln!(stream, "{}", update.to_line())?;
+            info!("Sent {update:?} to the running OSD");
+            return Ok(());
+        }
+        // Left behind by an OSD that did not shut down cleanly.
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+            fs::remove_file(&path)?;
+        }
+        Err(_) => {}
+    }
+
+    let connection = Connection::connect_to_env()?;
+    let listener = xdg::bind_private(&path)?;
+    listener.set_nonblocking(true)?;
+    info!("Listening on {}", path.display());
+
+    let (globals, queue) = registry_queue_init(&connection)?;
+    let qh = queue.handle();
+    let mut event_loop = EventLoop::<SimpleOsd>::try_new().expect("Failed to create event loop");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(queue)
+        .unwrap()
+        .insert(loop_handle.clone())
+        .unwrap();
+
+    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let layer_shell = LayerShell::bind(&globals, &qh).expect("layer shell not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl_shm not available");
+    let pool = SlotPool::new((WIDTH * HEIGHT * 4) as usize, &shm).expect("Failed to create pool");
+
+    // Centered without anchors, never taking keyboard or pointer input.
+    let surface = compositor.create_surface(&qh);
+    let layer = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("osd"), None);
+    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+    layer.set_size(WIDTH, HEIGHT);
+    let region = Region::new(&compositor).expect("Failed to create region");
+    layer
+        .wl_surface()
+        .set_input_region(Some(region.wl_region()));
+    layer.commit();
+
+    let font = Font::load(16.0)?;
+    let icon_font = Font::load(ICON_SIZE as f32)?;
+    let mut osd = SimpleOsd::init(
+        &globals,
+        &qh,
+        loop_handle.clone(),
+        shm,
+        pool,
+        layer,
+        font,
+        icon_font,
+    );
+    osd.update = update;
+    osd.timeout = Duration::from_millis(args.timeout);
+    osd.restart_timer();
+
+    loop_handle
+        .insert_source(
+            Generic::new(listener, Interest::READ, Mode::Level),
+            |_, listener, osd| {
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            read_pipe(&osd.loop_handle, stream, |osd: &mut SimpleOsd, text| {
+                                osd.receive(&text)
+                            })
+                        }
+                        Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(error) => {
+                            warn!("Failed to accept OSD connection: {error}");
+                            break;
+                        }
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|error| error.error)?;
+
+    let signal = event_loop.get_signal();
+    event_loop.run(None, &mut osd, |osd| {
+        if osd.exit {
+            signal.stop();
+        }
+    })?;
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+impl SimpleOsd {
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        globals: &GlobalList,
+        qh: &QueueHandle<Self>,
+        loop_handle: LoopHandle<'static, SimpleOsd>,
+        shm: Shm,
+        pool: SlotPool,
+        layer: LayerSurface,
+        font: Font,
+        icon_font: Font,
+    ) -> Self {
+        SimpleOsd {
+            registry_state: RegistryState::new(globals),
+            output_state: OutputState::new(globals, qh),
+            shm,
+
+            exit: false,
+            pool,
+            layer,
+            // The initial commit was made by the caller.
+            visibility: Visibility::Configuring,
+            update: OsdUpdate {
+                value: 0,
+                icon: None,
+                label: String::new(),
+            },
+            timeout: Duration::from_millis(1500),
+            hide_timer: None,
+            font,
+            icon_font,
+            loop_handle,
+        }
+    }
+
+    /// Apply the last valid update sent over one connection.
+    fn receive(&mut self, text: &str) {
+        match text.lines().rev().find_map(OsdUpdate::parse) {
+            Some(update) => self.show(update),
+            None => warn!("Ignoring invalid OSD update {text:?}"),
+        }
+    }
+
+    /// Show `update`, mapping the surface again if it was hidden.
+    pub fn show(&mut self, update: OsdUpdate) {
+        info!("Showing {update:?}");
+        self.update = update;
+        self.restart_timer();
+
+        match self.visibility {
+            Visibility::Hidden => {
+                self.layer.commit();
+                self.visibility = Visibility::Configuring;
+            }
+            // Drawn once the configure arrives.
+            Visibility::Configuring => {}
+            Visibility::Shown => self.draw(),
+        }
+    }
+
+    pub fn hide(&mut self) {
+        info!("Hiding");
+        if self.visibility == Visibility::Shown {
+            self.layer.wl_surface().attach(None, 0, 0);
+            self.layer.commit();
+        }
+        self.visibility = Visibility::Hidden;
+    }
+
+    fn restart_timer(&mut self) {
+        if let Some(token) = self.hide_timer.take() {
+            self.loop_handle.remove(token);
+        }
+
+        let timer = Timer::from_duration(self.timeout);
+        let result = self.loop_handle.insert_source(timer, |_, _, osd| {
+            osd.hide_timer = None;
+            osd.hide();
+            TimeoutAction::Drop
+        });
+        match result {
+            Ok(token) => self.hide_timer = Some(token),
+            Err(error) => warn!("Failed to start the hide timer: {}", error.error),
+        }
+    }
+
+    pub fn draw(&mut self) {
+        let (width, height) = (WIDTH, HEIGHT);
+        let (buffer, canvas) = self
+            .pool
+            .create_buffer(
+                width as i32,
+                height as i32,
+                width as i32 * 4,
+                wl_shm::Format::Argb8888,
+            )
+            .expect("create buffer");
+
+        fill_rect(
+            canvas,
+            width,
+            height,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            BACKGROUND,
+        );
+
+        let mut left = PADDING;
+        if let Some(icon) = self.update.icon {
+            let glyph = match icon {
+                OsdIcon::Volume => "♪",
+                OsdIcon::Muted => "✕",
+                OsdIcon::Brightness => "☀",
+            };
+            let x = left + (ICON_SIZE - self.icon_font.measure(glyph)) / 2;
+            let y = PADDING + self.icon_font.ascent() * 7 / 8;
+            self.icon_font
+                .draw(canvas, width, height, x, y, glyph, FOREGROUND);
+            left += ICON_SIZE + PADDING;
+        }
+        let right = width as i32 - PADDING;
+
+        // Label on the left and the value on the right, above the bar.
+        let baseline = PADDING + self.font.ascent();
+        self.font.draw(
+            canvas,
+            width,
+            height,
+            left,
+            baseline,
+            &self.update.label,
+            FOREGROUND,
+        );
+        let value = format!("{}%", self.update.value);
+        let value_x = right - self.font.measure(&value);
+        self.font
+            .draw(canvas, width, height, value_x, baseline, &value, FOREGROUND);
+
+        let bar_top = height as i32 - PADDING - BAR_HEIGHT;
+        let bar_width = right - left;
+        let filled = bar_width * self.update.value.min(100) as i32 / 100;
+        fill_rect(
+            canvas, width, height, left, bar_top, bar_width, BAR_HEIGHT, TRACK,
+        );
+        fill_rect(
+            canvas, width, height, left, bar_top, filled, BAR_HEIGHT, ACCENT,
+        );
+
+        let surface = self.layer.wl_surface();
+        surface.damage_buffer(0, 0, width as i32, height as i32);
+        buffer.attach_to(surface).expect("buffer attach");
+        self.layer.commit();
+    }
+}
+
+impl CompositorHandler for SimpleOsd {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_factor: i32,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        // Only redrawn when a new value arrives.
+    }
+}
+
+impl OutputHandler for SimpleOsd {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl LayerShellHandler for SimpleOsd {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        self.exit = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        _configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        // The size is fixed, so only the first configure after mapping matters.
+        if self.visibility == Visibility::Configuring {
+            self.visibility = Visibility::Shown;
+            self.draw();
+        }
+    }
+}
+
+impl ShmHandler for SimpleOsd {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_compositor!(SimpleOsd);
+delegate_output!(SimpleOsd);
+delegate_shm!(SimpleOsd);
+
+delegate_layer!(SimpleOsd);
+
+delegate_registry!(SimpleOsd);
+
+impl ProvidesRegistryState for SimpleOsd {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(value: u32, icon: Option<OsdIcon>, label: &str) -> OsdUpdate {
+        OsdUpdate {
+            value,
+            icon,
+            label: label.to_string(),
+        }
+    }
+
+    fn fields(update: &OsdUpdate) -> (u32, Option<OsdIcon>, &str) {
+        (update.value, update.icon, &update.label)
+    }
+
+    #[test]
+    fn updates_round_trip() {
+        for sent in [
+            update(42, Some(OsdIcon::Volume), "Speakers"),
+            update(0, Some(OsdIcon::Muted), ""),
+            update(100, None, "Brightness"),
+        ] {
+            let line = sent.to_line();
+            assert_eq!(line.lines().count(), 1);
+            let received = OsdUpdate::parse(&line).unwrap();
+            assert_eq!(fields(&received), fields(&sent));
+        }
+        assert_eq!(
+            update(5, Some(OsdIcon::Brightness), "x").to_line(),
+            "5\tbrightness\tx"
+        );
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let parsed = OsdUpdate::parse("30").unwrap();
+        assert_eq!(fields(&parsed), (30, None, ""));
+        let parsed = OsdUpdate::parse(" 30 \t").unwrap();
+        assert_eq!(fields(&parsed), (30, None, ""));
+        // Unknown icons are left out rather than failing the update.
+        let parsed = OsdUpdate::parse("30\tspeaker\tLabel").unwrap();
+        assert_eq!(fields(&parsed), (30, None, "Label"));
+        assert!(OsdUpdate::parse("").is_none());
+        assert!(OsdUpdate::parse("\tvolume\tLabel").is_none());
+    }
+
+    #[test]
+    fn values_must_be_unsigned_integers() {
+        assert!(OsdUpdate::parse("-1\tvolume").is_none());
+        assert!(OsdUpdate::parse("4294967296\tvolume").is_none());
+        assert!(OsdUpdate::parse("50.5\tvolume").is_none());
+        assert!(OsdUpdate::parse("loud\tvolume").is_none());
+        // Over 100 is shown as it is, with a full bar.
+        assert_eq!(OsdUpdate::parse("150").unwrap().value, 150);
+    }
+
+    #[test]
+    fn labels_keep_tabs_but_not_newlines() {
+        let sent = update(10, None, "a\tb\nc");
+        let line = sent.to_line();
+        assert_eq!(line, "10\t\ta\tb c");
+        assert_eq!(OsdUpdate::parse(&line).unwrap().label, "a\tb c");
+    }
+}
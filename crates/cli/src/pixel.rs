@@ -0,0 +1,290 @@
+use serde::Deserialize;
+use smithay_client_toolkit::reexports::client::protocol::wl_shm;
+
+/// A `wl_shm` buffer format the renderer can fill.
+///
+/// Everything is drawn as premultiplied ARGB8888 and converted when the buffer uses another format.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PixelFormat {
+    Argb8888,
+    Xrgb8888,
+    Argb2101010,
+    Xrgb2101010,
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn wl_format(self) -> wl_shm::Format {
+        match self {
+            PixelFormat::Argb8888 => wl_shm::Format::Argb8888,
+            PixelFormat::Xrgb8888 => wl_shm::Format::Xrgb8888,
+            PixelFormat::Argb2101010 => wl_shm::Format::Argb2101010,
+            PixelFormat::Xrgb2101010 => wl_shm::Format::Xrgb2101010,
+            PixelFormat::Rgb565 => wl_shm::Format::Rgb565,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            _ => 4,
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::Argb8888 | PixelFormat::Argb2101010)
+    }
+
+    /// `preferred` if the compositor supports it and it can show the surface, otherwise the
+    /// 8-bit format every compositor supports: without alpha for opaque surfaces, which lets the
+    /// compositor skip blending them.
+    pub fn choose(
+        preferred: Option<PixelFormat>,
+        supported: &[wl_shm::Format],
+        opaque: bool,
+    ) -> PixelFormat {
+        let usable = |format: &PixelFormat| {
+            supported.contains(&format.wl_format()) && (opaque || format.has_alpha())
+        };
+        match preferred.filter(usable) {
+            Some(format) => format,
+            None if opaque => PixelFormat::Xrgb8888,
+            None => PixelFormat::Argb8888,
+        }
+    }
+
+    /// Encode one premultiplied ARGB8888 color into `pixel`, which is `bytes_per_pixel` long.
+    pub fn write(self, pixel: &mut [u8], argb: u32) {
+        let [blue, green, red, alpha] = argb.to_le_bytes();
+        match self {
+            PixelFormat::Argb8888 => pixel.copy_from_slice(&argb.to_le_bytes()),
+            PixelFormat::Xrgb8888 => pixel.copy_from_slice(&(argb | 0xFF00_0000).to_le_bytes()),
+            PixelFormat::Argb2101010 => {
+                // Alpha keeps only 2 bits, so the colors are taken back to straight alpha and
+                // premultiplied again by the rounded one, which keeps their hue and brightness.
+                let quantized = (alpha as u32 * 3 + 127) / 255;
+                let rescale = |channel: u8| match alpha as u32 {
+                    0 => 0,
+                    alpha => {
+                        let scaled =
+                            (channel as u32 * quantized * 1023 + alpha * 3 / 2) / (alpha * 3);
+                        scaled.min(quantized * 1023 / 3)
+                    }
+                };
+                let value =
+                    quantized << 30 | rescale(red) << 20 | rescale(green) << 10 | rescale(blue);
+                pixel.copy_from_slice(&value.to_le_bytes());
+            }
+            PixelFormat::Xrgb2101010 => {
+                let expand = |channel: u8| (channel as u32) << 2 | (channel as u32) >> 6;
+                let value = 3 << 30 | expand(red) << 20 | expand(green) << 10 | expand(blue);
+                pixel.copy_from_slice(&value.to_le_bytes());
+            }
+            PixelFormat::Rgb565 => {
+                let reduce = |channel: u8, max: u32| (channel as u32 * max + 127) / 255;
+                let value = reduce(red, 31) << 11 | reduce(green, 63) << 5 | reduce(blue, 31);
+                pixel.copy_from_slice(&(value as u16).to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Fills buffers of one format from drawing done in premultiplied ARGB8888.
+pub(crate) struct PixelWriter {
+    format: PixelFormat,
+    /// Where drawing happens for formats that are not laid out like ARGB8888.
+    scratch: Vec<u8>,
+}
+
+impl PixelWriter {
+    pub fn new(format: PixelFormat) -> Self {
+        PixelWriter {
+            format,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Bytes per row of a buffer `width` pixels wide.
+    pub fn stride(&self, width: u32) -> i32 {
+        (width as usize * self.format.bytes_per_pixel()) as i32
+    }
+
+    /// Let `draw` fill an ARGB8888 canvas of `width` by `height` and leave the result in `buffer`.
+    ///
+    /// The 8-bit formats share ARGB8888's layout and are drawn into directly.
+    pub fn render(
+        &mut self,
+        buffer: &mut [u8],
+        width: u32,
+        height: u32,
+        draw: impl FnOnce(&mut [u8]),
+    ) {
+        let pixels = width as usize * height as usize;
+        match self.format {
+            // The compositor ignores Xrgb8888's alpha byte.
+            PixelFormat::Argb8888 | PixelFormat::Xrgb8888 => draw(&mut buffer[..pixels * 4]),
+            format => {
+                self.scratch.clear();
+                self.scratch.resize(pixels * 4, 0);
+                draw(&mut self.scratch);
+
+                let size = format.bytes_per_pixel();
+                for (source, pixel) in self
+                    .scratch
+                    .chunks_exact(4)
+                    .zip(buffer.chunks_exact_mut(size))
+                {
+                    format.write(pixel, u32::from_le_bytes(source.try_into().unwrap()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `argb` written as `format`, read back as one little-endian value.
+    fn encode(format: PixelFormat, argb: u32) -> u32 {
+        let mut pixel = [0; 4];
+        let size = format.bytes_per_pixel();
+        format.write(&mut pixel[..size], argb);
+        u32::from_le_bytes(pixel)
+    }
+
+    /// The alpha and color channels of a 2101010 value.
+    fn channels(value: u32) -> (u32, [u32; 3]) {
+        let channel = |shift: u32| value >> shift & 0x3FF;
+        (value >> 30, [channel(20), channel(10), channel(0)])
+    }
+
+    #[test]
+    fn argb2101010_keeps_colors_under_rounded_alpha() {
+        // Opaque colors only gain precision.
+        assert_eq!(
+            channels(encode(PixelFormat::Argb2101010, 0xFF_FF_80_00)),
+            (3, [1023, 514, 0])
+        );
+        // Full red at half opacity stays full red at the 2-bit alpha it rounds to.
+        assert_eq!(
+            channels(encode(PixelFormat::Argb2101010, 0x80_80_40_00)),
+            (2, [682, 341, 0])
+        );
+        assert_eq!(
+            channels(encode(PixelFormat::Argb2101010, 0x60_60_60_60)),
+            (1, [341, 341, 341])
+        );
+        // Too faint for 2 bits.
+        assert_eq!(encode(PixelFormat::Argb2101010, 0x20_20_20_20), 0);
+        assert_eq!(encode(PixelFormat::Argb2101010, 0), 0);
+    }
+
+    #[test]
+    fn argb2101010_stays_premultiplied_and_keeps_the_straight_color() {
+        for alpha in 1..=255u32 {
+            for channel in 0..=alpha {
+                let argb = alpha << 24 | channel << 16 | channel << 8 | channel;
+                let (quantized, colors) = channels(encode(PixelFormat::Argb2101010, argb));
+                let limit = quantized * 1023 / 3;
+                for color in colors {
+                    assert!(color <= limit, "{argb:08x}: {color} over {limit}");
+                    if quantized > 0 {
+                        let straight = channel as f32 / alpha as f32;
+                        let encoded = color as f32 / limit as f32;
+                        assert!(
+                            (straight - encoded).abs() < 0.005,
+                            "{argb:08x}: {straight} became {encoded}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xrgb2101010_is_opaque_and_expands_channels() {
+        assert_eq!(
+            channels(encode(PixelFormat::Xrgb2101010, 0x00_FF_80_01)),
+            (3, [1023, 514, 4])
+        );
+    }
+
+    #[test]
+    fn chooses_a_supported_format_that_can_show_the_surface() {
+        let supported = [
+            wl_shm::Format::Argb8888,
+            wl_shm::Format::Xrgb8888,
+            wl_shm::Format::Xrgb2101010,
+            wl_shm::Format::Rgb565,
+        ];
+        let choose = |preferred, opaque| PixelFormat::choose(preferred, &supported, opaque);
+
+        assert_eq!(choose(None, true), PixelFormat::Xrgb8888);
+        assert_eq!(choose(None, false), PixelFormat::Argb8888);
+        assert_eq!(choose(Some(PixelFormat::Rgb565), true), PixelFormat::Rgb565);
+        // No alpha channel to show a translucent surface with.
+        assert_eq!(
+            choose(Some(PixelFormat::Xrgb2101010), false),
+            PixelFormat::Argb8888
+        );
+        // Not offered by the compositor.
+        assert_eq!(
+            choose(Some(PixelFormat::Argb2101010), false),
+            PixelFormat::Argb8888
+        );
+    }
+
+    #[test]
+    fn rgb565_rounds_to_the_nearest_level() {
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF_FF_FF_FF), 0xFFFF);
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF_FF_00_00), 0xF800);
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF_00_FF_00), 0x07E0);
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF_00_00_FF), 0x001F);
+        // 0x80 is just over half way: 16 of 31 and 32 of 63.
+        assert_eq!(
+            encode(PixelFormat::Rgb565, 0xFF_80_80_80),
+            16 << 11 | 32 << 5 | 16
+        );
+    }
+
+    #[test]
+    fn xrgb8888_sets_the_unused_alpha() {
+        assert_eq!(encode(PixelFormat::Xrgb8888, 0x00_12_34_56), 0xFF_12_34_56);
+        assert_eq!(encode(PixelFormat::Argb8888, 0x80_12_34_56), 0x80_12_34_56);
+    }
+
+    #[test]
+    fn renders_through_scratch_for_other_layouts() {
+        let draw = |pixels: &mut [u8]| {
+            for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                let color = if index % 2 == 0 {
+                    0xFF_FF_00_00u32
+                } else {
+                    0xFF_00_00_FF
+                };
+                pixel.copy_from_slice(&color.to_le_bytes());
+            }
+        };
+
+        let mut writer = PixelWriter::new(PixelFormat::Rgb565);
+        let mut buffer = vec![0; writer.stride(3) as usize * 2];
+        assert_eq!(writer.stride(3), 6);
+        writer.render(&mut buffer, 3, 2, draw);
+        let pixels: Vec<u16> = buffer
+            .chunks_exact(2)
+            .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+            .collect();
+        assert_eq!(pixels, [0xF800, 0x001F, 0xF800, 0x001F, 0xF800, 0x001F]);
+
+        let mut writer = PixelWriter::new(PixelFormat::Argb8888);
+        let mut buffer = vec![0; writer.stride(2) as usize];
+        writer.render(&mut buffer, 2, 1, draw);
+        assert_eq!(buffer, [0, 0, 0xFF, 0xFF, 0xFF, 0, 0, 0xFF]);
+    }
+}
@@ -0,0 +1,448 @@
+use std::{fmt, time::Duration};
+
+use smithay_client_toolkit::reexports::{
+    calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle, RegistrationToken,
+    },
+    client::{
+        globals::GlobalList, protocol::wl_surface::WlSurface, Connection, Dispatch, QueueHandle,
+        WEnum,
+    },
+    protocols::wp::presentation_time::client::{
+        wp_presentation::{self, WpPresentation},
+        wp_presentation_feedback::{self, Kind, WpPresentationFeedback},
+    },
+};
+use tracing::warn;
+
+use crate::draw::{Canvas, Rect};
+use crate::font::Font;
+
+/// How often a [`Summary`] of the frames is made.
+const PERIOD: Duration = Duration::from_secs(1);
+const OVERLAY_PADDING: i32 = 6;
+const OVERLAY_BACKGROUND: u32 = 0xC0_10_10_14;
+const OVERLAY_FOREGROUND: u32 = 0xFF_A0_F0_A0;
+
+/// Frame timing through `wp_presentation`, collected while [`Presentation::enabled`] is set.
+pub(crate) struct Presentation {
+    manager: Option<WpPresentation>,
+    /// The clock presentation timestamps are in, which the compositor announces on binding.
+    clock: libc::clockid_t,
+    pub(crate) enabled: bool,
+    pub(crate) stats: FrameStats,
+    /// Closes a period every [`PERIOD`] while enabled, whether or not anything was drawn.
+    timer: Option<RegistrationToken>,
+}
+
+/// Access to the presentation state from protocol events.
+pub(crate) trait PresentationHandler: Sized {
+    fn presentation(&mut self) -> &mut Presentation;
+
+    /// A new [`Summary`] is in [`FrameStats::summary`], once per period while enabled.
+    fn stats_updated(&mut self);
+}
+
+/// What is known about a frame when it is committed.
+#[derive(Debug)]
+pub(crate) struct FeedbackData {
+    /// When it was committed on the presentation clock, in nanoseconds.
+    committed: u64,
+    /// How long drawing it took.
+    render: Duration,
+}
+
+impl Presentation {
+    pub fn new<D>(globals: &GlobalList, qh: &QueueHandle<D>) -> Self
+    where
+        D: Dispatch<WpPresentation, ()> + 'static,
+    {
+        let manager = globals
+            .bind(qh, 1..=1, ())
+            .map_err(|error| warn!("wp_presentation not available: {error}"))
+            .ok();
+
+        Presentation {
+            manager,
+            clock: libc::CLOCK_MONOTONIC,
+            enabled: false,
+            stats: FrameStats::default(),
+            timer: None,
+        }
+    }
+
+    /// Start or stop collecting, starting over from no frames.
+    ///
+    /// Periods are closed by a timer on `loop_handle` rather than by presented frames, so a
+    /// surface that stops drawing shows that instead of the last busy period.
+    pub fn toggle<D>(&mut self, loop_handle: &LoopHandle<'static, D>)
+    where
+        D: PresentationHandler + 'static,
+    {
+        self.enabled = !self.enabled;
+        self.stats = FrameStats::default();
+        if let Some(timer) = self.timer.take() {
+            loop_handle.remove(timer);
+        }
+        if !self.enabled {
+            return;
+        }
+        if self.manager.is_none() {
+            warn!("Frame statistics need wp_presentation, which the compositor does not offer");
+        }
+
+        self.stats.start = Some(now(self.clock));
+        let timer = Timer::from_duration(PERIOD);
+        let inserted = loop_handle.insert_source(timer, |_, _, state: &mut D| {
+            let presentation = state.presentation();
+            presentation.stats.close(now(presentation.clock));
+            state.stats_updated();
+            TimeoutAction::ToDuration(PERIOD)
+        });
+        match inserted {
+            Ok(timer) => self.timer = Some(timer),
+            Err(error) => warn!("Frame statistics will not be summarized: {error}"),
+        }
+    }
+
+    /// Ask for feedback on the frame about to be committed to `surface`, which took `render`
+    /// to draw.
+    pub fn feedback<D>(&self, surface: &WlSurface, qh: &QueueHandle<D>, render: Duration)
+    where
+        D: Dispatch<WpPresentationFeedback, FeedbackData> + 'static,
+    {
+        if let (true, Some(manager)) = (self.enabled, &self.manager) {
+            let committed = now(self.clock);
+            manager.feedback(surface, qh, FeedbackData { committed, render });
+        }
+    }
+}
+
+/// The current time on `clock` in nanoseconds.
+fn now(clock: libc::clockid_t) -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `clock_gettime` only writes to `time`.
+    unsafe { libc::clock_gettime(clock, &mut time) };
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+/// Statistics over the frames presented during one period.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Summary {
+    pub(crate) fps: f32,
+    /// Frames shown at least a refresh later than the one they were committed for.
+    pub(crate) missed: u32,
+    /// Frames replaced before they were ever shown.
+    pub(crate) discarded: u32,
+    pub(crate) render_avg: Duration,
+    pub(crate) render_max: Duration,
+    /// From commit to the frame turning visible.
+    pub(crate) latency_avg: Duration,
+    /// Zero when the output has no constant refresh rate.
+    pub(crate) refresh: Duration,
+    /// How the last frame was presented.
+    pub(crate) flags: Kind,
+}
+
+impl Summary {
+    /// Everything, a line per kind of measurement.
+    pub fn lines(&self) -> Vec<String> {
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        let mut flags = Vec::new();
+        for (flag, name) in [
+            (Kind::Vsync, "vsync"),
+            (Kind::HwClock, "hw-clock"),
+            (Kind::HwCompletion, "hw-completion"),
+            (Kind::ZeroCopy, "zero-copy"),
+        ] {
+            if self.flags.contains(flag) {
+                flags.push(name);
+            }
+        }
+        vec![
+            format!(
+                "{:.0} fps, {} missed, {} discarded",
+                self.fps, self.missed, self.discarded
+            ),
+            format!(
+                "render {:.2} ms avg, {:.2} ms max",
+                ms(self.render_avg),
+                ms(self.render_max)
+            ),
+            format!(
+                "latency {:.2} ms, refresh {:.2} ms",
+                ms(self.latency_avg),
+                ms(self.refresh)
+            ),
+            format!("flags: {}", flags.join(" ")),
+        ]
+    }
+}
+
+/// The most important numbers only, to fit a bar.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} fps, {} missed, render {:.2} ms, latency {:.2} ms",
+            self.fps,
+            self.missed,
+            self.render_avg.as_secs_f32() * 1000.0,
+            self.latency_avg.as_secs_f32() * 1000.0
+        )
+    }
+}
+
+/// Collects presentation feedback into a [`Summary`] of each period.
+#[derive(Debug, Default)]
+pub(crate) struct FrameStats {
+    /// When the previous frame was presented, in nanoseconds.
+    last_presented: Option<u64>,
+    /// When the current period started, in nanoseconds.
+    start: Option<u64>,
+    /// The output's refresh interval when the last frame was presented, in nanoseconds.
+    refresh: u32,
+    /// How the last frame was presented.
+    flags: Option<Kind>,
+    frames: u32,
+    missed: u32,
+    discarded: u32,
+    render_total: Duration,
+    render_max: Duration,
+    latency_total: Duration,
+    pub(crate) summary: Option<Summary>,
+}
+
+impl FrameStats {
+    /// Record a presented frame.
+    fn presented(&mut self, data: &FeedbackData, time: u64, refresh: u32, flags: Kind) {
+        self.frames += 1;
+        self.render_total += data.render;
+        self.render_max = self.render_max.max(data.render);
+        self.latency_total += Duration::from_nanos(time.saturating_sub(data.committed));
+
+        // Only frames committed in time for the next refresh could miss it, the others were
+        // drawn after the surface had been idle.
+        if let (Some(last), refresh @ 1..) = (self.last_presented, refresh as u64) {
+            if data.committed < last + refresh && time > last + refresh * 3 / 2 {
+                self.missed += ((time - last + refresh / 2) / refresh - 1) as u32;
+            }
+        }
+        self.last_presented = Some(time);
+        self.refresh = refresh;
+        self.flags = Some(flags);
+    }
+
+    /// Summarize the period ending at `time` and start the next one, even if no frames were
+    /// presented in it.
+    fn close(&mut self, time: u64) {
+        let start = *self.start.get_or_insert(time);
+        let elapsed = time.saturating_sub(start).max(1);
+        let frames = self.frames.max(1);
+        *self = FrameStats {
+            last_presented: self.last_presented,
+            start: Some(time),
+            refresh: self.refresh,
+            flags: self.flags,
+            summary: Some(Summary {
+                fps: self.frames as f32 * 1e9 / elapsed as f32,
+                missed: self.missed,
+                discarded: self.discarded,
+                render_avg: self.render_total / frames,
+                render_max: self.render_max,
+                latency_avg: self.latency_total / frames,
+                refresh: Duration::from_nanos(self.refresh as u64),
+                flags: self.flags.unwrap_or(Kind::empty()),
+            }),
+            ..FrameStats::default()
+        };
+    }
+
+    /// Draw `lines` of the summary in the top right corner, or a placeholder before the first
+    /// one is complete.
+    pub fn paint(
+        &self,
+        canvas: &mut Canvas,
+        font: &mut Font,
+        lines: impl Fn(&Summary) -> Vec<String>,
+    ) {
+        let lines = match &self.summary {
+            Some(summary) => lines(summary),
+            None => vec!["Collecting frame statistics".to_string()],
+        };
+        let width = lines
+            .iter()
+            .map(|line| font.measure(line))
+            .max()
+            .unwrap_or(0);
+        let line_height = font.line_height();
+        let bounds = canvas.bounds();
+        let area = Rect::new(
+            bounds.right() - width - 2 * OVERLAY_PADDING,
+            0,
+            width + 2 * OVERLAY_PADDING,
+            line_height * lines.len() as i32 + 2 * OVERLAY_PADDING,
+        );
+        canvas.fill_rect(area, OVERLAY_BACKGROUND);
+        for (index, line) in lines.iter().enumerate() {
+            let baseline = area.y + OVERLAY_PADDING + index as i32 * line_height + font.ascent();
+            canvas.text(
+                font,
+                area.x + OVERLAY_PADDING,
+                baseline,
+                line,
+                OVERLAY_FOREGROUND,
+            );
+        }
+    }
+}
+
+impl<D> Dispatch<WpPresentation, (), D> for Presentation
+where
+    D: Dispatch<WpPresentation, ()> + PresentationHandler,
+{
+    fn event(
+        state: &mut D,
+        _: &WpPresentation,
+        event: wp_presentation::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            state.presentation().clock = clk_id as libc::clockid_t;
+        }
+    }
+}
+
+impl<D> Dispatch<WpPresentationFeedback, FeedbackData, D> for Presentation
+where
+    D: Dispatch<WpPresentationFeedback, FeedbackData> + PresentationHandler,
+{
+    fn event(
+        state: &mut D,
+        _: &WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        data: &FeedbackData,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let presentation = state.presentation();
+        if !presentation.enabled {
+            return;
+        }
+        match event {
+            wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                refresh,
+                flags,
+                ..
+            } => {
+                let seconds = ((tv_sec_hi as u64) << 32) | tv_sec_lo as u64;
+                let time = seconds * 1_000_000_000 + tv_nsec as u64;
+                let flags = match flags {
+                    WEnum::Value(flags) => flags,
+                    WEnum::Unknown(bits) => Kind::from_bits_truncate(bits),
+                };
+                presentation.stats.presented(data, time, refresh, flags);
+            }
+            wp_presentation_feedback::Event::Discarded => presentation.stats.discarded += 1,
+            // The output it was shown on is not needed.
+            _ => {}
+        }
+    }
+}
+
+macro_rules! delegate_presentation {
+    ($ty: ty) => {
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation::WpPresentation: ()
+        ] => $crate::presentation::Presentation);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::WpPresentationFeedback: $crate::presentation::FeedbackData
+        ] => $crate::presentation::Presentation);
+    };
+}
+
+pub(crate) use delegate_presentation;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+    const REFRESH: u32 = 16_666_667;
+
+    fn frame(committed: u64, render_ms: u64) -> FeedbackData {
+        FeedbackData {
+            committed,
+            render: Duration::from_millis(render_ms),
+        }
+    }
+
+    fn started() -> FrameStats {
+        FrameStats {
+            start: Some(0),
+            ..FrameStats::default()
+        }
+    }
+
+    #[test]
+    fn summarizes_the_frames_of_a_period() {
+        let mut stats = started();
+        let refresh = REFRESH as u64;
+        for index in 0..60 {
+            let time = (index + 1) * refresh;
+            stats.presented(
+                &frame(time - 4 * MS, 2 + index % 3),
+                time,
+                REFRESH,
+                Kind::Vsync,
+            );
+        }
+        stats.discarded += 1;
+        stats.close(1000 * MS);
+
+        let summary = stats.summary.clone().unwrap();
+        assert_eq!(summary.fps.round(), 60.0);
+        assert_eq!(summary.missed, 0);
+        assert_eq!(summary.discarded, 1);
+        assert_eq!(summary.render_max, Duration::from_millis(4));
+        assert_eq!(summary.latency_avg, Duration::from_millis(4));
+        assert_eq!(summary.flags, Kind::Vsync);
+
+        // The next period starts over.
+        stats.close(2000 * MS);
+        let summary = stats.summary.unwrap();
+        assert_eq!((summary.fps, summary.discarded), (0.0, 0));
+    }
+
+    #[test]
+    fn an_idle_period_is_still_summarized() {
+        let mut stats = started();
+        assert!(stats.summary.is_none());
+        stats.close(1000 * MS);
+        let summary = stats.summary.unwrap();
+        assert_eq!(summary.fps, 0.0);
+        assert_eq!(summary.render_avg, Duration::ZERO);
+    }
+
+    #[test]
+    fn counts_refreshes_skipped_by_frames_committed_in_time() {
+        let mut stats = started();
+        let refresh = REFRESH as u64;
+        stats.presented(&frame(0, 1), refresh, REFRESH, Kind::Vsync);
+        // Committed before the next refresh but shown two refreshes late.
+        stats.presented(&frame(refresh + MS, 1), 4 * refresh, REFRESH, Kind::Vsync);
+        // Committed long after, when the surface had been idle.
+        stats.presented(&frame(10 * refresh, 1), 11 * refresh, REFRESH, Kind::Vsync);
+        stats.close(1000 * MS);
+        assert_eq!(stats.summary.unwrap().missed, 2);
+    }
+}
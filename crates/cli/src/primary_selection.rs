@@ -0,0 +1,253 @@
+use std::{os::fd::AsRawFd, sync::Mutex};
+
+use smithay_client_toolkit::reexports::{
+    calloop::LoopHandle,
+    client::{
+        event_created_child, globals::GlobalList, protocol::wl_seat, Connection, Dispatch, Proxy,
+        QueueHandle,
+    },
+    protocols::wp::primary_selection::zv1::client::{
+        zwp_primary_selection_device_manager_v1::{self, ZwpPrimarySelectionDeviceManagerV1},
+        zwp_primary_selection_device_v1::{self, ZwpPrimarySelectionDeviceV1},
+        zwp_primary_selection_offer_v1::{self, ZwpPrimarySelectionOfferV1},
+        zwp_primary_selection_source_v1::{self, ZwpPrimarySelectionSourceV1},
+    },
+};
+use tracing::{info, warn};
+
+use crate::clipboard::{preferred_text_mime, read_pipe, write_pipe, TEXT_MIME_TYPES};
+
+/// Mime types advertised by a primary selection offer.
+#[derive(Debug, Default)]
+pub(crate) struct PrimaryOfferData {
+    mime_types: Mutex<Vec<String>>,
+}
+
+/// The middle-click selection through `zwp_primary_selection_device_manager_v1`.
+///
+/// Mirrors [`crate::clipboard::Clipboard`], sharing its mime types and event loop pipe handling.
+pub(crate) struct PrimarySelection {
+    pub(crate) manager: Option<ZwpPrimarySelectionDeviceManagerV1>,
+    pub(crate) device: Option<ZwpPrimarySelectionDeviceV1>,
+    pub(crate) source: Option<ZwpPrimarySelectionSourceV1>,
+    pub(crate) offer: Option<ZwpPrimarySelectionOfferV1>,
+    pub(crate) contents: String,
+}
+
+/// Access to the primary selection state from protocol events.
+pub(crate) trait PrimarySelectionHandler: Sized {
+    fn primary_selection(&mut self) -> &mut PrimarySelection;
+
+    fn loop_handle(&self) -> &LoopHandle<'static, Self>;
+}
+
+impl PrimarySelection {
+    pub fn new<D>(globals: &GlobalList, qh: &QueueHandle<D>) -> Self
+    where
+        D: Dispatch<ZwpPrimarySelectionDeviceManagerV1, ()> + 'static,
+    {
+        let manager = globals
+            .bind(qh, 1..=1, ())
+            .map_err(|error| {
+                warn!("zwp_primary_selection_device_manager_v1 not available: {error}")
+            })
+            .ok();
+
+        PrimarySelection {
+            manager,
+            device: None,
+            source: None,
+            offer: None,
+            contents: String::new(),
+        }
+    }
+
+    /// Create the selection device for a seat, if one does not exist yet.
+    pub fn add_seat<D>(&mut self, qh: &QueueHandle<D>, seat: &wl_seat::WlSeat)
+    where
+        D: Dispatch<ZwpPrimarySelectionDeviceV1, ()> + 'static,
+    {
+        if let (Some(manager), None) = (&self.manager, &self.device) {
+            self.device = Some(manager.get_device(seat, qh, ()));
+        }
+    }
+
+    pub fn remove_seat(&mut self) {
+        if let Some(device) = self.device.take() {
+            device.destroy();
+        }
+        if let Some(offer) = self.offer.take() {
+            offer.destroy();
+        }
+    }
+
+    /// Make `text` the primary selection, in response to the input event with `serial`.
+    pub fn set<D>(&mut self, qh: &QueueHandle<D>, text: &str, serial: u32)
+    where
+        D: Dispatch<ZwpPrimarySelectionSourceV1, ()> + 'static,
+    {
+        let (Some(manager), Some(device)) = (&self.manager, &self.device) else {
+            return;
+        };
+        if self.source.is_some() && self.contents == text {
+            return;
+        }
+
+        let source = manager.create_source(qh, ());
+        for mime in TEXT_MIME_TYPES {
+            source.offer(mime.to_string());
+        }
+        device.set_selection(Some(&source), serial);
+
+        if let Some(previous) = self.source.replace(source) {
+            previous.destroy();
+        }
+        self.contents = text.to_string();
+    }
+
+    /// Request the primary selection as text; `on_text` runs once the whole transfer arrived.
+    pub fn paste<D, F>(&self, loop_handle: &LoopHandle<'static, D>, on_text: F)
+    where
+        F: FnOnce(&mut D, String) + 'static,
+    {
+        let Some(offer) = &self.offer else {
+            info!("Nothing to paste from the primary selection");
+            return;
+        };
+        let mime_types = offer
+            .data::<PrimaryOfferData>()
+            .map(|data| data.mime_types.lock().unwrap().clone())
+            .unwrap_or_default();
+        let Some(mime_type) = preferred_text_mime(&mime_types) else {
+            info!("Primary selection has no text representation: {mime_types:?}");
+            return;
+        };
+
+        match std::io::pipe() {
+            Ok((reader, writer)) => {
+                offer.receive(mime_type.to_string(), writer.as_raw_fd());
+                // The writing end must be closed here so the read sees EOF.
+                drop(writer);
+                read_pipe(loop_handle, reader, on_text);
+            }
+            Err(error) => warn!("Failed to create pipe: {error}"),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpPrimarySelectionDeviceManagerV1, (), D> for PrimarySelection
+where
+    D: Dispatch<ZwpPrimarySelectionDeviceManagerV1, ()>,
+{
+    fn event(
+        _: &mut D,
+        _: &ZwpPrimarySelectionDeviceManagerV1,
+        _: zwp_primary_selection_device_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        unreachable!("zwp_primary_selection_device_manager_v1 has no events")
+    }
+}
+
+impl<D> Dispatch<ZwpPrimarySelectionDeviceV1, (), D> for PrimarySelection
+where
+    D: Dispatch<ZwpPrimarySelectionDeviceV1, ()>
+        + Dispatch<ZwpPrimarySelectionOfferV1, PrimaryOfferData>
+        + PrimarySelectionHandler
+        + 'static,
+{
+    event_created_child!(D, ZwpPrimarySelectionDeviceV1, [
+        zwp_primary_selection_device_v1::EVT_DATA_OFFER_OPCODE => (ZwpPrimarySelectionOfferV1, PrimaryOfferData::default())
+    ]);
+
+    fn event(
+        state: &mut D,
+        _: &ZwpPrimarySelectionDeviceV1,
+        event: zwp_primary_selection_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        match event {
+            zwp_primary_selection_device_v1::Event::DataOffer { .. } => {}
+            zwp_primary_selection_device_v1::Event::Selection { id } => {
+                let primary = state.primary_selection();
+                if let Some(previous) = std::mem::replace(&mut primary.offer, id) {
+                    previous.destroy();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpPrimarySelectionOfferV1, PrimaryOfferData, D> for PrimarySelection
+where
+    D: Dispatch<ZwpPrimarySelectionOfferV1, PrimaryOfferData>,
+{
+    fn event(
+        _: &mut D,
+        _: &ZwpPrimarySelectionOfferV1,
+        event: zwp_primary_selection_offer_v1::Event,
+        data: &PrimaryOfferData,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        if let zwp_primary_selection_offer_v1::Event::Offer { mime_type } = event {
+            data.mime_types.lock().unwrap().push(mime_type);
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpPrimarySelectionSourceV1, (), D> for PrimarySelection
+where
+    D: Dispatch<ZwpPrimarySelectionSourceV1, ()> + PrimarySelectionHandler,
+{
+    fn event(
+        state: &mut D,
+        source: &ZwpPrimarySelectionSourceV1,
+        event: zwp_primary_selection_source_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        match event {
+            zwp_primary_selection_source_v1::Event::Send { mime_type, fd } => {
+                let contents = state.primary_selection().contents.clone().into_bytes();
+                if TEXT_MIME_TYPES.contains(&mime_type.as_str()) {
+                    write_pipe(state.loop_handle(), fd, contents);
+                }
+            }
+            zwp_primary_selection_source_v1::Event::Cancelled => {
+                let primary = state.primary_selection();
+                if primary.source.as_ref() == Some(source) {
+                    primary.source = None;
+                    primary.contents.clear();
+                }
+                source.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+macro_rules! delegate_primary_selection {
+    ($ty: ty) => {
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::primary_selection::zv1::client::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1: ()
+        ] => $crate::primary_selection::PrimarySelection);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::primary_selection::zv1::client::zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1: ()
+        ] => $crate::primary_selection::PrimarySelection);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::primary_selection::zv1::client::zwp_primary_selection_offer_v1::ZwpPrimarySelectionOfferV1: $crate::primary_selection::PrimaryOfferData
+        ] => $crate::primary_selection::PrimarySelection);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($ty: [
+            smithay_client_toolkit::reexports::protocols::wp::primary_selection::zv1::client::zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1: ()
+        ] => $crate::primary_selection::PrimarySelection);
+    };
+}
+
+pub(crate) use delegate_primary_selection;
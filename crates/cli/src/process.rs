@@ -0,0 +1,19 @@
+use std::{
+    io,
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Run `command` with `sh -c` in its own process group, so it outlives this process.
+pub(crate) fn spawn(command: &str) -> io::Result<()> {
+    spawn_command(Command::new("sh").arg("-c").arg(command))
+}
+
+/// Start `command` without input in its own process group, so it outlives this process.
+pub(crate) fn spawn_command(command: &mut Command) -> io::Result<()> {
+    let mut child = command.stdin(Stdio::null()).process_group(0).spawn()?;
+    // Reap it whenever it exits, rather than leaving a zombie until this process does.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
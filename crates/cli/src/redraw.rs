@@ -0,0 +1,48 @@
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_callback::WlCallback, wl_surface::WlSurface},
+    Dispatch, QueueHandle,
+};
+
+/// Decides when a surface draws: only after something changed or while an animation runs, and
+/// at most once per frame callback, so any number of changes in between make a single frame.
+///
+/// With nothing to draw no frame callback is requested, and the surface stays idle until the
+/// next change.
+#[derive(Debug, Default)]
+pub(crate) struct RedrawScheduler {
+    /// Something changed since the last frame was drawn.
+    dirty: bool,
+    /// A frame callback was requested and has not arrived yet.
+    pending: bool,
+}
+
+impl RedrawScheduler {
+    /// The surface's contents changed, so draw them at the next frame callback, asking for one
+    /// unless it is already coming.
+    pub fn invalidate<D>(&mut self, surface: &WlSurface, qh: &QueueHandle<D>)
+    where
+        D: Dispatch<WlCallback, WlSurface> + 'static,
+    {
+        self.dirty = true;
+        if !self.pending {
+            self.request(surface, qh);
+            surface.commit();
+        }
+    }
+
+    /// A frame callback arrived, returns whether to draw now.
+    pub fn frame(&mut self, animating: bool) -> bool {
+        self.pending = false;
+        std::mem::take(&mut self.dirty) || animating
+    }
+
+    /// Ask for a frame callback with the next commit, which is usually the frame being drawn.
+    /// Changes until it arrives wait for it rather than drawing faster than the display.
+    pub fn request<D>(&mut self, surface: &WlSurface, qh: &QueueHandle<D>)
+    where
+        D: Dispatch<WlCallback, WlSurface> + 'static,
+    {
+        surface.frame(qh, surface.clone());
+        self.pending = true;
+    }
+}
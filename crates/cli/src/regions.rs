@@ -0,0 +1,292 @@
+use smithay_client_toolkit::seat::pointer::PointerEventKind;
+
+/// Longest time between two clicks that still makes them a double click, in milliseconds.
+const DOUBLE_CLICK_MS: u32 = 400;
+
+/// How a region should look given what the pointer is doing to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RegionState {
+    Normal,
+    Hovered,
+    /// Pressed with the pointer still over it, so releasing would click it.
+    Pressed,
+}
+
+/// What happened to a region, found from a pointer event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RegionEvent {
+    Enter(String),
+    Leave(String),
+    /// A button was pressed and released over the same region.
+    Click {
+        key: String,
+        button: u32,
+        /// 2 for a double click, and so on.
+        count: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LastClick {
+    key: String,
+    button: u32,
+    time: u32,
+    count: u32,
+}
+
+/// Tracks which keyed region is hovered and pressed, turning pointer events into clicks.
+///
+/// Regions are named by the key the caller found under the pointer, usually with
+/// [`Widget::hit`](crate::widget::Widget::hit).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PointerRegions {
+    hovered: Option<String>,
+    /// The region and button of a press not released yet.
+    pressed: Option<(String, u32)>,
+    last_click: Option<LastClick>,
+}
+
+impl PointerRegions {
+    /// Update the state from one event, with `hit` being the region under its position.
+    pub fn handle(&mut self, kind: &PointerEventKind, hit: Option<&str>) -> Vec<RegionEvent> {
+        let mut events = Vec::new();
+        let hit = match kind {
+            PointerEventKind::Leave { .. } => None,
+            _ => hit,
+        };
+
+        if self.hovered.as_deref() != hit {
+            if let Some(key) = self.hovered.take() {
+                events.push(RegionEvent::Leave(key));
+            }
+            if let Some(key) = hit {
+                self.hovered = Some(key.to_string());
+                events.push(RegionEvent::Enter(key.to_string()));
+            }
+        }
+
+        match *kind {
+            PointerEventKind::Press { button, .. } => {
+                self.pressed = hit.map(|key| (key.to_string(), button));
+            }
+            PointerEventKind::Release { button, time, .. } => {
+                let pressed = self.pressed.take_if(|(_, pressed)| *pressed == button);
+                if let Some((key, _)) = pressed.filter(|(key, _)| Some(key.as_str()) == hit) {
+                    let count = match &self.last_click {
+                        Some(last)
+                            if last.key == key
+                                && last.button == button
+                                && time.wrapping_sub(last.time) <= DOUBLE_CLICK_MS =>
+                        {
+                            last.count + 1
+                        }
+                        _ => 1,
+                    };
+                    self.last_click = Some(LastClick {
+                        key: key.clone(),
+                        button,
+                        time,
+                        count,
+                    });
+                    events.push(RegionEvent::Click { key, button, count });
+                }
+            }
+            PointerEventKind::Leave { .. } => self.pressed = None,
+            _ => {}
+        }
+        events
+    }
+
+    pub fn state(&self, key: &str) -> RegionState {
+        if self.hovered.as_deref() != Some(key) {
+            RegionState::Normal
+        } else if self
+            .pressed
+            .as_ref()
+            .is_some_and(|(pressed, _)| pressed == key)
+        {
+            RegionState::Pressed
+        } else {
+            RegionState::Hovered
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: u32 = 0x110;
+    const RIGHT: u32 = 0x111;
+
+    fn enter() -> PointerEventKind {
+        PointerEventKind::Enter { serial: 0 }
+    }
+
+    fn leave() -> PointerEventKind {
+        PointerEventKind::Leave { serial: 0 }
+    }
+
+    fn motion() -> PointerEventKind {
+        PointerEventKind::Motion { time: 0 }
+    }
+
+    fn press(button: u32, time: u32) -> PointerEventKind {
+        PointerEventKind::Press {
+            time,
+            button,
+            serial: 0,
+        }
+    }
+
+    fn release(button: u32, time: u32) -> PointerEventKind {
+        PointerEventKind::Release {
+            time,
+            button,
+            serial: 0,
+        }
+    }
+
+    fn click(key: &str, button: u32, count: u32) -> RegionEvent {
+        RegionEvent::Click {
+            key: key.to_string(),
+            button,
+            count,
+        }
+    }
+
+    /// Press and release `button` over `key` at `time`, returning what the release did.
+    fn click_at(
+        regions: &mut PointerRegions,
+        key: &str,
+        button: u32,
+        time: u32,
+    ) -> Vec<RegionEvent> {
+        regions.handle(&press(button, time), Some(key));
+        regions.handle(&release(button, time + 50), Some(key))
+    }
+
+    #[test]
+    fn hovering_enters_and_leaves_regions() {
+        let mut regions = PointerRegions::default();
+        assert_eq!(
+            regions.handle(&enter(), Some("clock")),
+            [RegionEvent::Enter("clock".into())]
+        );
+        assert_eq!(regions.state("clock"), RegionState::Hovered);
+        assert_eq!(regions.handle(&motion(), Some("clock")), []);
+        assert_eq!(
+            regions.handle(&motion(), Some("text")),
+            [
+                RegionEvent::Leave("clock".into()),
+                RegionEvent::Enter("text".into())
+            ]
+        );
+        assert_eq!(
+            regions.handle(&motion(), None),
+            [RegionEvent::Leave("text".into())]
+        );
+        assert_eq!(regions.state("text"), RegionState::Normal);
+        regions.handle(&motion(), Some("text"));
+        // Leaving the surface leaves the region, whatever the hit says.
+        assert_eq!(
+            regions.handle(&leave(), Some("text")),
+            [RegionEvent::Leave("text".into())]
+        );
+    }
+
+    #[test]
+    fn clicks_need_press_and_release_over_the_same_region() {
+        let mut regions = PointerRegions::default();
+        regions.handle(&enter(), Some("clock"));
+        assert_eq!(regions.handle(&press(LEFT, 0), Some("clock")), []);
+        assert_eq!(regions.state("clock"), RegionState::Pressed);
+        assert_eq!(
+            regions.handle(&release(LEFT, 10), Some("clock")),
+            [click("clock", LEFT, 1)]
+        );
+        assert_eq!(regions.state("clock"), RegionState::Hovered);
+
+        // Pressed in one region, released in another.
+        regions.handle(&press(LEFT, 1000), Some("clock"));
+        assert_eq!(
+            regions.handle(&release(LEFT, 1010), Some("text")),
+            [
+                RegionEvent::Leave("clock".into()),
+                RegionEvent::Enter("text".into())
+            ]
+        );
+        // Dragged out and back in before releasing still clicks.
+        regions.handle(&press(LEFT, 2000), Some("text"));
+        regions.handle(&motion(), None);
+        assert_eq!(regions.state("text"), RegionState::Normal);
+        assert_eq!(
+            regions.handle(&release(LEFT, 2010), Some("text")),
+            [RegionEvent::Enter("text".into()), click("text", LEFT, 1)]
+        );
+        // Pressed over nothing.
+        regions.handle(&press(LEFT, 3000), None);
+        assert_eq!(regions.handle(&release(LEFT, 3010), None), []);
+    }
+
+    #[test]
+    fn release_must_match_the_pressed_button() {
+        let mut regions = PointerRegions::default();
+        regions.handle(&press(LEFT, 0), Some("clock"));
+        assert_eq!(regions.handle(&release(RIGHT, 10), Some("clock")), []);
+        assert_eq!(regions.state("clock"), RegionState::Pressed);
+        assert_eq!(
+            regions.handle(&release(LEFT, 20), Some("clock")),
+            [click("clock", LEFT, 1)]
+        );
+    }
+
+    #[test]
+    fn quick_clicks_count_up() {
+        let mut regions = PointerRegions::default();
+        assert_eq!(
+            click_at(&mut regions, "clock", LEFT, 0),
+            [click("clock", LEFT, 1)]
+        );
+        assert_eq!(
+            click_at(&mut regions, "clock", LEFT, 300),
+            [click("clock", LEFT, 2)]
+        );
+        assert_eq!(
+            click_at(&mut regions, "clock", LEFT, 600),
+            [click("clock", LEFT, 3)]
+        );
+        // Too slow.
+        assert_eq!(
+            click_at(&mut regions, "clock", LEFT, 1500),
+            [click("clock", LEFT, 1)]
+        );
+        // Another button or region starts over.
+        assert_eq!(
+            click_at(&mut regions, "clock", RIGHT, 1600),
+            [click("clock", RIGHT, 1)]
+        );
+        let events = click_at(&mut regions, "text", RIGHT, 1700);
+        assert_eq!(events.last(), Some(&click("text", RIGHT, 1)));
+    }
+
+    #[test]
+    fn double_clicks_survive_the_timestamp_wrapping() {
+        let mut regions = PointerRegions::default();
+        click_at(&mut regions, "clock", LEFT, u32::MAX - 150);
+        assert_eq!(
+            click_at(&mut regions, "clock", LEFT, 50),
+            [click("clock", LEFT, 2)]
+        );
+    }
+
+    #[test]
+    fn leaving_drops_the_press() {
+        let mut regions = PointerRegions::default();
+        regions.handle(&press(LEFT, 0), Some("clock"));
+        regions.handle(&leave(), None);
+        regions.handle(&enter(), Some("clock"));
+        assert_eq!(regions.state("clock"), RegionState::Hovered);
+        assert_eq!(regions.handle(&release(LEFT, 10), Some("clock")), []);
+    }
+}
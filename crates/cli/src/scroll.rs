@@ -0,0 +1,257 @@
+use smithay_client_toolkit::{
+    reexports::client::protocol::wl_pointer::AxisSource,
+    seat::pointer::{AxisScroll, PointerEvent, PointerEventKind},
+};
+
+/// One wheel detent in the high resolution wheel unit of `wl_pointer.axis_value120`.
+const DETENT: i32 = 120;
+/// Scroll distance of a wheel detent reported without a discrete step, as libinput does.
+const WHEEL_DISTANCE: f64 = 15.0;
+/// Touchpad distance counted as one step for step-wise actions.
+const FINGER_STEP: f64 = 20.0;
+
+/// The scrolling of one pointer frame, positive down and right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Scroll {
+    /// Where the pointer was, in surface coordinates.
+    pub(crate) position: (f64, f64),
+    /// Distance in pixels, for smooth scrolling.
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    /// Whole steps, for actions like changing the volume.
+    pub(crate) steps_x: i32,
+    pub(crate) steps_y: i32,
+    pub(crate) source: Option<AxisSource>,
+    /// The fingers left the touchpad, so any kinetic scrolling should end.
+    pub(crate) stop: bool,
+}
+
+/// Turns axis events into one [`Scroll`] per frame, carrying partial steps over between them.
+#[derive(Debug, Default)]
+pub(crate) struct ScrollState {
+    /// Progress towards the next step on each axis, in 120ths of a step.
+    partial: (i32, i32),
+}
+
+impl ScrollState {
+    /// Everything the axis events among `events` scrolled, or nothing if there were none.
+    /// `natural` inverts both axes, so content follows the fingers.
+    ///
+    /// The seat is bound below version 8, so high resolution wheels arrive as fractional
+    /// distances rather than `axis_value120`, and are converted to the same unit.
+    pub fn frame<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a PointerEvent>,
+        natural: bool,
+    ) -> Option<Scroll> {
+        let mut scroll: Option<Scroll> = None;
+        let (mut horizontal, mut vertical) = <(AxisScroll, AxisScroll)>::default();
+        for event in events {
+            let PointerEventKind::Axis {
+                horizontal: h,
+                vertical: v,
+                source,
+                ..
+            } = &event.kind
+            else {
+                continue;
+            };
+            add(&mut horizontal, h);
+            add(&mut vertical, v);
+            let scroll = scroll.get_or_insert(Scroll {
+                position: event.position,
+                x: 0.0,
+                y: 0.0,
+                steps_x: 0,
+                steps_y: 0,
+                source: None,
+                stop: false,
+            });
+            scroll.position = event.position;
+            scroll.source = scroll.source.or(*source);
+        }
+        let mut scroll = scroll?;
+
+        let sign = if natural { -1.0 } else { 1.0 };
+        scroll.x = horizontal.absolute * sign;
+        scroll.y = vertical.absolute * sign;
+        scroll.stop = horizontal.stop || vertical.stop;
+
+        let wheel = matches!(
+            scroll.source,
+            Some(AxisSource::Wheel | AxisSource::WheelTilt) | None
+        );
+        let value120 = |axis: &AxisScroll| match (axis.discrete, wheel) {
+            (0, true) => (axis.absolute / WHEEL_DISTANCE * DETENT as f64).round() as i32,
+            (0, false) => (axis.absolute / FINGER_STEP * DETENT as f64).round() as i32,
+            (discrete, _) => discrete * DETENT,
+        };
+        let (partial_x, partial_y) = &mut self.partial;
+        scroll.steps_x = step(partial_x, value120(&horizontal) * sign as i32);
+        scroll.steps_y = step(partial_y, value120(&vertical) * sign as i32);
+
+        // A new gesture starts from nothing, not halfway to a step.
+        if scroll.stop {
+            self.partial = (0, 0);
+        }
+        Some(scroll)
+    }
+}
+
+fn add(total: &mut AxisScroll, axis: &AxisScroll) {
+    total.absolute += axis.absolute;
+    total.discrete += axis.discrete;
+    total.stop |= axis.stop;
+}
+
+/// Add `value120` to `partial` and take the whole steps out of it.
+fn step(partial: &mut i32, value120: i32) -> i32 {
+    *partial += value120;
+    let steps = *partial / DETENT;
+    *partial -= steps * DETENT;
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use smithay_client_toolkit::reexports::client::{
+        protocol::wl_surface::WlSurface, Connection, Proxy,
+    };
+
+    use super::*;
+
+    /// Frames of pointer events for a surface that never needs a compositor.
+    struct Frames {
+        surface: WlSurface,
+        state: ScrollState,
+    }
+
+    impl Frames {
+        fn new() -> Self {
+            let (socket, _) = UnixStream::pair().unwrap();
+            let connection = Connection::from_socket(socket).unwrap();
+            Frames {
+                surface: WlSurface::inert(connection.backend().downgrade()),
+                state: ScrollState::default(),
+            }
+        }
+
+        fn event(&self, kind: PointerEventKind) -> PointerEvent {
+            PointerEvent {
+                surface: self.surface.clone(),
+                position: (1.0, 2.0),
+                kind,
+            }
+        }
+
+        /// One frame of vertical axis events from `source`, each `(absolute, discrete)`.
+        fn vertical(
+            &mut self,
+            source: AxisSource,
+            axes: &[(f64, i32)],
+            stop: bool,
+            natural: bool,
+        ) -> Option<Scroll> {
+            let events: Vec<_> = axes
+                .iter()
+                .map(|&(absolute, discrete)| {
+                    self.event(PointerEventKind::Axis {
+                        time: 0,
+                        horizontal: AxisScroll::default(),
+                        vertical: AxisScroll {
+                            absolute,
+                            discrete,
+                            stop,
+                        },
+                        source: Some(source),
+                    })
+                })
+                .collect();
+            self.state.frame(&events, natural)
+        }
+
+        fn steps(&mut self, source: AxisSource, absolute: f64) -> i32 {
+            self.vertical(source, &[(absolute, 0)], false, false)
+                .unwrap()
+                .steps_y
+        }
+    }
+
+    #[test]
+    fn frames_without_axis_events_scroll_nothing() {
+        let mut frames = Frames::new();
+        let motion = frames.event(PointerEventKind::Motion { time: 0 });
+        assert_eq!(frames.state.frame([&motion], false), None);
+        assert_eq!(frames.vertical(AxisSource::Wheel, &[], false, false), None);
+    }
+
+    #[test]
+    fn discrete_wheel_steps() {
+        let mut frames = Frames::new();
+        let scroll = frames
+            .vertical(AxisSource::Wheel, &[(15.0, 1), (15.0, 1)], false, false)
+            .unwrap();
+        assert_eq!((scroll.y, scroll.steps_y), (30.0, 2));
+        assert_eq!((scroll.x, scroll.steps_x), (0.0, 0));
+        assert_eq!(scroll.position, (1.0, 2.0));
+        assert_eq!(scroll.source, Some(AxisSource::Wheel));
+
+        // Natural scrolling inverts the distance and the steps.
+        let scroll = frames
+            .vertical(AxisSource::Wheel, &[(15.0, 1)], false, true)
+            .unwrap();
+        assert_eq!((scroll.y, scroll.steps_y), (-15.0, -1));
+    }
+
+    #[test]
+    fn partial_wheel_steps_add_up() {
+        let mut frames = Frames::new();
+        // Half detents of a high resolution wheel, 60 of 120 each.
+        assert_eq!(frames.steps(AxisSource::Wheel, 7.5), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, 7.5), 1);
+        assert_eq!(frames.steps(AxisSource::Wheel, 5.0), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, 5.0), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, 5.0), 1);
+        // Three half detents in one frame: a step and half of the next.
+        assert_eq!(
+            frames
+                .vertical(AxisSource::Wheel, &[(7.5, 0); 3], false, false)
+                .unwrap()
+                .steps_y,
+            1
+        );
+        assert_eq!(frames.steps(AxisSource::Wheel, 7.5), 1);
+    }
+
+    #[test]
+    fn negative_partials_step_back_and_cancel_out() {
+        let mut frames = Frames::new();
+        assert_eq!(frames.steps(AxisSource::Wheel, -7.5), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, -7.5), -1);
+        // Half a step each way is no step at all.
+        assert_eq!(frames.steps(AxisSource::Wheel, 7.5), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, -7.5), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, -7.5), 0);
+        assert_eq!(frames.steps(AxisSource::Wheel, -7.5), -1);
+    }
+
+    #[test]
+    fn touchpad_steps_restart_after_stop() {
+        let mut frames = Frames::new();
+        // Half a step of finger distance each.
+        assert_eq!(frames.steps(AxisSource::Finger, 10.0), 0);
+        assert_eq!(frames.steps(AxisSource::Finger, 10.0), 1);
+        assert_eq!(frames.steps(AxisSource::Finger, 10.0), 0);
+
+        // The fingers lift halfway to the next step.
+        let scroll = frames
+            .vertical(AxisSource::Finger, &[(0.0, 0)], true, false)
+            .unwrap();
+        assert!(scroll.stop);
+        assert_eq!(scroll.steps_y, 0);
+        assert_eq!(frames.steps(AxisSource::Finger, 10.0), 0);
+        assert_eq!(frames.steps(AxisSource::Finger, 10.0), 1);
+    }
+}
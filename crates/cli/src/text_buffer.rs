@@ -0,0 +1,302 @@
+use std::ops::Range;
+
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Modifiers};
+use xkbcommon::xkb::keysyms;
+
+/// What a key press asked of the surface owning a [`TextBuffer`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum EditAction {
+    /// The key was not an editing key.
+    Ignored,
+    /// The text, cursor or selection changed.
+    Edited,
+    Copy,
+    Paste,
+}
+
+/// A single line of editable text with a cursor and an optional selection.
+///
+/// Offsets are byte indices that always lie on `char` boundaries.
+#[derive(Debug, Default)]
+pub(crate) struct TextBuffer {
+    text: String,
+    cursor: usize,
+    anchor: Option<usize>,
+}
+
+impl TextBuffer {
+    /// Apply an editing key press.
+    pub fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> EditAction {
+        let extend = modifiers.shift;
+        match event.keysym {
+            keysyms::KEY_c | keysyms::KEY_C if modifiers.ctrl => return EditAction::Copy,
+            keysyms::KEY_v | keysyms::KEY_V if modifiers.ctrl => return EditAction::Paste,
+            keysyms::KEY_a | keysyms::KEY_A if modifiers.ctrl => self.select_all(),
+            keysyms::KEY_BackSpace => self.delete_backward(),
+            keysyms::KEY_Delete => self.delete_forward(),
+            keysyms::KEY_Left => self.move_left(extend),
+            keysyms::KEY_Right => self.move_right(extend),
+            keysyms::KEY_Home => self.move_home(extend),
+            keysyms::KEY_End => self.move_end(extend),
+            _ => match &event.utf8 {
+                Some(text) if !modifiers.ctrl && !text.chars().any(char::is_control) => {
+                    self.insert(text)
+                }
+                _ => return EditAction::Ignored,
+            },
+        }
+        EditAction::Edited
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replace the whole text, leaving the cursor at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    /// The selected byte range, if any text is selected.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        let range = anchor.min(self.cursor)..anchor.max(self.cursor);
+        (!range.is_empty()).then_some(range)
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|range| &self.text[range])
+    }
+
+    /// Insert text at the cursor, replacing the selection if there is one.
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    pub fn delete_backward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if let Some(previous) = self.previous_boundary() {
+            self.text.replace_range(previous..self.cursor, "");
+            self.cursor = previous;
+        }
+    }
+
+    pub fn delete_forward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if let Some(next) = self.next_boundary() {
+            self.text.replace_range(self.cursor..next, "");
+        }
+    }
+
+    pub fn move_left(&mut self, extend: bool) {
+        let target = self.previous_boundary().unwrap_or(self.cursor);
+        self.move_to(target, extend);
+    }
+
+    pub fn move_right(&mut self, extend: bool) {
+        let target = self.next_boundary().unwrap_or(self.cursor);
+        self.move_to(target, extend);
+    }
+
+    pub fn move_home(&mut self, extend: bool) {
+        self.move_to(0, extend);
+    }
+
+    pub fn move_end(&mut self, extend: bool) {
+        self.move_to(self.text.len(), extend);
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+
+    fn move_to(&mut self, target: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = target;
+    }
+
+    /// Remove the selected text, returning whether anything was removed.
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        match selection {
+            Some(range) => {
+                self.cursor = range.start;
+                self.text.replace_range(range, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn previous_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|c| self.cursor + c.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(buffer: &mut TextBuffer, keysym: u32, utf8: Option<&str>, ctrl: bool) -> EditAction {
+        key_with(
+            buffer,
+            keysym,
+            utf8,
+            Modifiers {
+                ctrl,
+                ..Modifiers::default()
+            },
+        )
+    }
+
+    fn key_with(
+        buffer: &mut TextBuffer,
+        keysym: u32,
+        utf8: Option<&str>,
+        modifiers: Modifiers,
+    ) -> EditAction {
+        let event = KeyEvent {
+            time: 0,
+            raw_code: 0,
+            keysym,
+            utf8: utf8.map(str::to_string),
+        };
+        buffer.handle_key(&event, &modifiers)
+    }
+
+    #[test]
+    fn typing_inserts_at_the_cursor() {
+        let mut buffer = TextBuffer::default();
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_h, Some("h"), false),
+            EditAction::Edited
+        );
+        key(&mut buffer, keysyms::KEY_i, Some("i"), false);
+        buffer.move_home(false);
+        key(&mut buffer, keysyms::KEY_eacute, Some("é"), false);
+        assert_eq!(buffer.text(), "éhi");
+        assert_eq!(buffer.cursor(), "é".len());
+
+        // Control characters and ctrl chords are not text.
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_Escape, Some("\u{1b}"), false),
+            EditAction::Ignored
+        );
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_x, Some("x"), true),
+            EditAction::Ignored
+        );
+        assert_eq!(buffer.text(), "éhi");
+    }
+
+    #[test]
+    fn cursor_moves_and_deletes_whole_characters() {
+        let mut buffer = TextBuffer::default();
+        buffer.set_text("aéb");
+        buffer.move_left(false);
+        buffer.move_left(false);
+        assert_eq!(buffer.cursor(), 1);
+        buffer.move_right(false);
+        assert_eq!(buffer.cursor(), 3);
+        key(&mut buffer, keysyms::KEY_BackSpace, None, false);
+        assert_eq!((buffer.text(), buffer.cursor()), ("ab", 1));
+        key(&mut buffer, keysyms::KEY_Delete, None, false);
+        assert_eq!((buffer.text(), buffer.cursor()), ("a", 1));
+
+        // Nothing to delete past either end.
+        key(&mut buffer, keysyms::KEY_Delete, None, false);
+        buffer.move_home(false);
+        key(&mut buffer, keysyms::KEY_BackSpace, None, false);
+        buffer.move_left(false);
+        assert_eq!((buffer.text(), buffer.cursor()), ("a", 0));
+    }
+
+    #[test]
+    fn shift_extends_the_selection_from_where_it_started() {
+        let mut buffer = TextBuffer::default();
+        buffer.set_text("hello world");
+        buffer.move_home(false);
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        };
+        for _ in 0..5 {
+            key_with(&mut buffer, keysyms::KEY_Right, None, shift);
+        }
+        assert_eq!(buffer.selected_text(), Some("hello"));
+        key_with(&mut buffer, keysyms::KEY_End, None, shift);
+        assert_eq!(buffer.selected_text(), Some("hello world"));
+        key_with(&mut buffer, keysyms::KEY_Home, None, shift);
+        assert_eq!(buffer.selection(), None);
+
+        // Moving without shift drops the selection.
+        buffer.select_all();
+        buffer.move_left(false);
+        assert_eq!(buffer.selection(), None);
+    }
+
+    #[test]
+    fn select_all_copy_and_paste_over_the_selection() {
+        let mut buffer = TextBuffer::default();
+        buffer.set_text("old text");
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_a, Some("\u{1}"), true),
+            EditAction::Edited
+        );
+        assert_eq!(buffer.selected_text(), Some("old text"));
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_c, Some("\u{3}"), true),
+            EditAction::Copy
+        );
+        assert_eq!(
+            key(&mut buffer, keysyms::KEY_v, Some("\u{16}"), true),
+            EditAction::Paste
+        );
+        // The surface answers a paste by inserting what it received.
+        buffer.insert("new");
+        assert_eq!((buffer.text(), buffer.cursor()), ("new", 3));
+        assert_eq!(buffer.selection(), None);
+    }
+
+    #[test]
+    fn deleting_removes_the_selection_only() {
+        let mut buffer = TextBuffer::default();
+        buffer.set_text("abcdef");
+        buffer.move_left(false);
+        buffer.move_left(true);
+        buffer.move_left(true);
+        assert_eq!(buffer.selected_text(), Some("de"));
+        key(&mut buffer, keysyms::KEY_BackSpace, None, false);
+        assert_eq!((buffer.text(), buffer.cursor()), ("abcf", 3));
+        key(&mut buffer, keysyms::KEY_BackSpace, None, false);
+        assert_eq!(buffer.text(), "abf");
+    }
+}
@@ -0,0 +1,259 @@
+use raster::Gradient;
+use serde::Deserialize;
+
+use crate::commands::parse_color;
+use crate::draw::Canvas;
+
+/// A straight ARGB color, written as `RRGGBB` or `RRGGBBAA` with an optional leading `#`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub(crate) struct Color(pub(crate) u32);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_color(&value).map(Color)
+    }
+}
+
+impl Color {
+    /// The color with its alpha scaled by `opacity`, still straight.
+    pub fn with_opacity(self, opacity: f32) -> u32 {
+        let alpha = ((self.0 >> 24) as f32 * opacity.clamp(0.0, 1.0)).round() as u32;
+        (alpha << 24) | (self.0 & 0x00FF_FFFF)
+    }
+}
+
+/// How the bar looks.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Theme {
+    /// The animated gradient is drawn when there is none.
+    pub(crate) background: Option<Color>,
+    pub(crate) foreground: Color,
+    pub(crate) accent: Color,
+    pub(crate) border_width: u32,
+    pub(crate) border_color: Color,
+    pub(crate) corner_radius: u32,
+    /// Space between the border and the content.
+    pub(crate) padding: u32,
+    /// Multiplies the alpha of everything drawn, from 0 to 1.
+    pub(crate) opacity: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: None,
+            foreground: Color(0xFF_FF_FF_FF),
+            accent: Color(0xFF_52_94_E2),
+            border_width: 0,
+            border_color: Color(0xFF_00_00_00),
+            corner_radius: 0,
+            padding: 8,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(format!(
+                "theme.opacity: expected a value from 0 to 1, got {}",
+                self.opacity
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether every pixel drawn by [`Theme::draw_frame`] is fully opaque.
+    pub fn is_opaque(&self) -> bool {
+        let opaque = |Color(color): Color| color >> 24 == 0xFF;
+        self.opacity >= 1.0
+            && self.corner_radius == 0
+            && self.background.is_none_or(opaque)
+            && (self.border_width == 0 || opaque(self.border_color))
+    }
+
+    /// Where content starts, inside the border and padding.
+    pub fn content_offset(&self) -> i32 {
+        (self.border_width + self.padding) as i32
+    }
+
+    /// Fill the whole canvas: the background inside the border, the border, and transparency
+    /// outside the rounded corners.
+    ///
+    /// Without a background color, `gradient` fills it instead.
+    pub fn draw_frame(&self, canvas: &mut Canvas, gradient: &Gradient) {
+        let bounds = canvas.bounds();
+        let border = self.border_width as i32;
+        let radius = self.corner_radius as f32;
+
+        canvas.clear(0);
+        let inner = bounds.inset(border);
+        if radius <= border as f32 && self.opacity >= 1.0 {
+            // Square inside the border, so every pixel is covered by the background itself.
+            match self.background {
+                Some(background) => canvas.fill_rect(inner, background.0),
+                None => canvas.fill_gradient(inner, gradient),
+            }
+        } else {
+            canvas.fill_rounded_rect_with(inner, radius - border as f32, |x, y| {
+                let fill = self
+                    .background
+                    .unwrap_or_else(|| Color(gradient.pixel(x as usize, y as usize)));
+                fill.with_opacity(self.opacity)
+            });
+        }
+        if border > 0 {
+            canvas.stroke_rounded_rect(
+                bounds,
+                radius,
+                border as f32,
+                self.border_color.with_opacity(self.opacity),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 12;
+
+    /// `theme`'s frame on a square canvas, as premultiplied pixels.
+    fn frame(theme: &Theme) -> Vec<u32> {
+        let mut pixels = vec![0xAA; (SIZE * SIZE * 4) as usize];
+        let mut canvas = Canvas::new(&mut pixels, SIZE, SIZE);
+        theme.draw_frame(&mut canvas, &Gradient::new(SIZE, SIZE, 0));
+        pixels
+            .chunks_exact(4)
+            .map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+            .collect()
+    }
+
+    fn at(pixels: &[u32], x: u32, y: u32) -> u32 {
+        pixels[(y * SIZE + x) as usize]
+    }
+
+    #[test]
+    fn parses_colors_and_keeps_defaults() {
+        let theme: Theme = toml::from_str(
+            r##"
+            background = "#102030"
+            border_color = "11223344"
+            border_width = 2
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.background, Some(Color(0xFF_10_20_30)));
+        assert_eq!(theme.border_color, Color(0x44_11_22_33));
+        assert_eq!(theme.border_width, 2);
+        assert_eq!(theme.foreground, Theme::default().foreground);
+
+        assert!(toml::from_str::<Theme>(r##"accent = "#12345""##).is_err());
+        assert!(toml::from_str::<Theme>(r#"accent = "nothex""#).is_err());
+        assert!(toml::from_str::<Theme>("shadow = 3").is_err());
+    }
+
+    #[test]
+    fn opacity_must_be_a_fraction() {
+        for opacity in [0.0, 0.5, 1.0] {
+            let theme = Theme {
+                opacity,
+                ..Theme::default()
+            };
+            assert_eq!(theme.validate(), Ok(()));
+        }
+        for opacity in [-0.1, 1.5, f32::NAN] {
+            let theme = Theme {
+                opacity,
+                ..Theme::default()
+            };
+            assert!(theme.validate().unwrap_err().starts_with("theme.opacity"));
+        }
+        assert_eq!(Color(0xFF_12_34_56).with_opacity(0.5), 0x80_12_34_56);
+        assert_eq!(Color(0x80_12_34_56).with_opacity(2.0), 0x80_12_34_56);
+    }
+
+    #[test]
+    fn opaque_only_without_anything_see_through() {
+        let opaque = Theme {
+            background: Some(Color(0xFF_20_20_20)),
+            ..Theme::default()
+        };
+        assert!(opaque.is_opaque());
+        assert!(Theme::default().is_opaque());
+        for theme in [
+            Theme {
+                opacity: 0.9,
+                ..opaque
+            },
+            Theme {
+                corner_radius: 4,
+                ..opaque
+            },
+            Theme {
+                background: Some(Color(0x80_20_20_20)),
+                ..opaque
+            },
+            Theme {
+                border_width: 1,
+                border_color: Color(0x80_00_00_00),
+                ..opaque
+            },
+        ] {
+            assert!(!theme.is_opaque(), "{theme:?}");
+        }
+        // A see-through border color does not matter without a border.
+        let borderless = Theme {
+            border_color: Color(0),
+            ..opaque
+        };
+        assert!(borderless.is_opaque());
+    }
+
+    #[test]
+    fn draws_border_around_background() {
+        let theme = Theme {
+            background: Some(Color(0xFF_FF_00_00)),
+            border_width: 2,
+            border_color: Color(0xFF_00_00_FF),
+            ..Theme::default()
+        };
+        let pixels = frame(&theme);
+        for (x, y) in [(0, 0), (1, 5), (SIZE - 1, SIZE - 2), (6, SIZE - 1)] {
+            assert_eq!(at(&pixels, x, y), 0xFF_00_00_FF, "{x}, {y}");
+        }
+        for (x, y) in [(2, 2), (6, 6), (SIZE - 3, SIZE - 3)] {
+            assert_eq!(at(&pixels, x, y), 0xFF_FF_00_00, "{x}, {y}");
+        }
+        assert_eq!(theme.content_offset(), 2 + 8);
+    }
+
+    #[test]
+    fn rounded_corners_are_cleared_and_opacity_premultiplied() {
+        let theme = Theme {
+            background: Some(Color(0xFF_FF_FF_FF)),
+            corner_radius: 5,
+            opacity: 0.5,
+            ..Theme::default()
+        };
+        let pixels = frame(&theme);
+        assert_eq!(at(&pixels, 0, 0), 0);
+        assert_eq!(at(&pixels, SIZE - 1, SIZE - 1), 0);
+        assert_eq!(at(&pixels, 6, 6), 0x80_80_80_80);
+    }
+
+    #[test]
+    fn gradient_fills_without_background() {
+        let gradient = Gradient::new(SIZE, SIZE, 0);
+        let pixels = frame(&Theme::default());
+        for (x, y) in [(0, 0), (5, 7), (SIZE - 1, SIZE - 1)] {
+            assert_eq!(at(&pixels, x, y), gradient.pixel(x as usize, y as usize));
+        }
+    }
+}
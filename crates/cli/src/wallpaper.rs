@@ -0,0 +1,574 @@
+use std::{path::PathBuf, time::Duration};
+
+use image::{imageops::FilterType, RgbaImage};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::calloop::{
+        signals::{Signal, Signals},
+        timer::{TimeoutAction, Timer},
+        EventLoop,
+    },
+    reexports::client::{
+        globals::{registry_queue_init, GlobalList},
+        protocol::{wl_output, wl_shm, wl_surface},
+        Connection, QueueHandle, WaylandSource,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    shm::slot::SlotPool,
+    shm::{Shm, ShmHandler},
+};
+use tracing::{info, warn};
+
+use crate::commands::{ScaleMode, WallpaperArgs};
+use crate::error::AppResult;
+
+/// A background layer surface covering one output.
+pub(crate) struct WallpaperSurface {
+    pub(crate) output: wl_output::WlOutput,
+    pub(crate) layer: LayerSurface,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) scale: i32,
+    pub(crate) configured: bool,
+}
+
+pub(crate) struct SimpleWallpaper {
+    pub(crate) registry_state: RegistryState,
+    pub(crate) output_state: OutputState,
+    pub(crate) compositor_state: CompositorState,
+    pub(crate) layer_shell: LayerShell,
+    pub(crate) shm: Shm,
+
+    pub(crate) pool: SlotPool,
+    pub(crate) surfaces: Vec<WallpaperSurface>,
+    pub(crate) images: Vec<PathBuf>,
+    pub(crate) current: usize,
+    pub(crate) image: Option<RgbaImage>,
+    pub(crate) mode: ScaleMode,
+    pub(crate) color: u32,
+}
+
+pub(crate) fn run(args: WallpaperArgs) -> AppResult<()> {
+    let connection = Connection::connect_to_env()?;
+
+    let (globals, queue) = registry_queue_init(&connection)?;
+    let qh = queue.handle();
+    let mut event_loop =
+        EventLoop::<SimpleWallpaper>::try_new().expect("Failed to create event loop");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(queue)
+        .unwrap()
+        .insert(loop_handle.clone())
+        .unwrap();
+
+    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let layer_shell = LayerShell::bind(&globals, &qh).expect("layer shell not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl_shm not available");
+    let pool = SlotPool::new(256 * 256 * 4, &shm).expect("Failed to create pool");
+
+    let mut wallpaper = SimpleWallpaper::init(&globals, &qh, compositor, layer_shell, shm, pool);
+    wallpaper.images = collect_images(&args.paths);
+    wallpaper.mode = args.mode;
+    wallpaper.color = args.color;
+    wallpaper.load_current();
+
+    // SIGHUP moves on to the next image, re-reading it from disk.
+    let signals = Signals::new(&[Signal::SIGHUP])?;
+    loop_handle
+        .insert_source(signals, |_, _, wallpaper| {
+            info!("Received SIGHUP");
+            wallpaper.next_image();
+        })
+        .map_err(|error| error.error)?;
+
+    if let Some(interval) = args.interval.map(Duration::from_secs) {
+        loop_handle
+            .insert_source(Timer::from_duration(interval), move |_, _, wallpaper| {
+                wallpaper.next_image();
+                TimeoutAction::ToDuration(interval)
+            })
+            .map_err(|error| error.error)?;
+    }
+
+    event_loop.run(None, &mut wallpaper, |_| {})?;
+
+    Ok(())
+}
+
+impl SimpleWallpaper {
+    pub fn init(
+        globals: &GlobalList,
+        qh: &QueueHandle<Self>,
+        compositor_state: CompositorState,
+        layer_shell: LayerShell,
+        shm: Shm,
+        pool: SlotPool,
+    ) -> Self {
+        SimpleWallpaper {
+            registry_state: RegistryState::new(globals),
+            output_state: OutputState::new(globals, qh),
+            compositor_state,
+            layer_shell,
+            shm,
+
+            pool,
+            surfaces: Vec::new(),
+            images: Vec::new(),
+            current: 0,
+            image: None,
+            mode: ScaleMode::Fill,
+            color: 0xFF00_0000,
+        }
+    }
+
+    /// Decode the current image, falling back to the solid color if that fails.
+    pub fn load_current(&mut self) {
+        self.image = self.images.get(self.current).and_then(|path| {
+            info!("Loading wallpaper {}", path.display());
+            image::open(path)
+                .map(|image| image.into_rgba8())
+                .map_err(|error| warn!("Failed to load {}: {error}", path.display()))
+                .ok()
+        });
+    }
+
+    pub fn next_image(&mut self) {
+        if !self.images.is_empty() {
+            self.current = (self.current + 1) % self.images.len();
+        }
+        self.load_current();
+        for index in 0..self.surfaces.len() {
+            self.draw(index);
+        }
+    }
+
+    fn create_surface(&mut self, qh: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        let surface = self.compositor_state.create_surface(qh);
+        let layer = self.layer_shell.create_layer_surface(
+            qh,
+            surface,
+            Layer::Background,
+            Some("wallpaper"),
+            Some(&output),
+        );
+
+        layer.set_anchor(Anchor::all());
+        layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+        layer.set_exclusive_zone(-1);
+        layer.set_size(0, 0);
+        layer.commit();
+
+        let scale = self
+            .output_state
+            .info(&output)
+            .map(|info| info.scale_factor)
+            .unwrap_or(1);
+
+        self.surfaces.push(WallpaperSurface {
+            output,
+            layer,
+            width: 0,
+            height: 0,
+            scale,
+            configured: false,
+        });
+    }
+
+    pub fn draw(&mut self, index: usize) {
+        let surface = &self.surfaces[index];
+        if !surface.configured {
+            return;
+        }
+
+        let width = surface.width * surface.scale as u32;
+        let height = surface.height * surface.scale as u32;
+        let stride = width as i32 * 4;
+
+        let (buffer, canvas) = self
+            .pool
+            .create_buffer(
+                width as i32,
+                height as i32,
+                stride,
+                wl_shm::Format::Argb8888,
+            )
+            .expect("create buffer");
+
+        render(
+            canvas,
+            width,
+            height,
+            self.image.as_ref(),
+            self.mode,
+            self.color,
+        );
+
+        let wl_surface = surface.layer.wl_surface();
+        wl_surface.set_buffer_scale(surface.scale);
+        wl_surface.damage_buffer(0, 0, width as i32, height as i32);
+        buffer.attach_to(wl_surface).expect("buffer attach");
+        surface.layer.commit();
+    }
+}
+
+/// Expand directories into the images they contain, in name order.
+fn collect_images(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let is_image = |path: &PathBuf| {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| {
+                ["png", "jpg", "jpeg"].contains(&extension.to_ascii_lowercase().as_str())
+            })
+            .unwrap_or(false)
+    };
+
+    let mut images = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            match std::fs::read_dir(path) {
+                Ok(entries) => {
+                    let mut found: Vec<_> = entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.is_file() && is_image(path))
+                        .collect();
+                    found.sort();
+                    images.extend(found);
+                }
+                Err(error) => warn!("Failed to read {}: {error}", path.display()),
+            }
+        } else {
+            images.push(path.clone());
+        }
+    }
+    images
+}
+
+/// Paint `image` scaled by `mode` over `color` into an ARGB8888 canvas.
+fn render(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    image: Option<&RgbaImage>,
+    mode: ScaleMode,
+    color: u32,
+) {
+    let background = color.to_be_bytes();
+    let Some(image) = image else {
+        canvas
+            .chunks_exact_mut(4)
+            .for_each(|chunk| chunk.copy_from_slice(&premultiply(background).to_le_bytes()));
+        return;
+    };
+
+    let (iw, ih) = image.dimensions();
+    let scaled;
+    let (source, offset_x, offset_y) = match mode {
+        ScaleMode::Fill | ScaleMode::Fit => {
+            let sx = width as f64 / iw as f64;
+            let sy = height as f64 / ih as f64;
+            let factor = if mode == ScaleMode::Fill {
+                sx.max(sy)
+            } else {
+                sx.min(sy)
+            };
+            let sw = ((iw as f64 * factor).round() as u32).max(1);
+            let sh = ((ih as f64 * factor).round() as u32).max(1);
+            scaled = image::imageops::resize(image, sw, sh, FilterType::Triangle);
+            let ox = (width as i64 - sw as i64) / 2;
+            let oy = (height as i64 - sh as i64) / 2;
+            (&scaled, ox, oy)
+        }
+        ScaleMode::Center => (
+            image,
+            (width as i64 - iw as i64) / 2,
+            (height as i64 - ih as i64) / 2,
+        ),
+        ScaleMode::Tile => (image, 0, 0),
+    };
+
+    let (sw, sh) = source.dimensions();
+    canvas
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(index, chunk)| {
+            let x = (index % width as usize) as i64 - offset_x;
+            let y = (index / width as usize) as i64 - offset_y;
+
+            let (x, y) = if mode == ScaleMode::Tile {
+                (x.rem_euclid(sw as i64), y.rem_euclid(sh as i64))
+            } else {
+                (x, y)
+            };
+
+            let argb = if x >= 0 && y >= 0 && x < sw as i64 && y < sh as i64 {
+                let [r, g, b, a] = source.get_pixel(x as u32, y as u32).0;
+                over([a, r, g, b], background)
+            } else {
+                background
+            };
+
+            chunk.copy_from_slice(&premultiply(argb).to_le_bytes());
+        });
+}
+
+/// Composite a straight-alpha ARGB pixel over another.
+fn over(top: [u8; 4], bottom: [u8; 4]) -> [u8; 4] {
+    let ta = top[0] as u32;
+    let ba = bottom[0] as u32 * (255 - ta) / 255;
+    let alpha = ta + ba;
+    if alpha == 0 {
+        return [0; 4];
+    }
+
+    let mut out = [alpha as u8, 0, 0, 0];
+    for channel in 1..4 {
+        out[channel] = ((top[channel] as u32 * ta + bottom[channel] as u32 * ba) / alpha) as u8;
+    }
+    out
+}
+
+/// Pack a straight-alpha ARGB pixel into a premultiplied ARGB8888 value.
+fn premultiply([a, r, g, b]: [u8; 4]) -> u32 {
+    let scale = |c: u8| (c as u32 * a as u32 + 127) / 255;
+    ((a as u32) << 24) | (scale(r) << 16) | (scale(g) << 8) | scale(b)
+}
+
+impl CompositorHandler for SimpleWallpaper {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        if let Some(index) = self
+            .surfaces
+            .iter()
+            .position(|s| s.layer.wl_surface() == surface)
+        {
+            self.surfaces[index].scale = new_factor;
+            self.draw(index);
+        }
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        // The wallpaper is only redrawn when it changes.
+    }
+}
+
+impl OutputHandler for SimpleWallpaper {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.create_surface(qh, output);
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.surfaces.retain(|surface| surface.output != output);
+    }
+}
+
+impl LayerShellHandler for SimpleWallpaper {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, layer: &LayerSurface) {
+        self.surfaces.retain(|surface| &surface.layer != layer);
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let Some(index) = self.surfaces.iter().position(|s| &s.layer == layer) else {
+            return;
+        };
+
+        let surface = &mut self.surfaces[index];
+        surface.width = configure.new_size.0.max(1);
+        surface.height = configure.new_size.1.max(1);
+        surface.configured = true;
+        self.draw(index);
+    }
+}
+
+impl ShmHandler for SimpleWallpaper {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_compositor!(SimpleWallpaper);
+delegate_output!(SimpleWallpaper);
+delegate_shm!(SimpleWallpaper);
+
+delegate_layer!(SimpleWallpaper);
+
+delegate_registry!(SimpleWallpaper);
+
+impl ProvidesRegistryState for SimpleWallpaper {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState];
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::Rgba;
+
+    use super::*;
+
+    const BACKGROUND: u32 = 0xFF_00_00_FF;
+    const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+    const GREEN: [u8; 4] = [0, 0xFF, 0, 0xFF];
+
+    /// `image` rendered into a `width` by `height` canvas, as rows of premultiplied pixels.
+    fn rendered(image: &RgbaImage, width: u32, height: u32, mode: ScaleMode) -> Vec<Vec<u32>> {
+        let mut canvas = vec![0; width as usize * height as usize * 4];
+        render(&mut canvas, width, height, Some(image), mode, BACKGROUND);
+        canvas
+            .chunks_exact(4)
+            .map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+            .collect::<Vec<_>>()
+            .chunks(width as usize)
+            .map(<[u32]>::to_vec)
+            .collect()
+    }
+
+    #[test]
+    fn fill_covers_the_output() {
+        // Scaled by 4 to 4x8, then cropped to the middle 4 rows.
+        let image = RgbaImage::from_pixel(1, 2, Rgba(RED));
+        assert_eq!(
+            rendered(&image, 4, 4, ScaleMode::Fill),
+            vec![vec![0xFF_FF_00_00; 4]; 4]
+        );
+    }
+
+    #[test]
+    fn fit_borders_the_whole_image() {
+        // Scaled by 2 to 4x2, between a background row above and below.
+        let image = RgbaImage::from_pixel(2, 1, Rgba(RED));
+        let rows = rendered(&image, 4, 4, ScaleMode::Fit);
+        assert_eq!(rows[0], [BACKGROUND; 4]);
+        assert_eq!(rows[1], [0xFF_FF_00_00; 4]);
+        assert_eq!(rows[2], [0xFF_FF_00_00; 4]);
+        assert_eq!(rows[3], [BACKGROUND; 4]);
+    }
+
+    #[test]
+    fn center_keeps_the_size() {
+        let image = RgbaImage::from_pixel(2, 2, Rgba(RED));
+        let (b, r) = (BACKGROUND, 0xFF_FF_00_00);
+        assert_eq!(
+            rendered(&image, 4, 3, ScaleMode::Center),
+            [[b, r, r, b], [b, r, r, b], [b, b, b, b]]
+        );
+        // Larger than the output: only the middle shows.
+        let mut image = RgbaImage::from_pixel(5, 1, Rgba(RED));
+        image.put_pixel(2, 0, Rgba(GREEN));
+        assert_eq!(rendered(&image, 1, 1, ScaleMode::Center), [[0xFF_00_FF_00]]);
+    }
+
+    #[test]
+    fn tile_repeats_from_the_top_left() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba(RED));
+        image.put_pixel(1, 0, Rgba(GREEN));
+        let (r, g) = (0xFF_FF_00_00, 0xFF_00_FF_00);
+        assert_eq!(
+            rendered(&image, 3, 2, ScaleMode::Tile),
+            [[r, g, r], [r, g, r]]
+        );
+    }
+
+    #[test]
+    fn translucent_images_show_the_background() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([0xFF, 0, 0, 0x80]));
+        assert_eq!(rendered(&image, 1, 1, ScaleMode::Center), [[0xFF_80_00_7F]]);
+
+        // No image at all, over a translucent color.
+        let mut canvas = [0; 4];
+        render(&mut canvas, 1, 1, None, ScaleMode::Fill, 0x80_FF_FF_FF);
+        assert_eq!(u32::from_le_bytes(canvas), 0x80_80_80_80);
+    }
+
+    #[test]
+    fn over_and_premultiply() {
+        assert_eq!(over([0, 1, 2, 3], [0xFF, 10, 20, 30]), [0xFF, 10, 20, 30]);
+        assert_eq!(over([0xFF, 1, 2, 3], [0xFF, 10, 20, 30]), [0xFF, 1, 2, 3]);
+        assert_eq!(over([0, 1, 2, 3], [0, 10, 20, 30]), [0; 4]);
+        // Half over half: three quarters covered, colors weighted 2 to 1, rounded down.
+        assert_eq!(
+            over([0x80, 0xFF, 0, 0], [0x80, 0, 0xFF, 0]),
+            [0xBF, 0xAA, 0x54, 0]
+        );
+
+        assert_eq!(premultiply([0xFF, 0x12, 0x34, 0x56]), 0xFF_12_34_56);
+        assert_eq!(premultiply([0x80, 0xFF, 0x40, 0]), 0x80_80_20_00);
+        assert_eq!(premultiply([0, 0xFF, 0xFF, 0xFF]), 0);
+    }
+
+    #[test]
+    fn directories_expand_to_their_images_in_order() {
+        let dir = std::env::temp_dir().join(format!("wallpapers-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.png")).unwrap();
+        for name in ["b.png", "a.JPG", "c.jpeg", "notes.txt", "noextension"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let given = PathBuf::from("/elsewhere/photo.webp");
+        let images = collect_images(&[dir.clone(), given.clone()]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            images,
+            [
+                dir.join("a.JPG"),
+                dir.join("b.png"),
+                dir.join("c.jpeg"),
+                given
+            ]
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use std::rc::Rc;
+
+use crate::draw::{Canvas, Image, Rect};
+use crate::font::Font;
+use crate::regions::{PointerRegions, RegionState};
+
+/// Space between a button's label and its edges.
+const BUTTON_PADDING: (i32, i32) = (12, 4);
+const BUTTON_RADIUS: f32 = 6.0;
+const PROGRESS_SIZE: (i32, i32) = (100, 6);
+/// Drawn over keyed widgets under the pointer.
+const HOVER_OVERLAY: u32 = 0x28_FF_FF_FF;
+const PRESSED_OVERLAY: u32 = 0x28_00_00_00;
+
+/// Where a widget goes across its row or column, or along both axes in a box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Align {
+    Start,
+    Center,
+    End,
+    /// Take all the space there is.
+    #[default]
+    Stretch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Row,
+    Column,
+}
+
+/// What a widget is, and what it holds.
+pub(crate) enum Kind {
+    /// Children on top of each other, later ones above.
+    Box(Vec<Widget>),
+    /// Children one after another, `spacing` apart.
+    Line {
+        direction: Direction,
+        spacing: i32,
+        children: Vec<Widget>,
+    },
+    Label {
+        text: String,
+        color: u32,
+        /// Byte index in `text` to draw a caret before.
+        caret: Option<usize>,
+    },
+    Image(Rc<Image>),
+    Button {
+        label: String,
+        color: u32,
+        background: u32,
+    },
+    /// A bar filled to `value`, from 0 to 1.
+    Progress {
+        value: f32,
+        color: u32,
+        track: u32,
+    },
+    /// Nothing, usually with flex to push its neighbours apart.
+    Spacer,
+}
+
+/// A node of a retained widget tree, measured, laid out and painted as a whole.
+///
+/// Colors are straight ARGB.
+pub(crate) struct Widget {
+    pub(crate) kind: Kind,
+    /// Reported by [`Widget::hit`] for events over the widget.
+    pub(crate) key: Option<String>,
+    /// Share of the space left over in a row or column, none without flex.
+    pub(crate) flex: u32,
+    pub(crate) padding: i32,
+    pub(crate) align: Align,
+    pub(crate) background: Option<u32>,
+    /// Where the last layout put it, including the padding.
+    pub(crate) bounds: Rect,
+}
+
+impl Widget {
+    pub fn new(kind: Kind) -> Self {
+        Widget {
+            kind,
+            key: None,
+            flex: 0,
+            padding: 0,
+            align: Align::default(),
+            background: None,
+            bounds: Rect::new(0, 0, 0, 0),
+        }
+    }
+
+    pub fn boxed(children: Vec<Widget>) -> Self {
+        Self::new(Kind::Box(children))
+    }
+
+    pub fn row(spacing: i32, children: Vec<Widget>) -> Self {
+        Self::new(Kind::Line {
+            direction: Direction::Row,
+            spacing,
+            children,
+        })
+    }
+
+    pub fn column(spacing: i32, children: Vec<Widget>) -> Self {
+        Self::new(Kind::Line {
+            direction: Direction::Column,
+            spacing,
+            children,
+        })
+    }
+
+    pub fn label(text: impl Into<String>, color: u32) -> Self {
+        Self::new(Kind::Label {
+            text: text.into(),
+            color,
+            caret: None,
+        })
+    }
+
+    pub fn image(image: Rc<Image>) -> Self {
+        Self::new(Kind::Image(image))
+    }
+
+    pub fn button(label: impl Into<String>, color: u32, background: u32) -> Self {
+        Self::new(Kind::Button {
+            label: label.into(),
+            color,
+            background,
+        })
+    }
+
+    pub fn progress(value: f32, color: u32, track: u32) -> Self {
+        Self::new(Kind::Progress {
+            value,
+            color,
+            track,
+        })
+    }
+
+    pub fn spacer() -> Self {
+        Self::new(Kind::Spacer).with_flex(1)
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    pub fn with_flex(mut self, flex: u32) -> Self {
+        self.flex = flex;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: i32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_background(mut self, color: u32) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// The size the widget would like, including its padding.
+    pub fn measure(&self, font: &mut Font) -> (i32, i32) {
+        let (width, height) = match &self.kind {
+            Kind::Box(children) => children
+                .iter()
+                .map(|child| child.measure(font))
+                .fold((0, 0), |(w, h), (cw, ch)| (w.max(cw), h.max(ch))),
+            Kind::Line {
+                direction,
+                spacing,
+                children,
+            } => {
+                let gaps = spacing * (children.len() as i32 - 1).max(0);
+                let (main, cross) = children
+                    .iter()
+                    .map(|child| direction.split(child.measure(font)))
+                    .fold((gaps, 0), |(main, cross), (m, c)| (main + m, cross.max(c)));
+                direction.join(main, cross)
+            }
+            Kind::Label { text, caret, .. } => {
+                // Room for a caret at the end.
+                let caret = caret.map_or(0, |_| 1);
+                (font.measure(text) + caret, font.line_height())
+            }
+            Kind::Image(image) => (image.width as i32, image.height as i32),
+            Kind::Button { label, .. } => (
+                font.measure(label) + 2 * BUTTON_PADDING.0,
+                font.line_height() + 2 * BUTTON_PADDING.1,
+            ),
+            Kind::Progress { .. } => PROGRESS_SIZE,
+            Kind::Spacer => (0, 0),
+        };
+        (width + 2 * self.padding, height + 2 * self.padding)
+    }
+
+    /// Place the widget and everything in it within `bounds`.
+    pub fn layout(&mut self, bounds: Rect, font: &mut Font) {
+        self.bounds = bounds;
+        let inner = bounds.inset(self.padding);
+        match &mut self.kind {
+            Kind::Box(children) => {
+                for child in children {
+                    let (width, height) = child.measure(font);
+                    let (x, w) = place(inner.x, inner.w, width, child.align);
+                    let (y, h) = place(inner.y, inner.h, height, child.align);
+                    child.layout(Rect::new(x, y, w, h), font);
+                }
+            }
+            Kind::Line {
+                direction,
+                spacing,
+                children,
+            } => {
+                let direction = *direction;
+                let sizes: Vec<_> = children
+                    .iter()
+                    .map(|child| direction.split(child.measure(font)))
+                    .collect();
+                let (available, cross_space) = direction.split((inner.w, inner.h));
+                let (start, cross_start) = direction.split((inner.x, inner.y));
+
+                let gaps = *spacing * (children.len() as i32 - 1).max(0);
+                let natural: i32 = sizes.iter().map(|&(main, _)| main).sum();
+                let mut extra = (available - gaps - natural).max(0);
+                let mut flex: u32 = children.iter().map(|child| child.flex).sum();
+
+                let mut position = start;
+                for (child, (main, cross)) in children.iter_mut().zip(sizes) {
+                    // Each flexible child takes its share of what the earlier ones left.
+                    let grow = match child.flex {
+                        0 => 0,
+                        share => {
+                            let grow = extra * share as i32 / flex as i32;
+                            extra -= grow;
+                            flex -= share;
+                            grow
+                        }
+                    };
+                    let main = main + grow;
+                    let (cross_position, cross) =
+                        place(cross_start, cross_space, cross, child.align);
+                    let (x, y) = direction.join(position, cross_position);
+                    let (w, h) = direction.join(main, cross);
+                    child.layout(Rect::new(x, y, w, h), font);
+                    position += main + *spacing;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the widget where the last layout put it, clipped to its bounds, with keyed
+    /// widgets highlighted as `regions` says the pointer is hovering or pressing them.
+    pub fn paint(&self, canvas: &mut Canvas, font: &mut Font, regions: &PointerRegions) {
+        let previous = canvas.clip();
+        canvas.set_clip(Some(previous.intersect(self.bounds)));
+
+        if let Some(color) = self.background {
+            canvas.fill_rect(self.bounds, color);
+        }
+        let overlay = match self.key.as_deref().map(|key| regions.state(key)) {
+            Some(RegionState::Hovered) => Some(HOVER_OVERLAY),
+            Some(RegionState::Pressed) => Some(PRESSED_OVERLAY),
+            Some(RegionState::Normal) | None => None,
+        };
+        // Buttons highlight their own shape instead.
+        if let (Some(overlay), false) = (overlay, matches!(self.kind, Kind::Button { .. })) {
+            canvas.fill_rect(self.bounds, overlay);
+        }
+        let inner = self.bounds.inset(self.padding);
+        match &self.kind {
+            Kind::Box(children) | Kind::Line { children, .. } => {
+                for child in children {
+                    child.paint(canvas, font, regions);
+                }
+            }
+            Kind::Label { text, color, caret } => {
+                let baseline = inner.y + (inner.h - font.line_height()) / 2 + font.ascent();
+                canvas.text(font, inner.x, baseline, text, *color);
+                if let Some(caret) = caret {
+                    let x = inner.x + font.measure(&text[..*caret]);
+                    let top = baseline - font.ascent();
+                    canvas.fill_rect(Rect::new(x, top, 1, font.line_height()), *color);
+                }
+            }
+            Kind::Image(image) => {
+                let x = inner.x + (inner.w - image.width as i32) / 2;
+                let y = inner.y + (inner.h - image.height as i32) / 2;
+                canvas.blit(image, x, y, 1.0);
+            }
+            Kind::Button {
+                label,
+                color,
+                background,
+            } => {
+                canvas.fill_rounded_rect(inner, BUTTON_RADIUS, *background);
+                if let Some(overlay) = overlay {
+                    canvas.fill_rounded_rect(inner, BUTTON_RADIUS, overlay);
+                }
+                let x = inner.x + (inner.w - font.measure(label)) / 2;
+                let baseline = inner.y + (inner.h - font.line_height()) / 2 + font.ascent();
+                canvas.text(font, x, baseline, label, *color);
+            }
+            Kind::Progress {
+                value,
+                color,
+                track,
+            } => {
+                let radius = inner.h as f32 / 2.0;
+                canvas.fill_rounded_rect(inner, radius, *track);
+                let filled = (inner.w as f32 * value.clamp(0.0, 1.0)).round() as i32;
+                canvas.fill_rounded_rect(Rect { w: filled, ..inner }, radius, *color);
+            }
+            Kind::Spacer => {}
+        }
+
+        canvas.set_clip(Some(previous));
+    }
+
+    /// The widget called `key`, searching depth first.
+    pub fn find(&self, key: &str) -> Option<&Widget> {
+        if self.key.as_deref() == Some(key) {
+            return Some(self);
+        }
+        match &self.kind {
+            Kind::Box(children) | Kind::Line { children, .. } => {
+                children.iter().find_map(|child| child.find(key))
+            }
+            _ => None,
+        }
+    }
+
+    /// The key of the innermost keyed widget at `x`, `y`, the topmost where they overlap.
+    pub fn hit(&self, x: i32, y: i32) -> Option<&str> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+        let children = match &self.kind {
+            Kind::Box(children) | Kind::Line { children, .. } => children.as_slice(),
+            _ => &[],
+        };
+        children
+            .iter()
+            .rev()
+            .find_map(|child| child.hit(x, y))
+            .or(self.key.as_deref())
+    }
+}
+
+impl Direction {
+    /// A width and height as the size along the direction and across it.
+    fn split(self, (width, height): (i32, i32)) -> (i32, i32) {
+        match self {
+            Direction::Row => (width, height),
+            Direction::Column => (height, width),
+        }
+    }
+
+    /// The inverse of [`Direction::split`].
+    fn join(self, main: i32, cross: i32) -> (i32, i32) {
+        self.split((main, cross))
+    }
+}
+
+/// Position and length of something `size` long aligned in `available` from `start`.
+fn place(start: i32, available: i32, size: i32, align: Align) -> (i32, i32) {
+    match align {
+        Align::Start => (start, size),
+        Align::Center => (start + (available - size) / 2, size),
+        Align::End => (start + available - size, size),
+        Align::Stretch => (start, available),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text is measured with whatever font is installed, everything else does not need it.
+    fn font() -> Option<Font> {
+        Font::load(16.0)
+            .map_err(|error| eprintln!("No font, skipping: {error}"))
+            .ok()
+    }
+
+    fn image(width: u32, height: u32) -> Widget {
+        Widget::image(Rc::new(Image::from_fn(width, height, |_, _| 0xFF_FF_FF_FF)))
+    }
+
+    fn bounds(widget: &Widget, key: &str) -> Rect {
+        widget.find(key).unwrap().bounds
+    }
+
+    #[test]
+    fn rows_share_the_space_left_by_flex() {
+        let Some(mut font) = font() else { return };
+        let mut row = Widget::row(
+            4,
+            vec![
+                image(10, 8).with_key("image"),
+                Widget::spacer().with_key("one"),
+                Widget::spacer().with_flex(2).with_key("two"),
+                Widget::progress(0.5, 0, 0).with_key("progress"),
+            ],
+        );
+        assert_eq!(row.measure(&mut font), (10 + 100 + 3 * 4, 8));
+
+        row.layout(Rect::new(5, 0, 200, 20), &mut font);
+        assert_eq!(bounds(&row, "image"), Rect::new(5, 0, 10, 20));
+        // 200 - 110 - 12 = 78 left over, a third and two thirds of it.
+        assert_eq!(bounds(&row, "one"), Rect::new(19, 0, 26, 20));
+        assert_eq!(bounds(&row, "two"), Rect::new(49, 0, 52, 20));
+        assert_eq!(bounds(&row, "progress"), Rect::new(105, 0, 100, 20));
+    }
+
+    #[test]
+    fn columns_align_across() {
+        let Some(mut font) = font() else { return };
+        let mut column = Widget::column(
+            2,
+            vec![
+                image(10, 10).with_align(Align::Start).with_key("start"),
+                image(10, 10).with_align(Align::Center).with_key("center"),
+                image(10, 10).with_align(Align::End).with_key("end"),
+                image(10, 10).with_key("stretch"),
+            ],
+        )
+        .with_padding(3);
+        assert_eq!(column.measure(&mut font), (10 + 6, 4 * 10 + 3 * 2 + 6));
+
+        column.layout(Rect::new(0, 0, 46, 100), &mut font);
+        assert_eq!(bounds(&column, "start"), Rect::new(3, 3, 10, 10));
+        assert_eq!(bounds(&column, "center"), Rect::new(18, 15, 10, 10));
+        assert_eq!(bounds(&column, "end"), Rect::new(33, 27, 10, 10));
+        assert_eq!(bounds(&column, "stretch"), Rect::new(3, 39, 40, 10));
+    }
+
+    #[test]
+    fn overflowing_rows_keep_natural_sizes() {
+        let Some(mut font) = font() else { return };
+        let mut row = Widget::row(
+            0,
+            vec![
+                image(30, 5).with_key("a"),
+                Widget::spacer().with_key("b"),
+                image(30, 5).with_key("c"),
+            ],
+        );
+        row.layout(Rect::new(0, 0, 40, 5), &mut font);
+        assert_eq!(bounds(&row, "b"), Rect::new(30, 0, 0, 5));
+        assert_eq!(bounds(&row, "c"), Rect::new(30, 0, 30, 5));
+    }
+
+    #[test]
+    fn labels_and_buttons_fit_their_text() {
+        let Some(mut font) = font() else { return };
+        let text = font.measure("Hello");
+        let line = font.line_height();
+        assert_eq!(Widget::label("Hello", 0).measure(&mut font), (text, line));
+        let caret = Widget::new(Kind::Label {
+            text: "Hello".to_string(),
+            color: 0,
+            caret: Some(5),
+        });
+        assert_eq!(caret.measure(&mut font), (text + 1, line));
+        assert_eq!(
+            Widget::button("Hello", 0, 0)
+                .with_padding(1)
+                .measure(&mut font),
+            (
+                text + 2 * BUTTON_PADDING.0 + 2,
+                line + 2 * BUTTON_PADDING.1 + 2
+            )
+        );
+    }
+
+    #[test]
+    fn hits_the_innermost_topmost_keyed_widget() {
+        let Some(mut font) = font() else { return };
+        let mut tree = Widget::boxed(vec![
+            Widget::row(
+                0,
+                vec![
+                    image(10, 10).with_key("left"),
+                    Widget::row(0, vec![image(10, 10)]).with_key("right"),
+                ],
+            )
+            .with_key("row"),
+            image(4, 4).with_align(Align::Center).with_key("above"),
+        ])
+        .with_key("root");
+        tree.layout(Rect::new(0, 0, 20, 10), &mut font);
+
+        assert_eq!(tree.hit(1, 1), Some("left"));
+        // Unkeyed widgets report their closest keyed parent.
+        assert_eq!(tree.hit(15, 1), Some("right"));
+        // Later children of a box are above earlier ones.
+        assert_eq!(tree.hit(9, 4), Some("above"));
+        assert_eq!(tree.hit(20, 4), None);
+        assert_eq!(tree.find("above").unwrap().bounds, Rect::new(8, 3, 4, 4));
+        assert!(tree.find("missing").is_none());
+    }
+}
@@ -1,19 +1,32 @@
+use std::{num::NonZeroU32, rc::Rc, time::Instant};
+
+use raster::Gradient;
 use smithay_client_toolkit::{
-    compositor::CompositorHandler,
-    delegate_compositor, delegate_keyboard, delegate_output, delegate_pointer, delegate_registry,
+    compositor::{CompositorHandler, CompositorState},
+    data_device_manager::{
+        data_device::{DataDevice, DataDeviceHandler},
+        data_offer::{DataDeviceOffer, DataOfferHandler, DragOffer},
+        data_source::DataSourceHandler,
+        WritePipe,
+    },
+    delegate_compositor, delegate_data_device, delegate_data_device_manager, delegate_data_offer,
+    delegate_data_source, delegate_keyboard, delegate_output, delegate_pointer, delegate_registry,
     delegate_seat, delegate_shm, delegate_xdg_shell, delegate_xdg_window,
     output::{OutputHandler, OutputState},
     reexports::calloop::LoopHandle,
     reexports::client::{
         globals::GlobalList,
-        protocol::{wl_output, wl_seat, wl_shm, wl_surface},
+        protocol::{
+            wl_data_device_manager::DndAction, wl_data_source::WlDataSource, wl_output, wl_seat,
+            wl_surface,
+        },
         Connection, QueueHandle,
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         keyboard::{KeyEvent, KeyboardHandler, Modifiers},
-        pointer::{PointerEvent, PointerEventKind, PointerHandler},
+        pointer::{PointerEvent, PointerEventKind, PointerHandler, BTN_LEFT, BTN_MIDDLE},
         Capability, SeatHandler, SeatState,
     },
     shell::{
@@ -23,13 +36,42 @@ use smithay_client_toolkit::{
     shm::slot::{Buffer, SlotPool},
     shm::{Shm, ShmHandler},
 };
-use tracing::info;
-use wayland_client::protocol::{wl_keyboard, wl_pointer};
+use tracing::{info, warn};
+use wayland_client::protocol::{
+    wl_keyboard,
+    wl_pointer::{self, AxisSource},
+};
+
+use crate::animation::{FrameClock, Tween};
+use crate::clipboard::Clipboard;
+use crate::config::{AnimationConfig, KeyBinding};
+use crate::dnd::{DragAndDrop, Dropped};
+use crate::draw::{Canvas, Image, Rect};
+use crate::font::Font;
+use crate::list_view::{ListEvent, ListView};
+use crate::pixel::{PixelFormat, PixelWriter};
+use crate::presentation::{delegate_presentation, Presentation, PresentationHandler, Summary};
+use crate::primary_selection::{
+    delegate_primary_selection, PrimarySelection, PrimarySelectionHandler,
+};
+use crate::redraw::RedrawScheduler;
+use crate::regions::{PointerRegions, RegionEvent};
+use crate::scroll::{Scroll, ScrollState};
+use crate::text_buffer::{EditAction, TextBuffer};
+use crate::theme::Color;
+use crate::widget::{Align, Kind, Widget};
+
+/// Rows the list moves for each wheel detent.
+const WHEEL_ROWS: i32 = 3;
+/// The animate button's color while the animation is stopped, and while it runs.
+const START_COLOR: Color = Color(0xFF_30_60_C0);
+const STOP_COLOR: Color = Color(0xFF_C0_50_30);
 
 pub(crate) struct SimpleWindow {
     pub(crate) registry_state: RegistryState,
     pub(crate) seat_state: SeatState,
     pub(crate) output_state: OutputState,
+    pub(crate) compositor_state: CompositorState,
     pub(crate) shm: Shm,
 
     pub(crate) exit: bool,
@@ -37,13 +79,62 @@ pub(crate) struct SimpleWindow {
     pub(crate) pool: SlotPool,
     pub(crate) width: u32,
     pub(crate) height: u32,
-    pub(crate) shift: Option<u32>,
+    /// How far the gradient has moved while animated, in pixels.
+    pub(crate) shift: Option<f32>,
     pub(crate) buffer: Option<Buffer>,
     pub(crate) window: Window,
     pub(crate) keyboard: Option<wl_keyboard::WlKeyboard>,
     pub(crate) keyboard_focus: bool,
     pub(crate) pointer: Option<wl_pointer::WlPointer>,
+    pub(crate) modifiers: Modifiers,
+    pub(crate) text: TextBuffer,
+    pub(crate) clipboard: Clipboard,
+    pub(crate) primary_selection: PrimarySelection,
+    pub(crate) dnd: DragAndDrop,
     pub(crate) loop_handle: LoopHandle<'static, SimpleWindow>,
+    /// Buffer format from the config, used when the compositor supports it.
+    pub(crate) format: Option<PixelFormat>,
+    pub(crate) pixels: PixelWriter,
+    /// Draws the controls, which are left out without it.
+    pub(crate) font: Option<Font>,
+    /// The controls as laid out for the last frame, for routing pointer events.
+    pub(crate) root: Option<Widget>,
+    /// Which control the pointer is over or pressing.
+    pub(crate) regions: PointerRegions,
+    pub(crate) scroll: ScrollState,
+    pub(crate) natural_scroll: bool,
+    pub(crate) list: ListView,
+    /// What the last frame showed besides the list, to tell what needs repainting.
+    pub(crate) last_frame: Option<FrameKey>,
+    pub(crate) redraw: RedrawScheduler,
+    /// Kept for redrawing after changes made outside of Wayland events.
+    pub(crate) qh: QueueHandle<SimpleWindow>,
+    pub(crate) animation: AnimationConfig,
+    pub(crate) clock: FrameClock,
+    /// The animate button's color, changing along with the animation.
+    pub(crate) button: Tween<Color>,
+    /// The bar's bindings for showing frame statistics, the only action the window takes from
+    /// its key bindings.
+    pub(crate) stats_keys: Vec<KeyBinding>,
+    /// Frame statistics, shown in the corner while enabled.
+    pub(crate) presentation: Presentation,
+}
+
+/// Everything other than the list that changes how a frame looks.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FrameKey {
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    shift: Option<f32>,
+    button: Color,
+    text: String,
+    cursor: usize,
+    focused: bool,
+    regions: PointerRegions,
+    dnd_hovering: bool,
+    /// The statistics shown, if enabled.
+    stats: Option<Option<Summary>>,
 }
 
 impl SimpleWindow {
@@ -51,6 +142,7 @@ impl SimpleWindow {
         globals: &GlobalList,
         qh: &QueueHandle<Self>,
         loop_handle: LoopHandle<'static, SimpleWindow>,
+        compositor_state: CompositorState,
         shm: Shm,
         pool: SlotPool,
         window: Window,
@@ -59,6 +151,7 @@ impl SimpleWindow {
             registry_state: RegistryState::new(globals),
             seat_state: SeatState::new(globals, qh),
             output_state: OutputState::new(globals, qh),
+            compositor_state,
             shm,
 
             exit: false,
@@ -72,23 +165,130 @@ impl SimpleWindow {
             keyboard: None,
             keyboard_focus: false,
             pointer: None,
+            modifiers: Modifiers::default(),
+            text: TextBuffer::default(),
+            clipboard: Clipboard::new(globals, qh),
+            primary_selection: PrimarySelection::new(globals, qh),
+            dnd: DragAndDrop::default(),
             loop_handle,
+            format: None,
+            pixels: PixelWriter::new(PixelFormat::Argb8888),
+            font: None,
+            root: None,
+            regions: PointerRegions::default(),
+            scroll: ScrollState::default(),
+            natural_scroll: false,
+            list: ListView::new(Vec::new()),
+            last_frame: None,
+            redraw: RedrawScheduler::default(),
+            qh: qh.clone(),
+            animation: AnimationConfig::default(),
+            clock: FrameClock::default(),
+            button: Tween::fixed(START_COLOR),
+            stats_keys: Vec::new(),
+            presentation: Presentation::new(globals, qh),
+        }
+    }
+
+    /// Advance the animations to the frame callback timestamp `time`, returning whether any
+    /// of them still needs frames drawn.
+    fn animate(&mut self, time: u32) -> bool {
+        let elapsed = self.clock.tick(time);
+        if let Some(shift) = &mut self.shift {
+            *shift = (*shift + self.animation.speed * elapsed) % self.width as f32;
+        }
+        // Drawn once more after it finished, to show where it ended.
+        let button = !self.button.is_done();
+        self.button.update(time);
+        self.shift.is_some() || button
+    }
+
+    fn toggle_animation(&mut self) {
+        self.shift = self.shift.xor(Some(0.0));
+        let color = match self.shift {
+            Some(_) => STOP_COLOR,
+            None => START_COLOR,
+        };
+        self.button = self
+            .button
+            .retarget(color, self.animation.duration, self.animation.easing);
+        self.invalidate();
+    }
+
+    /// Draw again at the next frame, once the window has been configured.
+    pub fn invalidate(&mut self) {
+        if !self.first_configure {
+            self.redraw.invalidate(self.window.wl_surface(), &self.qh);
         }
     }
 
     pub fn draw(&mut self, _conn: &Connection, qh: &QueueHandle<Self>) {
+        let started = Instant::now();
         let width = self.width;
         let height = self.height;
-        let stride = self.width as i32 * 4;
+
+        // the surface is always opaque
+        let format = PixelFormat::choose(self.format, self.shm.formats(), true);
+        if format != self.pixels.format() {
+            info!("Drawing in {format:?}");
+            if let Some(preferred) = self.format.filter(|&preferred| preferred != format) {
+                warn!("{preferred:?} is not supported for this surface, using {format:?}");
+            }
+            self.pixels = PixelWriter::new(format);
+            self.buffer = None;
+        }
+        let stride = self.pixels.stride(width);
+
+        self.root = self.font.as_mut().map(|font| {
+            let mut root = controls(
+                &self.text,
+                self.keyboard_focus,
+                self.shift,
+                self.button.value(),
+                width,
+                format,
+            );
+            root.layout(Rect::new(0, 0, width as i32, height as i32), font);
+            root
+        });
+        let list = self.root.as_ref().and_then(|root| root.find("list"));
+        if let (Some(list), Some(font)) = (list, &self.font) {
+            self.list.layout(list.bounds, font);
+        }
+
+        // Only the list is repainted for the compositor when nothing else changed, as when
+        // scrolling it.
+        let frame = FrameKey {
+            width,
+            height,
+            format,
+            shift: self.shift,
+            button: self.button.value(),
+            text: self.text.text().to_string(),
+            cursor: self.text.cursor(),
+            focused: self.keyboard_focus,
+            regions: self.regions.clone(),
+            dnd_hovering: self.dnd.hovering,
+            stats: self
+                .presentation
+                .enabled
+                .then(|| self.presentation.stats.summary.clone()),
+        };
+        let list_damage = self.list.take_damage();
+        let damage = if self.last_frame.as_ref() != Some(&frame) {
+            Some(Rect::new(0, 0, width as i32, height as i32))
+        } else {
+            list_damage
+        };
+        self.last_frame = Some(frame);
+        let Some(damage) = damage else {
+            // Nothing visible changed, so there is nothing to commit.
+            return;
+        };
 
         let buffer = self.buffer.get_or_insert_with(|| {
             self.pool
-                .create_buffer(
-                    width as i32,
-                    height as i32,
-                    stride,
-                    wl_shm::Format::Argb8888,
-                )
+                .create_buffer(width as i32, height as i32, stride, format.wl_format())
                 .expect("Failed to create buffer")
                 .0
         });
@@ -104,7 +304,7 @@ impl SimpleWindow {
                         self.width as i32,
                         self.height as i32,
                         stride,
-                        wl_shm::Format::Argb8888,
+                        format.wl_format(),
                     )
                     .expect("Failed to create buffer");
                 *buffer = second_buffer;
@@ -113,39 +313,38 @@ impl SimpleWindow {
         };
 
         // draw to the window
-        {
-            let shift = self.shift.unwrap_or(0);
-            canvas
-                .chunks_exact_mut(4)
-                .enumerate()
-                .for_each(|(index, chunk)| {
-                    let x = ((index + shift as usize) % width as usize) as u32;
-                    let y = (index / width as usize) as u32;
-
-                    let a = 0xFF;
-                    let r = u32::min(((width - x) * 0xFF) / width, ((height - y) * 0xFF) / height);
-                    let g = u32::min((x * 0xFF) / width, ((height - y) * 0xFF) / height);
-                    let b = u32::min(((width - x) * 0xFF) / width, (y * 0xFF) / height);
-                    let color = (a << 24) + (r << 16) + (g << 8) + b;
-
-                    let array: &mut [u8; 4] = chunk.try_into().unwrap();
-                    *array = color.to_le_bytes();
-                });
-
-            if let Some(shift) = &mut self.shift {
-                *shift = (*shift + 1) % width;
+        self.pixels.render(canvas, width, height, |pixels| {
+            let mut canvas = Canvas::new(pixels, width, height);
+            let shift = self.shift.unwrap_or(0.0) as u32;
+            canvas.fill_gradient(canvas.bounds(), &Gradient::new(width, height, shift));
+
+            if let (Some(root), Some(font)) = (&self.root, &mut self.font) {
+                if let Some(shapes) = root.find("shapes") {
+                    draw_shapes(&mut canvas, shapes.bounds, shift);
+                }
+                root.paint(&mut canvas, font, &self.regions);
+                self.list.paint(&mut canvas, font);
+                if self.presentation.enabled {
+                    self.presentation
+                        .stats
+                        .paint(&mut canvas, font, Summary::lines);
+                }
             }
-        }
 
-        // damage the entire window
+            // outline the window while something droppable hovers over it
+            if self.dnd.hovering {
+                canvas.stroke_rect(canvas.bounds(), 4, 0xFF_30_60_C0);
+            }
+        });
+
         self.window
             .wl_surface()
-            .damage_buffer(0, 0, self.width as i32, self.height as i32);
+            .damage_buffer(damage.x, damage.y, damage.w, damage.h);
 
         // request next frame
-        self.window
-            .wl_surface()
-            .frame(qh, self.window.wl_surface().clone());
+        self.redraw.request(self.window.wl_surface(), qh);
+        self.presentation
+            .feedback(self.window.wl_surface(), qh, started.elapsed());
 
         // attach and commit to present
         buffer
@@ -153,6 +352,156 @@ impl SimpleWindow {
             .expect("Failed to attach buffer");
         self.window.commit();
     }
+
+    /// Scrolling over the list moves it, and over the progress bar scrubs through the
+    /// animation.
+    fn scrolled(&mut self, scroll: Scroll) {
+        self.invalidate();
+        let (x, y) = (scroll.position.0 as i32, scroll.position.1 as i32);
+        match self.root.as_ref().and_then(|root| root.hit(x, y)) {
+            // Touchpads scroll by the pixel, wheels by whole rows.
+            Some("list") => match scroll.source {
+                Some(AxisSource::Finger | AxisSource::Continuous) => self.list.scroll_by(scroll.y),
+                _ => self.list.scroll_rows(scroll.steps_y * WHEEL_ROWS),
+            },
+            Some("progress") => {
+                if let Some(shift) = &mut self.shift {
+                    let moved = *shift + (scroll.x + scroll.y) as f32;
+                    *shift = moved.rem_euclid(self.width as f32);
+                }
+            }
+            key => info!("Scroll {scroll:?} over {key:?}"),
+        }
+    }
+
+    fn list_activated(&self, index: usize) {
+        info!("Activated {:?}", self.list.item(index));
+    }
+}
+
+/// A toolbar with the typed text above a hint, the list beside the shapes, and the animation's
+/// progress at the bottom.
+fn controls(
+    text: &TextBuffer,
+    focused: bool,
+    shift: Option<f32>,
+    button: Color,
+    width: u32,
+    format: PixelFormat,
+) -> Widget {
+    const FOREGROUND: u32 = 0xFF_E0_E0_E0;
+    const DIM: u32 = 0xFF_A0_A0_B0;
+    const ACCENT: u32 = START_COLOR.0;
+    const TRACK: u32 = 0x80_20_20_28;
+
+    let field = match text.text() {
+        "" if !focused => Widget::label("Type something", DIM),
+        typed => Widget::new(Kind::Label {
+            text: typed.to_string(),
+            color: FOREGROUND,
+            caret: focused.then(|| text.cursor()),
+        }),
+    };
+    let toolbar = Widget::row(
+        8,
+        vec![
+            field.with_flex(1).with_align(Align::Center),
+            Widget::button(
+                if shift.is_some() { "Pause" } else { "Animate" },
+                FOREGROUND,
+                button.0,
+            )
+            .with_key("animate"),
+        ],
+    )
+    .with_padding(6)
+    .with_background(0xC0_20_20_28);
+    let hint = Widget::label("Drag to share the text, middle click to paste", DIM)
+        .with_align(Align::Start)
+        .with_padding(4)
+        .with_background(TRACK);
+
+    let gradient = Gradient::new(16, 16, 0);
+    let swatch = Image::from_fn(16, 16, |x, y| gradient.pixel(x as usize, y as usize));
+    let status = Widget::row(
+        8,
+        vec![
+            Widget::image(Rc::new(swatch)),
+            Widget::progress(shift.unwrap_or(0.0) / width as f32, ACCENT, TRACK)
+                .with_key("progress")
+                .with_flex(1)
+                .with_align(Align::Center),
+            Widget::label(format!("{format:?}"), FOREGROUND).with_align(Align::End),
+        ],
+    );
+
+    // The list and the shapes are drawn into these by the window.
+    let middle = Widget::row(
+        8,
+        vec![
+            Widget::spacer().with_flex(2).with_key("list"),
+            Widget::spacer().with_key("shapes"),
+        ],
+    )
+    .with_flex(1);
+
+    Widget::boxed(vec![Widget::column(8, vec![toolbar, hint, middle, status])]).with_padding(12)
+}
+
+/// A panel of shapes in the middle of `area`, showing off what the canvas can draw.
+///
+/// `shift` turns the arc along with the animated background.
+fn draw_shapes(canvas: &mut Canvas, area: Rect, shift: u32) {
+    let (center_x, center_y) = (area.x + area.w / 2, area.y + area.h / 2);
+    let panel = Rect::new(center_x - 100, center_y - 60, 200, 120);
+    let previous = canvas.clip();
+    let visible = previous.intersect(area);
+    canvas.set_clip(Some(visible));
+    canvas.fill_rounded_rect(panel, 16.0, 0xC0_20_20_28);
+    canvas.stroke_rounded_rect(panel, 16.0, 2.0, 0xFF_E0_E0_E8);
+
+    // everything inside stays within the panel's border
+    canvas.set_clip(Some(visible.intersect(panel.inset(2))));
+    let (x, y) = (panel.x as f32, panel.y as f32);
+    canvas.fill_circle(x + 40.0, y + 40.0, 20.0, 0xFF_E2_52_52);
+    canvas.stroke_circle(x + 100.0, y + 40.0, 18.0, 3.0, 0xFF_52_E2_94);
+    let start = shift as f32 * 0.05;
+    canvas.arc(
+        x + 160.0,
+        y + 40.0,
+        18.0,
+        start,
+        start + std::f32::consts::PI * 1.5,
+        4.0,
+        0xFF_52_94_E2,
+    );
+    canvas.line(
+        (x + 20.0, y + 100.0),
+        (x + 80.0, y + 75.0),
+        3.0,
+        0xFF_E2_C2_52,
+    );
+    canvas.fill_polygon(
+        &[
+            (x + 100.0, y + 100.0),
+            (x + 120.0, y + 70.0),
+            (x + 140.0, y + 100.0),
+        ],
+        0x80_FF_FF_FF,
+    );
+    canvas.set_clip(Some(previous));
+}
+
+/// The size after a configure: whatever the compositor picked, and `current` along the axes it
+/// leaves to the client.
+fn configured_size(
+    new_size: (Option<NonZeroU32>, Option<NonZeroU32>),
+    current: (u32, u32),
+) -> (u32, u32) {
+    (
+        new_size.0.map_or(current.0, |v| v.get()),
+        new_size.1.map_or(current.1, |v| v.get()),
+    )
 }
 
 impl CompositorHandler for SimpleWindow {
@@ -171,9 +520,12 @@ impl CompositorHandler for SimpleWindow {
         conn: &Connection,
         qh: &QueueHandle<Self>,
         _surface: &wl_surface::WlSurface,
-        _time: u32,
+        time: u32,
     ) {
-        self.draw(conn, qh);
+        let animating = self.animate(time);
+        if self.redraw.frame(animating) {
+            self.draw(conn, qh);
+        }
     }
 }
 
@@ -243,6 +595,8 @@ impl SeatHandler for SimpleWindow {
                 .expect("Failed to create keyboard");
 
             self.keyboard = Some(keyboard);
+            self.clipboard.add_seat(qh, &seat);
+            self.primary_selection.add_seat(qh, &seat);
         }
 
         if capability == Capability::Pointer && self.pointer.is_none() {
@@ -265,6 +619,8 @@ impl SeatHandler for SimpleWindow {
         if capability == Capability::Keyboard && self.keyboard.is_some() {
             info!("Unset keyboard capability");
             self.keyboard.take().unwrap().release();
+            self.clipboard.remove_seat();
+            self.primary_selection.remove_seat();
         }
 
         if capability == Capability::Pointer && self.pointer.is_some() {
@@ -290,6 +646,7 @@ impl KeyboardHandler for SimpleWindow {
         if self.window.wl_surface() == surface {
             info!("Keyboard focus on window with pressed syms: {keysyms:?}");
             self.keyboard_focus = true;
+            self.invalidate();
         }
     }
 
@@ -304,18 +661,54 @@ impl KeyboardHandler for SimpleWindow {
         if self.window.wl_surface() == surface {
             info!("Release keyboard focus on window");
             self.keyboard_focus = false;
+            self.invalidate();
         }
     }
 
     fn press_key(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
-        _: u32,
+        serial: u32,
         event: KeyEvent,
     ) {
         info!("Key press: {event:?}");
+        self.invalidate();
+
+        if self
+            .stats_keys
+            .iter()
+            .any(|binding| binding.matches(&event, &self.modifiers))
+        {
+            return self.presentation.toggle(&self.loop_handle);
+        }
+        match self.list.handle_key(&event, &self.modifiers) {
+            ListEvent::Ignored => {}
+            ListEvent::Changed => return,
+            ListEvent::Activated(index) => return self.list_activated(index),
+        }
+        match self.text.handle_key(&event, &self.modifiers) {
+            EditAction::Copy => {
+                let text = self.text.selected_text().unwrap_or(self.text.text());
+                self.clipboard.copy(qh, text, serial);
+            }
+            EditAction::Paste => {
+                self.clipboard
+                    .paste(&self.loop_handle, |window: &mut SimpleWindow, text| {
+                        window.text.insert(&text);
+                        window.invalidate();
+                        info!("Text: {:?}", window.text.text());
+                    });
+            }
+            EditAction::Edited => {
+                info!("Text: {:?}", self.text.text());
+                if let Some(selected) = self.text.selected_text() {
+                    self.primary_selection.set(qh, selected, serial);
+                }
+            }
+            EditAction::Ignored => {}
+        }
     }
 
     fn release_key(
@@ -338,6 +731,7 @@ impl KeyboardHandler for SimpleWindow {
         modifiers: Modifiers,
     ) {
         info!("Update modifiers: {modifiers:?}");
+        self.modifiers = modifiers;
     }
 }
 
@@ -345,17 +739,43 @@ impl PointerHandler for SimpleWindow {
     fn pointer_frame(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _pointer: &wl_pointer::WlPointer,
         events: &[PointerEvent],
     ) {
         use PointerEventKind::*;
+        let regions = self.regions.clone();
         for event in events {
             // Ignore events for other surfaces
             if &event.surface != self.window.wl_surface() {
                 continue;
             }
 
+            let (x, y) = (event.position.0 as i32, event.position.1 as i32);
+            let hit = self
+                .root
+                .as_ref()
+                .and_then(|root| root.hit(x, y))
+                .map(str::to_string);
+            for region in self.regions.handle(&event.kind, hit.as_deref()) {
+                match region {
+                    RegionEvent::Click { key, button, .. }
+                        if key == "animate" && button == BTN_LEFT =>
+                    {
+                        self.toggle_animation();
+                    }
+                    RegionEvent::Click { key, button, count }
+                        if key == "list" && button == BTN_LEFT =>
+                    {
+                        self.invalidate();
+                        if let (Some(index), 2) = (self.list.click(y), count) {
+                            self.list_activated(index);
+                        }
+                    }
+                    region => info!("{region:?}"),
+                }
+            }
+
             match event.kind {
                 Enter { .. } => {
                     info!("Pointer entered @{:?}", event.position);
@@ -363,23 +783,203 @@ impl PointerHandler for SimpleWindow {
                 Leave { .. } => {
                     info!("Pointer left");
                 }
-                Motion { .. } => {}
-                Press { button, .. } => {
+                Motion { .. } => {
+                    let text = self.text.selected_text().unwrap_or(self.text.text());
+                    self.dnd.motion(
+                        qh,
+                        self.clipboard.manager.as_ref(),
+                        self.clipboard.device.as_ref(),
+                        &self.compositor_state,
+                        &mut self.pool,
+                        self.window.wl_surface(),
+                        event.position,
+                        text,
+                    );
+                }
+                Press { button, .. } if button == BTN_MIDDLE => {
+                    info!("Middle press @ {:?}", event.position);
+                    self.primary_selection.paste(
+                        &self.loop_handle,
+                        |window: &mut SimpleWindow, text| {
+                            window.text.insert(&text);
+                            window.invalidate();
+                            info!("Text: {:?}", window.text.text());
+                        },
+                    );
+                }
+                // Controls only react to clicks, the shapes are part of the background.
+                Press { .. } if hit.as_deref().is_some_and(|key| key != "shapes") => {}
+                Press { button, serial, .. } => {
                     info!("Press {:x} @ {:?}", button, event.position);
-                    self.shift = self.shift.xor(Some(0));
+                    self.toggle_animation();
+                    if button == BTN_LEFT {
+                        self.dnd.press(serial, event.position);
+                    }
                 }
                 Release { button, .. } => {
                     info!("Release {:x} @ {:?}", button, event.position);
+                    self.dnd.release();
                 }
-                Axis {
-                    horizontal,
-                    vertical,
-                    ..
-                } => {
-                    info!("Scroll H:{horizontal:?}, V:{vertical:?}");
-                }
+                Axis { .. } => {}
             }
         }
+
+        let surface = self.window.wl_surface().clone();
+        let events = events.iter().filter(|event| event.surface == surface);
+        if let Some(scroll) = self.scroll.frame(events, self.natural_scroll) {
+            self.scrolled(scroll);
+        }
+        // Hovered and pressed controls are highlighted.
+        if self.regions != regions {
+            self.invalidate();
+        }
+    }
+}
+
+impl DataDeviceHandler for SimpleWindow {
+    fn enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, data_device: DataDevice) {
+        self.dnd.enter(&data_device);
+        self.invalidate();
+    }
+
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {
+        self.dnd.leave();
+        self.invalidate();
+    }
+
+    fn motion(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {}
+
+    fn selection(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: DataDevice) {
+        // The offer is kept by the data device and only read when pasting.
+    }
+
+    fn drop_performed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: DataDevice,
+    ) {
+        self.dnd.drop_performed(
+            &self.loop_handle,
+            &data_device,
+            |window: &mut SimpleWindow, dropped| match dropped {
+                Dropped::Files(paths) => {
+                    for path in paths {
+                        info!("Dropped file: {}", path.display());
+                        println!("{}", path.display());
+                    }
+                }
+                Dropped::Text(text) => {
+                    window.text.insert(&text);
+                    window.invalidate();
+                    info!("Text: {:?}", window.text.text());
+                }
+            },
+        );
+    }
+}
+
+impl DataOfferHandler for SimpleWindow {
+    fn offer(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DataDeviceOffer,
+        _mime_type: String,
+    ) {
+    }
+
+    fn source_actions(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+
+    fn selected_action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+}
+
+impl DataSourceHandler for SimpleWindow {
+    fn accept_mime(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _mime: Option<String>,
+    ) {
+    }
+
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &WlDataSource,
+        mime: String,
+        fd: WritePipe,
+    ) {
+        if self.dnd.owns(source) {
+            self.dnd.send(&self.loop_handle, &mime, fd);
+        } else {
+            self.clipboard.send(&self.loop_handle, source, &mime, fd);
+        }
+    }
+
+    fn cancelled(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        if self.dnd.owns(source) {
+            self.dnd.end();
+        } else {
+            self.clipboard.cancelled(source);
+        }
+    }
+
+    fn dnd_dropped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _source: &WlDataSource) {
+    }
+
+    fn dnd_finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        if self.dnd.owns(source) {
+            self.dnd.end();
+        }
+    }
+
+    fn action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _action: DndAction,
+    ) {
+    }
+}
+
+impl PrimarySelectionHandler for SimpleWindow {
+    fn primary_selection(&mut self) -> &mut PrimarySelection {
+        &mut self.primary_selection
+    }
+
+    fn loop_handle(&self) -> &LoopHandle<'static, Self> {
+        &self.loop_handle
+    }
+}
+
+impl PresentationHandler for SimpleWindow {
+    fn presentation(&mut self) -> &mut Presentation {
+        &mut self.presentation
+    }
+
+    fn stats_updated(&mut self) {
+        if let Some(summary) = &self.presentation.stats.summary {
+            info!("Frame statistics: {summary}");
+        }
+        self.invalidate();
     }
 }
 
@@ -397,13 +997,15 @@ impl WindowHandler for SimpleWindow {
         _serial: u32,
     ) {
         self.buffer = None;
-        self.width = configure.new_size.0.map(|v| v.get()).unwrap_or(256);
-        self.height = configure.new_size.1.map(|v| v.get()).unwrap_or(256);
+        // Without a size from the compositor, keep the one asked for in the config.
+        (self.width, self.height) = configured_size(configure.new_size, (self.width, self.height));
 
         // Initiate the first draw.
         if self.first_configure {
             self.first_configure = false;
             self.draw(conn, qh);
+        } else {
+            self.invalidate();
         }
     }
 }
@@ -419,6 +1021,13 @@ delegate_pointer!(SimpleWindow);
 delegate_xdg_shell!(SimpleWindow);
 delegate_xdg_window!(SimpleWindow);
 
+delegate_data_device_manager!(SimpleWindow);
+delegate_data_device!(SimpleWindow);
+delegate_data_offer!(SimpleWindow);
+delegate_data_source!(SimpleWindow);
+delegate_primary_selection!(SimpleWindow);
+delegate_presentation!(SimpleWindow);
+
 delegate_registry!(SimpleWindow);
 
 impl ProvidesRegistryState for SimpleWindow {
@@ -427,3 +1036,20 @@ impl ProvidesRegistryState for SimpleWindow {
     }
     registry_handlers![OutputState, SeatState,];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configure_keeps_the_size_the_compositor_leaves_open() {
+        let size = NonZeroU32::new;
+        assert_eq!(configured_size((None, None), (640, 480)), (640, 480));
+        assert_eq!(configured_size((size(800), None), (640, 480)), (800, 480));
+        assert_eq!(configured_size((None, size(300)), (640, 480)), (640, 300));
+        assert_eq!(
+            configured_size((size(1024), size(768)), (640, 480)),
+            (1024, 768)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    env,
+    fs::{self, DirBuilder, Permissions},
+    io,
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        net::UnixListener,
+    },
+    path::{Path, PathBuf},
+};
+
+/// Prefix of everything kept in the runtime directory, which other programs share.
+const RUNTIME_PREFIX: &str = "lockpad";
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+/// An absolute directory from `var`, falling back to `$HOME/<default>`.
+fn user_dir(var: &str, default: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home().map(|home| home.join(default)))
+}
+
+/// `$XDG_CONFIG_HOME`, defaulting to `~/.config`.
+pub(crate) fn config_home() -> Option<PathBuf> {
+    user_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`.
+pub(crate) fn data_home() -> Option<PathBuf> {
+    user_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// The data directories in order of preference: `$XDG_DATA_HOME` first, then `$XDG_DATA_DIRS`.
+pub(crate) fn data_dirs() -> Vec<PathBuf> {
+    let system = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home()
+        .into_iter()
+        .chain(
+            system
+                .split(':')
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute()),
+        )
+        .collect()
+}
+
+/// `$XDG_RUNTIME_DIR`, or a directory of this user's in the temporary directory when it is not
+/// set, as the sockets kept here must not be reachable by other users.
+pub(crate) fn runtime_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+    {
+        return Ok(dir);
+    }
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("{RUNTIME_PREFIX}-{uid}"));
+    tracing::warn!("XDG_RUNTIME_DIR is not set, using {}", dir.display());
+    private_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+/// Where the socket called `name` goes in the runtime directory.
+pub(crate) fn runtime_socket(name: &str) -> io::Result<PathBuf> {
+    Ok(runtime_dir()?.join(format!("{RUNTIME_PREFIX}-{name}.sock")))
+}
+
+/// Listen on `path`, connectable by this user only.
+pub(crate) fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Create `dir` accessible by `uid` only, or check that it already is.
+fn private_dir(dir: &Path, uid: u32) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(error) => return Err(error),
+    }
+    // Anyone can leave a directory or a symlink with this name in the temporary directory.
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not private to this user", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("xdg-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn uid() -> u32 {
+        unsafe { libc::getuid() }
+    }
+
+    #[test]
+    fn private_dir_is_created_for_this_user_only() {
+        let dir = temp("created");
+        private_dir(&dir, uid()).unwrap();
+        let mode = fs::metadata(&dir).unwrap().mode();
+        // Reused as it is.
+        private_dir(&dir, uid()).unwrap();
+        fs::remove_dir(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[test]
+    fn private_dir_refuses_what_others_could_use() {
+        let open = temp("open");
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&open, Permissions::from_mode(0o755)).unwrap();
+        let link = temp("link");
+        symlink(&open, &link).unwrap();
+        fs::set_permissions(&open, Permissions::from_mode(0o700)).unwrap();
+
+        let foreign = private_dir(&open, uid() + 1);
+        let linked = private_dir(&link, uid());
+        fs::set_permissions(&open, Permissions::from_mode(0o755)).unwrap();
+        let readable = private_dir(&open, uid());
+        fs::remove_file(&link).unwrap();
+        fs::remove_dir(&open).unwrap();
+
+        for result in [foreign, linked, readable] {
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        }
+    }
+
+    #[test]
+    fn sockets_are_bound_private() {
+        let dir = temp("socket");
+        private_dir(&dir, uid()).unwrap();
+        let path = dir.join("test.sock");
+        let listener = bind_private(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().mode();
+        drop(listener);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
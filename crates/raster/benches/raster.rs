@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use raster::{fill, Gradient};
+
+/// A bar, the example window, and full screens.
+const SIZES: [(&str, usize, usize); 4] = [
+    ("bar 1920x32", 1920, 32),
+    ("window 640x480", 640, 480),
+    ("1080p", 1920, 1080),
+    ("4k", 3840, 2160),
+];
+
+/// Time `draw` filling a whole surface of each size, given its pixels, width and height.
+fn sizes(c: &mut Criterion, name: &str, draw: impl Fn(&mut [u8], usize, usize)) {
+    let mut group = c.benchmark_group(name);
+    for (size, width, height) in SIZES {
+        let mut pixels = vec![0; width * height * 4];
+        group.throughput(Throughput::Elements((width * height) as u64));
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| draw(black_box(&mut pixels), width, height))
+        });
+    }
+    group.finish();
+}
+
+/// The gradient as it was drawn before, one pixel at a time, for comparison.
+fn per_pixel(x: u32, y: u32, width: u32, height: u32) -> u32 {
+    let a = 0xFF;
+    let r = u32::min(((width - x) * 0xFF) / width, ((height - y) * 0xFF) / height);
+    let g = u32::min((x * 0xFF) / width, ((height - y) * 0xFF) / height);
+    let b = u32::min(((width - x) * 0xFF) / width, (y * 0xFF) / height);
+    (a << 24) + (r << 16) + (g << 8) + b
+}
+
+fn benches(c: &mut Criterion) {
+    let shift = 17;
+    sizes(c, "fill", |pixels, width, height| {
+        fill(pixels, width, 0..height, 0..width, 0xFF_20_20_28);
+    });
+    sizes(c, "gradient", |pixels, width, height| {
+        let gradient = Gradient::new(width as u32, height as u32, shift);
+        gradient.fill(pixels, width, 0..height, 0..width);
+    });
+    sizes(c, "gradient per pixel", |pixels, width, height| {
+        let (width, height) = (width as u32, height as u32);
+        for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            let color = per_pixel((x + shift) % width, y, width, height);
+            pixel.copy_from_slice(&color.to_le_bytes());
+        }
+    });
+}
+
+criterion_group!(raster, benches);
+criterion_main!(raster);
@@ -0,0 +1,251 @@
+//! Filling large areas of premultiplied ARGB8888 pixels quickly.
+//!
+//! Areas are split into bands of rows that are filled in parallel, and each row is written by
+//! loops over fixed groups of pixels without divisions, which the compiler turns into vector
+//! instructions.
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+/// Rows filled by one task.
+const BAND_ROWS: usize = 32;
+/// Smaller areas are filled on the calling thread, as handing them out costs more than it
+/// saves.
+const PARALLEL_PIXELS: usize = 256 * 256;
+/// Pixels written by one iteration of the row loops.
+const LANES: usize = 8;
+
+/// Call `paint` with the bytes of `columns` in each of `rows`, of pixels laid out `width` to a
+/// row, in parallel bands when the area is large.
+///
+/// `paint` gets the row's index along with it.
+pub fn for_each_row(
+    pixels: &mut [u8],
+    width: usize,
+    rows: Range<usize>,
+    columns: Range<usize>,
+    paint: impl Fn(usize, &mut [u8]) + Sync,
+) {
+    if rows.is_empty() || columns.is_empty() {
+        return;
+    }
+    let stride = width * 4;
+    let area = &mut pixels[rows.start * stride..rows.end * stride];
+    let band = |(index, band): (usize, &mut [u8])| {
+        for (offset, row) in band.chunks_exact_mut(stride).enumerate() {
+            let y = rows.start + index * BAND_ROWS + offset;
+            paint(y, &mut row[columns.start * 4..columns.end * 4]);
+        }
+    };
+    if rows.len() * columns.len() < PARALLEL_PIXELS {
+        area.chunks_mut(stride * BAND_ROWS)
+            .enumerate()
+            .for_each(band);
+    } else {
+        area.par_chunks_mut(stride * BAND_ROWS)
+            .enumerate()
+            .for_each(band);
+    }
+}
+
+/// Write `color` to every pixel of `row`.
+pub fn fill_row(row: &mut [u8], color: u32) {
+    let pixel = color.to_le_bytes();
+    let lanes: [u8; 4 * LANES] = std::array::from_fn(|i| pixel[i % 4]);
+    let mut chunks = row.chunks_exact_mut(4 * LANES);
+    for chunk in &mut chunks {
+        chunk.copy_from_slice(&lanes);
+    }
+    for chunk in chunks.into_remainder().chunks_exact_mut(4) {
+        chunk.copy_from_slice(&pixel);
+    }
+}
+
+/// Write `color` to every pixel of `columns` in `rows`, without blending.
+pub fn fill(
+    pixels: &mut [u8],
+    width: usize,
+    rows: Range<usize>,
+    columns: Range<usize>,
+    color: u32,
+) {
+    for_each_row(pixels, width, rows, columns, |_, row| fill_row(row, color));
+}
+
+/// The RGB gradient shown when there is nothing else to draw: red fading out to the right and
+/// bottom, green in towards the top right and blue towards the bottom left.
+///
+/// Each channel is the smaller of one value per column and one per row, so those are worked
+/// out once and a row is the bytewise minimum of the columns and that row's limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gradient {
+    /// Each column's pixel before the row limits it, falling from 255 at the left edge
+    /// towards 0 at the right in red and blue, and rising in green.
+    columns: Vec<u8>,
+    /// The most each row allows of blue, green and red, in the order of pixel bytes.
+    rows: Vec<[u8; 4]>,
+}
+
+impl Gradient {
+    /// A gradient over `width` by `height` pixels, scrolled left by `shift` columns and
+    /// wrapping around.
+    pub fn new(width: u32, height: u32, shift: u32) -> Self {
+        let fall = |at: u32, size: u32| ((size - at) * 0xFF / size) as u8;
+        let rise = |at: u32, size: u32| (at * 0xFF / size) as u8;
+        let columns = (0..width)
+            .flat_map(|x| {
+                let x = (x + shift) % width;
+                [fall(x, width), rise(x, width), fall(x, width), 0xFF]
+            })
+            .collect();
+        let rows = (0..height)
+            .map(|y| [rise(y, height), fall(y, height), fall(y, height), 0xFF])
+            .collect();
+        Gradient { columns, rows }
+    }
+
+    /// The opaque color of one pixel.
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        let column = &self.columns[x * 4..x * 4 + 4];
+        u32::from_le_bytes(std::array::from_fn(|i| column[i].min(self.rows[y][i])))
+    }
+
+    /// Write row `y` to `row`, starting at column `x`.
+    pub fn row(&self, y: usize, x: usize, row: &mut [u8]) {
+        let limit = self.rows[y];
+        let lanes: [u8; 4 * LANES] = std::array::from_fn(|i| limit[i % 4]);
+        let columns = &self.columns[x * 4..x * 4 + row.len()];
+
+        let mut chunks = row.chunks_exact_mut(4 * LANES);
+        let mut sources = columns.chunks_exact(4 * LANES);
+        for (chunk, source) in (&mut chunks).zip(&mut sources) {
+            for ((byte, &source), &limit) in chunk.iter_mut().zip(source).zip(&lanes) {
+                *byte = source.min(limit);
+            }
+        }
+        let remainder = chunks.into_remainder();
+        for ((byte, &source), &limit) in remainder.iter_mut().zip(sources.remainder()).zip(&lanes) {
+            *byte = source.min(limit);
+        }
+    }
+
+    /// Write the gradient to every pixel of `columns` in `rows`.
+    pub fn fill(&self, pixels: &mut [u8], width: usize, rows: Range<usize>, columns: Range<usize>) {
+        let x = columns.start;
+        for_each_row(pixels, width, rows, columns, |y, row| self.row(y, x, row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The gradient one pixel at a time, as it was drawn before [`Gradient`].
+    fn per_pixel(x: u32, y: u32, width: u32, height: u32) -> u32 {
+        let a = 0xFF;
+        let r = u32::min(((width - x) * 0xFF) / width, ((height - y) * 0xFF) / height);
+        let g = u32::min((x * 0xFF) / width, ((height - y) * 0xFF) / height);
+        let b = u32::min(((width - x) * 0xFF) / width, (y * 0xFF) / height);
+        (a << 24) + (r << 16) + (g << 8) + b
+    }
+
+    fn pixel(pixels: &[u8], width: usize, x: usize, y: usize) -> u32 {
+        let at = (y * width + x) * 4;
+        u32::from_le_bytes(pixels[at..at + 4].try_into().unwrap())
+    }
+
+    /// Whole surfaces and partial ones, with widths on and off [`LANES`] and large enough to
+    /// be filled in parallel.
+    const AREAS: [(usize, usize, Range<usize>, Range<usize>); 6] = [
+        (1, 1, 0..1, 0..1),
+        (7, 3, 0..3, 0..7),
+        (37, 19, 2..17, 3..29),
+        (64, 40, 5..40, 1..64),
+        (300, 250, 0..250, 0..300),
+        (333, 301, 17..299, 9..325),
+    ];
+
+    #[test]
+    fn gradient_rows_match_the_per_pixel_formula() {
+        for width in [1, 5, 8, 13, 16, 33, 100] {
+            for shift in [0, 3, 17] {
+                let gradient = Gradient::new(width, 9, shift);
+                for y in 0..9 {
+                    for x in [0, 1, width as usize / 2] {
+                        let mut row = vec![0; (width as usize - x) * 4];
+                        gradient.row(y, x, &mut row);
+                        for (column, pixel) in row.chunks_exact(4).enumerate() {
+                            let shifted = (x + column) as u32 + shift;
+                            assert_eq!(
+                                u32::from_le_bytes(pixel.try_into().unwrap()),
+                                per_pixel(shifted % width, y as u32, width, 9),
+                                "width {width} shift {shift} at {}, {y}",
+                                x + column
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_fill_stays_inside_the_area() {
+        let shift = 5;
+        for (width, height, rows, columns) in AREAS {
+            let gradient = Gradient::new(width as u32, height as u32, shift);
+            let mut pixels = vec![0; width * height * 4];
+            gradient.fill(&mut pixels, width, rows.clone(), columns.clone());
+            for y in 0..height {
+                for x in 0..width {
+                    let shifted = (x as u32 + shift) % width as u32;
+                    let color = per_pixel(shifted, y as u32, width as u32, height as u32);
+                    assert_eq!(gradient.pixel(x, y), color);
+                    let expected = if rows.contains(&y) && columns.contains(&x) {
+                        color
+                    } else {
+                        0
+                    };
+                    assert_eq!(
+                        pixel(&pixels, width, x, y),
+                        expected,
+                        "{width}x{height} at {x}, {y}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fill_stays_inside_the_area() {
+        let color = 0x80_40_20_10;
+        for (width, height, rows, columns) in AREAS {
+            let mut pixels = vec![0; width * height * 4];
+            fill(&mut pixels, width, rows.clone(), columns.clone(), color);
+            for y in 0..height {
+                for x in 0..width {
+                    let expected = if rows.contains(&y) && columns.contains(&x) {
+                        color
+                    } else {
+                        0
+                    };
+                    assert_eq!(
+                        pixel(&pixels, width, x, y),
+                        expected,
+                        "{width}x{height} at {x}, {y}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_ranges_write_nothing() {
+        let mut pixels = vec![0; 16 * 4 * 4];
+        fill(&mut pixels, 16, 2..2, 0..16, 0xFFFF_FFFF);
+        fill(&mut pixels, 16, 0..4, 5..5, 0xFFFF_FFFF);
+        Gradient::new(16, 4, 0).fill(&mut pixels, 16, 3..3, 0..16);
+        assert!(pixels.iter().all(|&byte| byte == 0));
+    }
+}